@@ -1,5 +1,7 @@
 use criterion::{Criterion, Throughput, criterion_group, criterion_main};
 use difa::RecordStream;
+use difa::borrowed::RecordParser;
+use difa::parallel::ParallelParser;
 use futures::StreamExt;
 use std::hint::black_box;
 
@@ -35,6 +37,38 @@ fn parse(c: &mut Criterion) {
     }
 
     group.finish();
+
+    let mut group = c.benchmark_group("parse_borrowed");
+
+    for (size, data) in &datasets {
+        group.throughput(Throughput::Elements(*size as u64));
+        group.bench_function(format!("{}", size), |b| {
+            b.iter(|| {
+                let mut count = 0;
+                for result in RecordParser::new(&data[..], true) {
+                    let _ = result.unwrap();
+                    count += 1;
+                }
+                black_box(count)
+            });
+        });
+    }
+
+    group.finish();
+
+    let mut group = c.benchmark_group("parse_parallel");
+
+    for (size, data) in &datasets {
+        group.throughput(Throughput::Elements(*size as u64));
+        group.bench_function(format!("{}", size), |b| {
+            b.iter(|| {
+                let records = ParallelParser::new(&data[..], true).parse();
+                black_box(records.unwrap().len())
+            });
+        });
+    }
+
+    group.finish();
 }
 
 criterion_group!(benches, parse);
@@ -0,0 +1,259 @@
+//! Derive macro for the `difa` crate
+//!
+//! This crate is not meant to be used directly.  Enable the `derive`
+//! feature of `difa` and use `difa::AdifRecord` instead.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments,
+    Type, parse_macro_input,
+};
+
+/// Derive conversions between a struct and an ADIF record.
+///
+/// See the documentation of `difa::AdifRecord`.
+#[proc_macro_derive(AdifRecord, attributes(adif))]
+pub fn derive_adif_record(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A field of the struct and how it maps to the record
+struct Field<'a> {
+    ident: &'a Ident,
+    name: String,
+    typ: Option<TokenStream2>,
+    kind: Kind<'a>,
+}
+
+enum Kind<'a> {
+    Required(&'a Type),
+    Optional(&'a Type),
+    Extra,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "AdifRecord can only be derived for structs",
+        ));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &data.fields,
+            "AdifRecord requires named fields",
+        ));
+    };
+    let fields = named
+        .named
+        .iter()
+        .map(parse_field)
+        .collect::<syn::Result<Vec<_>>>()?;
+    let mut extras = fields.iter().filter(|f| matches!(f.kind, Kind::Extra));
+    let extra = extras.next().map(|f| f.ident);
+    if let Some(f) = extras.next() {
+        return Err(syn::Error::new_spanned(
+            f.ident,
+            "only one field may hold the extra fields",
+        ));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+    let try_from = try_from(&fields, extra);
+    let into = into(&fields, extra);
+    Ok(quote! {
+        impl #impl_generics ::core::convert::TryFrom<::difa::Record>
+            for #ident #ty_generics #where_clause
+        {
+            type Error = ::difa::Error;
+
+            fn try_from(
+                __record: ::difa::Record,
+            ) -> ::core::result::Result<Self, ::difa::Error> {
+                #try_from
+            }
+        }
+
+        impl #impl_generics ::core::convert::From<#ident #ty_generics>
+            for ::difa::Record #where_clause
+        {
+            fn from(__value: #ident #ty_generics) -> Self {
+                #into
+            }
+        }
+    })
+}
+
+fn try_from(fields: &[Field], extra: Option<&Ident>) -> TokenStream2 {
+    let reads = fields.iter().map(|f| {
+        let Field {
+            ident, name, typ, ..
+        } = f;
+        let typ = indicator(typ);
+        match f.kind {
+            Kind::Required(ty) => quote! {
+                let #ident = ::difa::__private::required::<#ty>(
+                    &__record, #name, #typ,
+                )?;
+            },
+            Kind::Optional(ty) => quote! {
+                let #ident = ::difa::__private::optional::<#ty>(
+                    &__record, #name, #typ,
+                )?;
+            },
+            Kind::Extra => quote! {},
+        }
+    });
+    let names = fields
+        .iter()
+        .filter(|f| !matches!(f.kind, Kind::Extra))
+        .map(|f| &f.name);
+    let keep = extra.map(|extra| {
+        quote! {
+            let __known: &[&str] = &[#(#names),*];
+            let #extra = ::difa::__private::remaining(__record, __known);
+        }
+    });
+    let idents = fields.iter().map(|f| f.ident);
+    quote! {
+        #(#reads)*
+        #keep
+        ::core::result::Result::Ok(Self { #(#idents),* })
+    }
+}
+
+fn into(fields: &[Field], extra: Option<&Ident>) -> TokenStream2 {
+    let record = match extra {
+        Some(extra) => quote! { __value.#extra },
+        None => quote! { ::difa::Record::new() },
+    };
+    let writes = fields.iter().map(|f| {
+        let Field {
+            ident, name, typ, ..
+        } = f;
+        let typ = indicator(typ);
+        match f.kind {
+            Kind::Required(_) => quote! {
+                ::difa::__private::put(&mut __record, #name, __value.#ident, #typ);
+            },
+            Kind::Optional(_) => quote! {
+                if let ::core::option::Option::Some(__v) = __value.#ident {
+                    ::difa::__private::put(&mut __record, #name, __v, #typ);
+                }
+            },
+            Kind::Extra => quote! {},
+        }
+    });
+    quote! {
+        let mut __record = #record;
+        #(#writes)*
+        __record
+    }
+}
+
+fn indicator(typ: &Option<TokenStream2>) -> TokenStream2 {
+    match typ {
+        Some(typ) => quote! {
+            ::core::option::Option::Some(::difa::DataType::#typ)
+        },
+        None => quote! { ::core::option::Option::None },
+    }
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<Field<'_>> {
+    let Some(ident) = &field.ident else {
+        return Err(syn::Error::new_spanned(field, "field has no name"));
+    };
+    let mut name = None;
+    let mut typ = None;
+    let mut extra = false;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("adif")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                let s: LitStr = meta.value()?.parse()?;
+                if s.value().is_empty() {
+                    return Err(meta.error("field name cannot be empty"));
+                }
+                name = Some(s.value());
+            } else if meta.path.is_ident("type") {
+                let s: LitStr = meta.value()?.parse()?;
+                typ = Some(data_type(&s)?);
+            } else if meta.path.is_ident("extra") {
+                extra = true;
+            } else {
+                return Err(meta.error("unknown adif attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let kind = if extra {
+        if name.is_some() || typ.is_some() {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "extra field cannot have a name or type",
+            ));
+        }
+        Kind::Extra
+    } else {
+        match option_inner(&field.ty) {
+            Some(ty) => Kind::Optional(ty),
+            None => Kind::Required(&field.ty),
+        }
+    };
+    let name = name.unwrap_or_else(|| {
+        let name = ident.to_string();
+        name.strip_prefix("r#").map_or(name.clone(), str::to_string)
+    });
+    Ok(Field {
+        ident,
+        name,
+        typ,
+        kind,
+    })
+}
+
+/// Map an ADIF type indicator to the variant of `DataType`.
+fn data_type(s: &LitStr) -> syn::Result<TokenStream2> {
+    let variant = match s.value().to_ascii_lowercase().as_str() {
+        "b" => quote! { Boolean },
+        "n" => quote! { Number },
+        "d" => quote! { Date },
+        "t" => quote! { Time },
+        "s" => quote! { String },
+        "i" => quote! { IntlString },
+        "m" => quote! { MultilineString },
+        "g" => quote! { IntlMultilineString },
+        "e" => quote! { Enumeration },
+        "l" => quote! { Location },
+        _ => {
+            return Err(syn::Error::new_spanned(s, "unknown ADIF data type"));
+        }
+    };
+    Ok(variant)
+}
+
+/// Return the `T` of a field of type `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let last = path.path.segments.last()?;
+    if last.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &last.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(ty) if args.args.len() == 1 => Some(ty),
+        _ => None,
+    }
+}
@@ -1,6 +1,5 @@
-use difa::{NormalizeExt, RecordStream};
+use difa::{RecordStream, filter};
 use futures::StreamExt;
-use rust_decimal::prelude::*;
 
 const SAMPLE_ADIF: &[u8] = b"\
 This is some ADIF data from WhizzBangHamLogger version 12345!!!
@@ -14,36 +13,12 @@ This is some ADIF data from WhizzBangHamLogger version 12345!!!
 <call:4>W6RQ<qso_date:8>20240116<time_on:6>120000<freq:6>21.074<mode:3>FT4<eor>
 ";
 
-fn freq_to_band(freq: Decimal) -> Option<&'static str> {
-    let mhz = freq.to_u32()?;
-    match mhz {
-        7 => Some("40M"),
-        14 => Some("20M"),
-        21 => Some("15M"),
-        28 | 29 => Some("10M"),
-        _ => panic!(),
-    }
-}
-
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let stream = RecordStream::new(SAMPLE_ADIF, true);
 
     // Add a band field if none exists.
-    let mut stream = stream.normalize(|record| {
-        if record.is_header() {
-            return Ok(());
-        }
-        if record.get("band").is_some() {
-            return Ok(());
-        }
-        if let Some(freq) = record.get("freq").and_then(|f| f.as_number())
-            && let Some(band) = freq_to_band(freq)
-        {
-            record.insert("band", band)?;
-        }
-        Ok(())
-    });
+    let mut stream = filter::derive_band(stream);
 
     while let Some(result) = stream.next().await {
         let record = result?;
@@ -0,0 +1,869 @@
+//! Reading and writing ADX, the XML encoding of ADIF
+//!
+//! ADX documents are decoded into the same [Tag] items as ADI files, so
+//! they can be aggregated with [RecordStream] and passed through every
+//! adapter in the [filter](crate::filter) module unchanged.  Records can
+//! be written back out as ADX with an [AdxSink].
+
+use crate::parse::{RecordStream, RecordStreamExt, lossy, parse_field_value};
+use crate::write::{OutputTypes, TagEncoder};
+use crate::{
+    Datum, Error, Field, InvalidFormatKind, Position, Record, Span, Tag,
+};
+use bytes::{Buf, BufMut, BytesMut};
+use futures::sink::Sink;
+use std::borrow::Cow;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
+
+#[cfg(test)]
+mod test;
+
+/// Stream of ADIF tags read from an ADX document.
+pub type AdxStream<R> = FramedRead<R, AdxDecoder>;
+
+fn markup<M>(message: M) -> InvalidFormatKind
+where
+    M: Into<Cow<'static, str>>,
+{
+    InvalidFormatKind::Other(message.into())
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    #[default]
+    Prolog,
+    Adx,
+    Header,
+    Records,
+    Record,
+    Done,
+}
+
+#[derive(Debug)]
+enum Markup<'a> {
+    Text(&'a [u8]),
+    CData(&'a [u8]),
+    Start {
+        name: &'a str,
+        attrs: &'a str,
+        empty: bool,
+    },
+    End(&'a str),
+    Skip,
+}
+
+/// Field element whose content is still being read.
+#[derive(Debug)]
+struct Pending {
+    position: Position,
+    header: bool,
+    element: String,
+    name: String,
+    typ: Option<String>,
+    suffix: Option<String>,
+    value: String,
+}
+
+/// Decoder for reading ADIF tags from an ADX document.
+///
+/// Each child of `<HEADER>` and `<RECORD>` becomes a field.  `<APP>`
+/// elements are named `APP_{PROGRAMID}_{FIELDNAME}` and typed according
+/// to their `TYPE` attribute, record `<USERDEF>` elements take the name in
+/// their `FIELDNAME` attribute, and header `<USERDEF>` elements become
+/// `USERDEFn` fields whose value is written as in ADI files.  The
+/// [span](Field::span) of a field runs from its start tag to its end tag.
+#[derive(Debug, Default)]
+pub struct AdxDecoder {
+    ignore_partial: bool,
+    section: Section,
+    pending: Option<Pending>,
+    // field whose end tag was just read, to be returned once consumed
+    finished: Option<Pending>,
+    consumed: usize,
+    line: usize,
+    column: usize,
+}
+
+impl AdxDecoder {
+    /// Create a new AdxDecoder.
+    ///
+    /// See [new_stream](Self::new_stream) for the meaning of
+    /// `ignore_partial`.
+    pub fn new(ignore_partial: bool) -> Self {
+        Self {
+            ignore_partial,
+            line: 1,
+            column: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Create a new stream that returns ADIF tags from an ADX document.
+    ///
+    /// If `ignore_partial` is `true`, an unterminated document will be
+    /// silently ignored.  Set it to `false` to get an error in this
+    /// situation.
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::AdxDecoder;
+    /// use futures::StreamExt;
+    /// let adx = "<ADX><RECORDS><RECORD><CALL>W1AW</CALL></RECORD></RECORDS></ADX>";
+    /// let mut t = AdxDecoder::new_stream(adx.as_bytes(), true);
+    /// let tag = t.next().await.unwrap().unwrap();
+    /// let field = tag.as_field().unwrap();
+    /// assert_eq!(field.name(), "CALL");
+    /// assert_eq!(field.value().as_str(), "W1AW");
+    /// assert!(t.next().await.unwrap().unwrap().is_eor());
+    /// # });
+    /// ```
+    pub fn new_stream<R>(reader: R, ignore_partial: bool) -> AdxStream<R>
+    where
+        R: AsyncRead,
+    {
+        FramedRead::new(reader, Self::new(ignore_partial))
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+            byte: self.consumed,
+        }
+    }
+
+    fn invalid(&self, kind: InvalidFormatKind) -> Error {
+        Error::InvalidFormat {
+            kind,
+            position: self.position(),
+        }
+    }
+
+    fn advance(&mut self, src: &mut BytesMut, consumed: usize) {
+        for &byte in &src[..consumed] {
+            if byte == b'\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.consumed += consumed;
+        src.advance(consumed);
+    }
+
+    fn start_field(
+        &self, element: &str, attrs: &str,
+    ) -> Result<Pending, InvalidFormatKind> {
+        let attrs = parse_attrs(attrs)?;
+        let attr = |key: &str| {
+            attrs
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.to_string())
+        };
+        let require = |key: &str| {
+            attr(key).ok_or_else(|| {
+                markup(format!("{element} element missing {key}"))
+            })
+        };
+
+        let mut pending = Pending {
+            position: self.position(),
+            header: self.section == Section::Header,
+            element: element.to_string(),
+            name: element.to_string(),
+            typ: None,
+            suffix: None,
+            value: String::new(),
+        };
+        if element.eq_ignore_ascii_case("app") {
+            let program = require("PROGRAMID")?;
+            let field = require("FIELDNAME")?;
+            pending.name = format!("APP_{program}_{field}");
+            pending.typ = attr("TYPE");
+        } else if element.eq_ignore_ascii_case("userdef") {
+            if self.section == Section::Header {
+                pending.name = format!("USERDEF{}", require("FIELDID")?);
+                pending.typ = attr("TYPE");
+                pending.suffix = attr("ENUM").or_else(|| attr("RANGE"));
+            } else {
+                pending.name = require("FIELDNAME")?;
+            }
+        }
+        Ok(pending)
+    }
+
+    fn finish_field(&self, pending: Pending) -> Result<Tag, Error> {
+        let Pending {
+            position,
+            header,
+            name,
+            typ,
+            suffix,
+            mut value,
+            ..
+        } = pending;
+        if let Some(suffix) = suffix {
+            value.push(',');
+            value.push_str(&suffix);
+        }
+        let value = parse_field_value(&name, &value, typ.as_deref(), header)
+            .map_err(|kind| Error::InvalidFormat { kind, position })?;
+        let span = Span {
+            start: position,
+            end: self.position(),
+        };
+        Ok(Tag::Field(Field::new(name, value).with_span(span)))
+    }
+
+    fn start(
+        &mut self, name: &str, attrs: &str, empty: bool,
+    ) -> Result<Option<Tag>, Error> {
+        let is = |s: &str| name.eq_ignore_ascii_case(s);
+        let tag = match self.section {
+            Section::Prolog if is("adx") => {
+                self.section = if empty { Section::Done } else { Section::Adx };
+                None
+            }
+            Section::Adx if is("header") => {
+                if empty {
+                    return Ok(Some(Tag::Eoh));
+                }
+                self.section = Section::Header;
+                None
+            }
+            Section::Adx if is("records") => {
+                if !empty {
+                    self.section = Section::Records;
+                }
+                None
+            }
+            Section::Records if is("record") => {
+                if empty {
+                    return Ok(Some(Tag::Eor));
+                }
+                self.section = Section::Record;
+                None
+            }
+            Section::Header | Section::Record if self.pending.is_none() => {
+                let pending = self
+                    .start_field(name, attrs)
+                    .map_err(|m| self.invalid(m))?;
+                if empty {
+                    self.finished = Some(pending);
+                } else {
+                    self.pending = Some(pending);
+                }
+                None
+            }
+            _ => {
+                let message = format!("unexpected element {name}");
+                return Err(self.invalid(markup(message)));
+            }
+        };
+        Ok(tag)
+    }
+
+    fn end(&mut self, name: &str) -> Result<Option<Tag>, Error> {
+        let is = |s: &str| name.eq_ignore_ascii_case(s);
+        let (section, tag) = match (self.pending.take(), self.section) {
+            (Some(pending), _) if is(&pending.element) => {
+                self.finished = Some(pending);
+                return Ok(None);
+            }
+            (None, Section::Header) if is("header") => {
+                (Section::Adx, Some(Tag::Eoh))
+            }
+            (None, Section::Record) if is("record") => {
+                (Section::Records, Some(Tag::Eor))
+            }
+            (None, Section::Records) if is("records") => (Section::Adx, None),
+            (None, Section::Adx) if is("adx") => (Section::Done, None),
+            _ => {
+                let message = format!("mismatched end tag {name}");
+                return Err(self.invalid(markup(message)));
+            }
+        };
+        self.section = section;
+        Ok(tag)
+    }
+
+    fn text(
+        &mut self, text: &[u8], escaped: bool,
+    ) -> Result<(), InvalidFormatKind> {
+        let Some(pending) = self.pending.as_mut() else {
+            // text between elements is insignificant
+            return Ok(());
+        };
+        let text = as_str(text, Some(&pending.name))?;
+        if escaped {
+            unescape(text, &mut pending.value)?;
+        } else {
+            pending.value.push_str(text);
+        }
+        Ok(())
+    }
+
+    fn process(&mut self, markup: Markup<'_>) -> Result<Option<Tag>, Error> {
+        let text = |this: &mut Self, text, escaped| {
+            this.text(text, escaped)
+                .map(|_| None)
+                .map_err(|m| this.invalid(m))
+        };
+        match markup {
+            Markup::Text(t) => text(self, t, true),
+            Markup::CData(t) => text(self, t, false),
+            Markup::Start { name, attrs, empty } => {
+                self.start(name, attrs, empty)
+            }
+            Markup::End(name) => self.end(name),
+            Markup::Skip => Ok(None),
+        }
+    }
+
+    fn decode_inner(
+        &mut self, src: &mut BytesMut,
+    ) -> Result<Option<Tag>, Error> {
+        loop {
+            let Some((markup, n)) = scan(src).map_err(|m| self.invalid(m))?
+            else {
+                return Ok(None);
+            };
+            let tag = self.process(markup)?;
+            self.advance(src, n);
+            if let Some(pending) = self.finished.take() {
+                return self.finish_field(pending).map(Some);
+            } else if tag.is_some() {
+                return Ok(tag);
+            }
+        }
+    }
+
+    fn decode(
+        &mut self, src: &mut BytesMut, eof: bool,
+    ) -> Result<Option<Tag>, Error> {
+        let tag = self.decode_inner(src)?;
+        if tag.is_some() || !eof {
+            return Ok(tag);
+        }
+        let trailing = src.iter().any(|b| !b.is_ascii_whitespace());
+        let open = !matches!(self.section, Section::Prolog | Section::Done);
+        if trailing || open {
+            return Err(self.invalid(InvalidFormatKind::PartialData));
+        }
+        Ok(None)
+    }
+}
+
+impl Decoder for AdxDecoder {
+    type Item = Tag;
+    type Error = Error;
+
+    fn decode(
+        &mut self, src: &mut BytesMut,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode(src, false)
+    }
+
+    fn decode_eof(
+        &mut self, src: &mut BytesMut,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode(src, !self.ignore_partial)
+    }
+}
+
+impl<R> RecordStream<AdxStream<R>>
+where
+    R: AsyncRead,
+{
+    /// Create a new stream that returns ADIF records from an ADX document.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::RecordStream;
+    /// use futures::StreamExt;
+    /// let adx = r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <ADX>
+    ///   <HEADER><ADIF_VER>3.1.4</ADIF_VER></HEADER>
+    ///   <RECORDS>
+    ///     <RECORD>
+    ///       <CALL>W1AW</CALL>
+    ///       <APP PROGRAMID="LOTW" FIELDNAME="MODEGROUP" TYPE="S">DATA</APP>
+    ///     </RECORD>
+    ///   </RECORDS>
+    /// </ADX>"#;
+    /// let mut r = RecordStream::new_adx(adx.as_bytes(), true);
+    /// let header = r.next().await.unwrap().unwrap();
+    /// assert_eq!(header.get("adif_ver").unwrap().as_str(), "3.1.4");
+    /// let rec = r.next().await.unwrap().unwrap();
+    /// assert_eq!(rec.get("call").unwrap().as_str(), "W1AW");
+    /// assert_eq!(rec.get("app_lotw_modegroup").unwrap().as_str(), "DATA");
+    /// # });
+    /// ```
+    pub fn new_adx(reader: R, ignore_partial: bool) -> Self {
+        AdxDecoder::new_stream(reader, ignore_partial).records()
+    }
+}
+
+enum Item {
+    Record(Record),
+    Eof,
+}
+
+struct AdxEncoder {
+    types: TagEncoder,
+    userdefs: Vec<String>,
+    started: bool,
+    header: bool,
+    records: bool,
+    finished: bool,
+}
+
+impl AdxEncoder {
+    fn new(types: OutputTypes) -> Self {
+        Self {
+            types: TagEncoder::with_types(types),
+            userdefs: Vec::new(),
+            started: false,
+            header: false,
+            records: false,
+            finished: false,
+        }
+    }
+
+    fn start(&mut self, dst: &mut BytesMut) {
+        if !self.started {
+            self.started = true;
+            dst.put_slice(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            dst.put_slice(b"<ADX>\n");
+        }
+    }
+
+    fn open_records(&mut self, dst: &mut BytesMut) {
+        if !self.records {
+            self.records = true;
+            dst.put_slice(b"  <RECORDS>\n");
+        }
+    }
+
+    fn put_attr(
+        dst: &mut BytesMut, name: &str, value: &str,
+    ) -> Result<(), Error> {
+        dst.put_u8(b' ');
+        dst.put_slice(name.as_bytes());
+        dst.put_slice(b"=\"");
+        escape(value, true, dst)?;
+        dst.put_u8(b'"');
+        Ok(())
+    }
+
+    fn put_element(
+        dst: &mut BytesMut, name: &str, attrs: &[(&str, &str)], value: &str,
+    ) -> Result<(), Error> {
+        dst.put_u8(b'<');
+        dst.put_slice(name.as_bytes());
+        for (k, v) in attrs {
+            Self::put_attr(dst, k, v)?;
+        }
+        dst.put_u8(b'>');
+        escape(value, false, dst)?;
+        dst.put_slice(b"</");
+        dst.put_slice(name.as_bytes());
+        dst.put_slice(b">\n");
+        Ok(())
+    }
+
+    fn encode_field(
+        &mut self, name: &str, value: &Datum, header: bool, dst: &mut BytesMut,
+    ) -> Result<(), Error> {
+        let typ = self.types.type_indicator(value)?;
+        let s = value.as_str();
+        let upper = name.to_ascii_uppercase();
+
+        if let Some((program, field)) = TagEncoder::check_app_name(name)? {
+            let typ = typ.map(|t| t.to_ascii_uppercase());
+            let mut attrs = vec![("PROGRAMID", program), ("FIELDNAME", field)];
+            if let Some(typ) = typ.as_deref() {
+                attrs.push(("TYPE", typ));
+            }
+            Self::put_element(dst, "APP", &attrs, &s)?;
+        } else if header && let Some(id) = upper.strip_prefix("USERDEF") {
+            let (field, values) = match s.split_once(',') {
+                Some((field, values)) => (field, Some(values)),
+                None => (s.as_ref(), None),
+            };
+            let typ = typ.map(|t| t.to_ascii_uppercase());
+            let mut attrs = vec![("FIELDID", id)];
+            if let Some(typ) = typ.as_deref() {
+                attrs.push(("TYPE", typ));
+            }
+            if let Some(values) = values {
+                let kind = if values.contains(':') {
+                    "RANGE"
+                } else {
+                    "ENUM"
+                };
+                attrs.push((kind, values));
+            }
+            Self::put_element(dst, "USERDEF", &attrs, field)?;
+            self.userdefs.push(field.to_string());
+        } else if self.userdefs.iter().any(|u| u.eq_ignore_ascii_case(name)) {
+            Self::put_element(dst, "USERDEF", &[("FIELDNAME", name)], &s)?;
+        } else {
+            if !is_name(name) {
+                return Err(Error::CannotOutput {
+                    typ: "field",
+                    reason: "field name is not a valid XML element name",
+                });
+            }
+            Self::put_element(dst, &upper, &[], &s)?;
+        }
+        Ok(())
+    }
+
+    fn encode_record(
+        &mut self, r: Record, dst: &mut BytesMut,
+    ) -> Result<(), Error> {
+        if r.is_header() {
+            if self.header {
+                return Err(Error::DuplicateHeader);
+            }
+            if self.records {
+                return Err(Error::CannotOutput {
+                    typ: "header",
+                    reason: "header must precede all records",
+                });
+            }
+            self.header = true;
+        }
+
+        // encode into a scratch buffer so that a field that cannot be
+        // output doesn't leave a partial record in the document
+        let mut buf = BytesMut::new();
+        let element: &[u8] = if r.is_header() {
+            b"  <HEADER>\n"
+        } else {
+            b"    <RECORD>\n"
+        };
+        buf.put_slice(element);
+        for (name, value) in r.output_fields() {
+            buf.put_slice(if r.is_header() { b"    " } else { b"      " });
+            self.encode_field(name, value, r.is_header(), &mut buf)?;
+        }
+        let element: &[u8] = if r.is_header() {
+            b"  </HEADER>\n"
+        } else {
+            b"    </RECORD>\n"
+        };
+        buf.put_slice(element);
+
+        self.start(dst);
+        if !r.is_header() {
+            self.open_records(dst);
+        }
+        dst.put_slice(&buf);
+        Ok(())
+    }
+}
+
+impl Encoder<Item> for AdxEncoder {
+    type Error = Error;
+
+    fn encode(
+        &mut self, item: Item, dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        match item {
+            Item::Record(r) => self.encode_record(r, dst),
+            Item::Eof => {
+                if self.finished {
+                    return Ok(());
+                }
+                self.finished = true;
+                self.start(dst);
+                self.open_records(dst);
+                dst.put_slice(b"  </RECORDS>\n</ADX>\n");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Sink for writing records to an async writer as an ADX document
+///
+/// The XML declaration and root element are written with the first
+/// record, and the document is completed when the sink is closed.  A
+/// header record, if any, must be sent before all other records.
+///
+/// Fields named `APP_{PROGRAMID}_{FIELD}` are written as `<APP>` elements,
+/// header fields named `USERDEFn` are written as `<USERDEF>` declarations,
+/// and record fields so declared are written as `<USERDEF>` elements.
+/// Other field names are written in uppercase as required by ADX.
+pub struct AdxSink<W> {
+    inner: FramedWrite<W, AdxEncoder>,
+}
+
+impl<W> AdxSink<W>
+where
+    W: AsyncWrite,
+{
+    /// Create a new AdxSink with default configuration.
+    ///
+    /// ```
+    /// use difa::{AdxSink, Record};
+    /// use futures::SinkExt;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let mut buf = Vec::new();
+    /// let mut sink = AdxSink::new(&mut buf);
+    ///
+    /// let mut record = Record::new();
+    /// record.insert("call", "W1AW").unwrap();
+    /// record.insert("comment", "<3").unwrap();
+    /// sink.send(record).await.unwrap();
+    /// sink.close().await.unwrap();
+    ///
+    /// let adx = String::from_utf8(buf).unwrap();
+    /// assert!(adx.contains("<CALL>W1AW</CALL>"));
+    /// assert!(adx.contains("<COMMENT>&lt;3</COMMENT>"));
+    /// # })
+    /// ```
+    pub fn new(writer: W) -> Self {
+        Self::with_types(writer, OutputTypes::default())
+    }
+
+    /// Create a new AdxSink with given type specifier behavior.
+    ///
+    /// Only `<APP>` elements carry a `TYPE` attribute chosen by this in
+    /// ADX, so it has no effect on other fields.  Header `<USERDEF>`
+    /// declarations carry the declared type as their `TYPE` attribute
+    /// whatever the behavior.
+    pub fn with_types(writer: W, types: OutputTypes) -> Self {
+        Self {
+            inner: FramedWrite::new(writer, AdxEncoder::new(types)),
+        }
+    }
+}
+
+impl<W> Sink<Record> for AdxSink<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    type Error = Error;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.inner).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, r: Record) -> Result<(), Error> {
+        Pin::new(&mut self.inner).start_send(Item::Record(r))
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Error>> {
+        ready!(Pin::new(&mut self.inner).poll_ready(cx))?;
+        Pin::new(&mut self.inner).start_send(Item::Eof)?;
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+}
+
+fn escape(s: &str, attr: bool, dst: &mut BytesMut) -> Result<(), Error> {
+    for c in s.chars() {
+        let escaped: &[u8] = match c {
+            '&' => b"&amp;",
+            '<' => b"&lt;",
+            '>' => b"&gt;",
+            '"' if attr => b"&quot;",
+            '\r' => b"&#13;",
+            '\n' if attr => b"&#10;",
+            '\t' if attr => b"&#9;",
+            // not allowed in XML 1.0, even as character references
+            '\0'..='\u{8}'
+            | '\u{b}'
+            | '\u{c}'
+            | '\u{e}'..='\u{1f}'
+            | '\u{fffe}'
+            | '\u{ffff}' => {
+                return Err(Error::CannotOutput {
+                    typ: "field",
+                    reason: "value contains characters not allowed in XML",
+                });
+            }
+            c => {
+                let mut buf = [0; 4];
+                dst.put_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+        };
+        dst.put_slice(escaped);
+    }
+    Ok(())
+}
+
+fn as_str<'a>(
+    data: &'a [u8], name: Option<&str>,
+) -> Result<&'a str, InvalidFormatKind> {
+    str::from_utf8(data).map_err(|_| InvalidFormatKind::BadEncoding {
+        name: name.map(str::to_string),
+        text: lossy(data),
+    })
+}
+
+fn find(src: &[u8], from: usize, pat: &[u8]) -> Option<usize> {
+    src.get(from..)?
+        .windows(pat.len())
+        .position(|w| w == pat)
+        .map(|i| from + i)
+}
+
+fn find_tag_end(src: &[u8]) -> Option<usize> {
+    let mut quote = None;
+    for (i, &b) in src.iter().enumerate() {
+        match quote {
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None if b == b'"' || b == b'\'' => quote = Some(b),
+            None if b == b'>' => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+/// Find the next complete piece of markup at the start of `src`.
+///
+/// Returns [None] if more data is needed.
+fn scan(src: &[u8]) -> Result<Option<(Markup<'_>, usize)>, InvalidFormatKind> {
+    const COMMENT: &[u8] = b"<!--";
+    const CDATA: &[u8] = b"<![CDATA[";
+
+    let Some(&first) = src.first() else {
+        return Ok(None);
+    };
+    if first != b'<' {
+        let Some(end) = src.iter().position(|&b| b == b'<') else {
+            return Ok(None);
+        };
+        return Ok(Some((Markup::Text(&src[..end]), end)));
+    }
+    if src.len() < CDATA.len()
+        && (CDATA.starts_with(src) || COMMENT.starts_with(src))
+    {
+        return Ok(None);
+    }
+
+    let (markup, n) = if src.starts_with(COMMENT) {
+        let Some(end) = find(src, COMMENT.len(), b"-->") else {
+            return Ok(None);
+        };
+        (Markup::Skip, end + 3)
+    } else if src.starts_with(CDATA) {
+        let Some(end) = find(src, CDATA.len(), b"]]>") else {
+            return Ok(None);
+        };
+        (Markup::CData(&src[CDATA.len()..end]), end + 3)
+    } else if src.starts_with(b"<?") {
+        let Some(end) = find(src, 2, b"?>") else {
+            return Ok(None);
+        };
+        (Markup::Skip, end + 2)
+    } else {
+        let Some(end) = find_tag_end(src) else {
+            return Ok(None);
+        };
+        let inner = as_str(&src[1..end], None)?;
+        let markup = if inner.starts_with('!') {
+            Markup::Skip
+        } else if let Some(name) = inner.strip_prefix('/') {
+            Markup::End(name.trim())
+        } else {
+            let (inner, empty) = match inner.strip_suffix('/') {
+                Some(inner) => (inner, true),
+                None => (inner, false),
+            };
+            let split = inner
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(inner.len());
+            let (name, attrs) = inner.split_at(split);
+            if name.is_empty() {
+                return Err(markup("missing element name"));
+            }
+            Markup::Start { name, attrs, empty }
+        };
+        (markup, end + 1)
+    };
+    Ok(Some((markup, n)))
+}
+
+fn parse_attrs(mut s: &str) -> Result<Vec<(&str, String)>, InvalidFormatKind> {
+    let err = || markup("malformed attribute");
+    let mut attrs = Vec::new();
+    loop {
+        s = s.trim_start();
+        if s.is_empty() {
+            return Ok(attrs);
+        }
+        let (key, rest) = s.split_once('=').ok_or_else(err)?;
+        let rest = rest.trim_start();
+        let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'');
+        let quote = quote.ok_or_else(err)?;
+        let rest = &rest[1..];
+        let end = rest.find(quote).ok_or_else(err)?;
+        let mut value = String::new();
+        unescape(&rest[..end], &mut value)?;
+        attrs.push((key.trim(), value));
+        s = &rest[end + 1..];
+    }
+}
+
+fn unescape(mut s: &str, out: &mut String) -> Result<(), InvalidFormatKind> {
+    while let Some(i) = s.find('&') {
+        out.push_str(&s[..i]);
+        s = &s[i + 1..];
+        let end = s.find(';').ok_or_else(|| markup("unterminated entity"))?;
+        let c = match &s[..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            e => {
+                let code = if let Some(hex) =
+                    e.strip_prefix("#x").or_else(|| e.strip_prefix("#X"))
+                {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = e.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                code.and_then(char::from_u32)
+            }
+        };
+        let c =
+            c.ok_or_else(|| markup(format!("unknown entity &{};", &s[..end])))?;
+        out.push(c);
+        s = &s[end + 1..];
+    }
+    out.push_str(s);
+    Ok(())
+}
@@ -0,0 +1,633 @@
+use std::str::FromStr;
+
+use futures::{SinkExt, Stream, StreamExt};
+use rust_decimal::Decimal;
+
+use super::*;
+use crate::filter::normalize_mode;
+use crate::test::helpers::*;
+use crate::{DataType, Datum, Duplicates, Record, UserDef, UserDefValues};
+
+const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- exported for testing -->
+<ADX>
+  <HEADER>
+    <ADIF_VER>3.1.4</ADIF_VER>
+    <PROGRAMID>difa</PROGRAMID>
+    <USERDEF FIELDID="1" TYPE="N">EPC</USERDEF>
+    <USERDEF FIELDID="2" TYPE="E" ENUM="{S,M,L}">SWEATERSIZE</USERDEF>
+  </HEADER>
+  <RECORDS>
+    <RECORD>
+      <CALL>W1AW</CALL>
+      <FREQ>14.074</FREQ>
+      <APP PROGRAMID="MONOLOG" FIELDNAME="Compression" TYPE="N">12</APP>
+      <USERDEF FIELDNAME="SWEATERSIZE">M</USERDEF>
+    </RECORD>
+    <RECORD>
+      <CALL>AB9BH</CALL>
+      <COMMENT>Tom &amp; Jerry &lt;3 &#x263A; &#9731;</COMMENT>
+    </RECORD>
+  </RECORDS>
+</ADX>
+"#;
+
+fn records(s: &str, ignore_partial: bool) -> RecordStream<AdxStream<&[u8]>> {
+    RecordStream::new_adx(s.as_bytes(), ignore_partial)
+}
+
+async fn next<S>(s: &mut S) -> Record
+where
+    S: Stream<Item = Result<Record, Error>> + Unpin,
+{
+    s.next().await.unwrap().unwrap()
+}
+
+async fn next_err<S>(s: &mut S) -> Error
+where
+    S: Stream<Item = Result<Record, Error>> + Unpin,
+{
+    s.next().await.unwrap().unwrap_err()
+}
+
+async fn no_records<S>(s: &mut S)
+where
+    S: Stream<Item = Result<Record, Error>> + Unpin,
+{
+    assert!(s.next().await.is_none());
+}
+
+fn adx(records: &str) -> String {
+    format!("<ADX><RECORDS>{records}</RECORDS></ADX>")
+}
+
+async fn one(records: &str) -> Record {
+    let s = adx(records);
+    let mut f = RecordStream::new_adx(s.as_bytes(), false);
+    let rec = next(&mut f).await;
+    no_records(&mut f).await;
+    rec
+}
+
+async fn one_err(records: &str) -> Error {
+    let s = adx(records);
+    let mut f = RecordStream::new_adx(s.as_bytes(), false);
+    next_err(&mut f).await
+}
+
+async fn check_sample(
+    mut f: impl Stream<Item = Result<Record, Error>> + Unpin,
+) {
+    let header = next(&mut f).await;
+    assert!(header.is_header());
+    assert_eq!(header.get("adif_ver").unwrap().as_str(), "3.1.4");
+    assert_eq!(header.get("programid").unwrap().as_str(), "difa");
+    assert_eq!(header.get("userdef1").unwrap().as_str(), "EPC");
+    assert_eq!(
+        header.get("userdef2").unwrap().as_str(),
+        "SWEATERSIZE,{S,M,L}"
+    );
+
+    let rec = next(&mut f).await;
+    assert!(!rec.is_header());
+    assert_eq!(rec.get("call").unwrap().as_str(), "W1AW");
+    assert_eq!(rec.get("freq").unwrap().as_str(), "14.074");
+    assert_eq!(
+        rec.get("app_monolog_compression").unwrap(),
+        &Datum::Number(Decimal::from(12))
+    );
+    assert_eq!(rec.get("sweatersize").unwrap().as_str(), "M");
+
+    let rec = next(&mut f).await;
+    assert_eq!(rec.get("call").unwrap().as_str(), "AB9BH");
+    assert_eq!(
+        rec.get("comment").unwrap().as_str(),
+        "Tom & Jerry <3 \u{263A} \u{2603}"
+    );
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn sample() {
+    check_sample(records(SAMPLE, false)).await;
+}
+
+#[tokio::test]
+async fn trickle() {
+    for chunk in 1..4 {
+        let reader = TrickleReader::new(SAMPLE, chunk);
+        let f = AdxDecoder::new_stream(reader, false).records();
+        check_sample(TrickleStream::new(f)).await;
+    }
+}
+
+#[tokio::test]
+async fn field_names_preserve_case() {
+    let rec = one("<RECORD><Call>W1AW</Call></RECORD>").await;
+    let (name, value) = rec.fields().next().unwrap();
+    assert_eq!(name, "Call");
+    assert_eq!(value.as_str(), "W1AW");
+}
+
+#[tokio::test]
+async fn whitespace_preserved_in_values() {
+    let rec = one("<RECORD><NAME> Joe\n Smith </NAME></RECORD>").await;
+    assert_eq!(rec.get("name").unwrap().as_str(), " Joe\n Smith ");
+}
+
+#[tokio::test]
+async fn cdata() {
+    let rec =
+        one("<RECORD><NOTES>a<![CDATA[<b> & ]]]>c&gt;</NOTES></RECORD>").await;
+    assert_eq!(rec.get("notes").unwrap().as_str(), "a<b> & ]c>");
+}
+
+#[tokio::test]
+async fn empty_elements() {
+    let rec = one("<RECORD><CALL/><MODE></MODE></RECORD>").await;
+    assert_eq!(rec.get("call").unwrap().as_str(), "");
+    assert_eq!(rec.get("mode").unwrap().as_str(), "");
+
+    let rec = one("<RECORD/>").await;
+    assert_eq!(rec.fields().count(), 0);
+
+    let mut f = records("<ADX><HEADER/><RECORDS/></ADX>", false);
+    let rec = next(&mut f).await;
+    assert!(rec.is_header());
+    no_records(&mut f).await;
+
+    let mut f = records("<ADX/>", false);
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn comments_and_doctype_skipped() {
+    let rec = one(
+        "<!DOCTYPE ADX><RECORD><!-- <CALL>X</CALL> --><CALL>W1AW</CALL>\
+         <?pi data?></RECORD>",
+    )
+    .await;
+    assert_eq!(rec.get("call").unwrap().as_str(), "W1AW");
+    assert_eq!(rec.fields().count(), 1);
+}
+
+#[tokio::test]
+async fn attributes_single_quoted_and_escaped() {
+    let rec = one(
+        "<RECORD><APP PROGRAMID='A&amp;B' FIELDNAME = \"X>Y\">1</APP>\
+         </RECORD>",
+    )
+    .await;
+    assert_eq!(rec.get("app_a&b_x>y").unwrap().as_str(), "1");
+}
+
+#[tokio::test]
+async fn app_typed() {
+    let rec = one(r#"<RECORD>
+        <APP PROGRAMID="X" FIELDNAME="D" TYPE="D">20240115</APP>
+        <APP PROGRAMID="X" FIELDNAME="B" TYPE="B">Y</APP>
+        <APP PROGRAMID="X" FIELDNAME="S">14.074</APP>
+        </RECORD>"#)
+    .await;
+    assert_eq!(rec.get("app_x_d").unwrap().as_str(), "20240115");
+    assert_eq!(rec.get("app_x_b").unwrap(), &Datum::Boolean(true));
+    assert_eq!(
+        rec.get("app_x_s").unwrap(),
+        &Datum::String("14.074".to_string())
+    );
+}
+
+#[tokio::test]
+async fn userdef_range() {
+    let mut f = records(
+        r#"<ADX><HEADER>
+        <USERDEF FIELDID="3" TYPE="N" RANGE="{0:100}">SCORE</USERDEF>
+        </HEADER></ADX>"#,
+        false,
+    );
+    let header = next(&mut f).await;
+    assert_eq!(header.get("userdef3").unwrap().as_str(), "SCORE,{0:100}");
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn spans() {
+    let rec = one("<RECORD><CALL>W1AW</CALL>\n<QSL_RCVD/></RECORD>").await;
+    let call = span((1, 23, 22), (1, 40, 39));
+    assert_eq!(rec.field_span("call"), Some(call));
+    let qsl = span((2, 1, 40), (2, 12, 51));
+    assert_eq!(rec.field_span("qsl_rcvd"), Some(qsl));
+    assert_eq!(rec.span(), Some(span((1, 23, 22), (2, 12, 51))));
+
+    let err = one_err("<RECORD><CALL>W1AW</CALL>\n<CALL/></RECORD>").await;
+    let Error::DuplicateKey { span: dup, .. } = err else {
+        panic!("{err:?}");
+    };
+    assert_eq!(dup, Some(span((2, 1, 40), (2, 8, 47))));
+}
+
+#[tokio::test]
+async fn userdef_name_in_record() {
+    let rec = one("<RECORD><USERDEF1>,{}</USERDEF1></RECORD>").await;
+    assert_eq!(rec.get("userdef1"), Some(&Datum::String(",{}".into())));
+}
+
+#[tokio::test]
+async fn normalizers_work_unchanged() {
+    let s = adx(r#"<RECORD>
+        <APP PROGRAMID="LOTW" FIELDNAME="MODE">FT8</APP>
+        </RECORD>"#);
+    let mut f = normalize_mode(records(&s, false));
+    let rec = next(&mut f).await;
+    assert_eq!(rec.get(":mode").unwrap().as_str(), "FT8");
+}
+
+#[tokio::test]
+async fn invalid_typed_value() {
+    let err = one_err(
+        r#"<RECORD><APP PROGRAMID="X" FIELDNAME="N" TYPE="N">abc</APP>
+        </RECORD>"#,
+    )
+    .await;
+    assert_eq!(
+        err,
+        invalid_format(
+            bad_value("APP_X_N", "abc", DataType::Number),
+            1,
+            23,
+            22
+        )
+    );
+}
+
+#[tokio::test]
+async fn missing_attributes() {
+    let err = one_err(r#"<RECORD><APP FIELDNAME="N">1</APP></RECORD>"#).await;
+    assert_eq!(
+        err,
+        invalid_format(other("APP element missing PROGRAMID"), 1, 23, 22)
+    );
+
+    let err = one_err(r#"<RECORD><USERDEF>1</USERDEF></RECORD>"#).await;
+    assert_eq!(
+        err,
+        invalid_format(other("USERDEF element missing FIELDNAME"), 1, 23, 22)
+    );
+
+    let mut f = records("<ADX><HEADER><USERDEF>X</USERDEF>", false);
+    let err = next_err(&mut f).await;
+    assert_eq!(
+        err,
+        invalid_format(other("USERDEF element missing FIELDID"), 1, 14, 13)
+    );
+}
+
+#[tokio::test]
+async fn malformed_attributes() {
+    for attrs in ["PROGRAMID", "PROGRAMID=X"] {
+        let err = one_err(&format!("<RECORD><APP {attrs}/></RECORD>")).await;
+        assert_eq!(
+            err,
+            invalid_format(other("malformed attribute"), 1, 23, 22)
+        );
+    }
+}
+
+#[tokio::test]
+async fn bad_entities() {
+    let err = one_err("<RECORD><CALL>&bogus;</CALL></RECORD>").await;
+    assert_eq!(
+        err,
+        invalid_format(other("unknown entity &bogus;"), 1, 29, 28)
+    );
+
+    let err = one_err("<RECORD><CALL>&#xD800;</CALL></RECORD>").await;
+    assert_eq!(
+        err,
+        invalid_format(other("unknown entity &#xD800;"), 1, 29, 28)
+    );
+
+    let err = one_err("<RECORD><CALL>a &amp b</CALL></RECORD>").await;
+    assert_eq!(err, invalid_format(other("unterminated entity"), 1, 29, 28));
+}
+
+#[tokio::test]
+async fn structure_errors() {
+    let err = one_err("<RECORD><CALL>W1AW</MODE></RECORD>").await;
+    assert_eq!(
+        err,
+        invalid_format(other("mismatched end tag MODE"), 1, 33, 32)
+    );
+
+    let err = one_err("<RECORD><CALL><B>W1AW</B></CALL></RECORD>").await;
+    assert_eq!(
+        err,
+        invalid_format(other("unexpected element B"), 1, 29, 28)
+    );
+
+    let err = one_err("<FOO/>").await;
+    assert_eq!(
+        err,
+        invalid_format(other("unexpected element FOO"), 1, 15, 14)
+    );
+
+    let err = one_err("<RECORD></RECORDS>").await;
+    assert_eq!(
+        err,
+        invalid_format(other("mismatched end tag RECORDS"), 1, 23, 22)
+    );
+
+    let mut f = records("<RECORDS/>", false);
+    let err = next_err(&mut f).await;
+    assert_eq!(
+        err,
+        invalid_format(other("unexpected element RECORDS"), 1, 1, 0)
+    );
+
+    let mut f = records("<ADX></ADX><ADX>", false);
+    let err = next_err(&mut f).await;
+    assert_eq!(
+        err,
+        invalid_format(other("unexpected element ADX"), 1, 12, 11)
+    );
+
+    let mut f = records("<ADX><  /></ADX>", false);
+    let err = next_err(&mut f).await;
+    assert_eq!(err, invalid_format(other("missing element name"), 1, 6, 5));
+}
+
+#[tokio::test]
+async fn invalid_utf8() {
+    let bytes = b"<ADX><RECORDS><RECORD><CALL>\xFF</CALL></RECORD>";
+    let mut f = RecordStream::new_adx(&bytes[..], false);
+    let err = next_err(&mut f).await;
+    assert_eq!(
+        err,
+        invalid_format(bad_encoding(Some("CALL"), "\u{FFFD}"), 1, 29, 28)
+    );
+
+    let bytes = b"<ADX><\xFF>";
+    let mut f = RecordStream::new_adx(&bytes[..], false);
+    let err = next_err(&mut f).await;
+    assert_eq!(err, invalid_format(bad_encoding(None, "\u{FFFD}"), 1, 6, 5));
+}
+
+#[tokio::test]
+async fn partial_document() {
+    let doc = adx("<RECORD><CALL>W1AW</CALL></RECORD><RECORD><CALL>AB");
+    let doc = &doc[..doc.len() - "</RECORDS></ADX>".len()];
+    let mut f = records(doc, true);
+    next(&mut f).await;
+    no_records(&mut f).await;
+
+    let mut f = records(doc, false);
+    next(&mut f).await;
+    let err = next_err(&mut f).await;
+    assert_eq!(err, partial_data(1, 63, 62));
+
+    let mut f = records("<ADX><RECORDS>", false);
+    let err = next_err(&mut f).await;
+    assert_eq!(err, partial_data(1, 15, 14));
+
+    let mut f = records("<ADX><!-- ", false);
+    let err = next_err(&mut f).await;
+    assert_eq!(err, partial_data(1, 6, 5));
+}
+
+#[tokio::test]
+async fn trailing_whitespace_ignored() {
+    let mut f = records("\n<ADX></ADX>\n\n", false);
+    no_records(&mut f).await;
+
+    let mut f = records("", false);
+    no_records(&mut f).await;
+
+    let mut f = records("<ADX></ADX> x", false);
+    let err = next_err(&mut f).await;
+    assert_eq!(err, partial_data(1, 12, 11));
+}
+
+#[tokio::test]
+async fn number_field() {
+    let rec = one("<RECORD><FREQ>7.074</FREQ></RECORD>").await;
+    assert_eq!(
+        rec.get("freq").unwrap().as_number().unwrap(),
+        Decimal::from_str("7.074").unwrap()
+    );
+}
+
+async fn write(records: Vec<Record>) -> String {
+    let mut buf = Vec::new();
+    let mut sink = AdxSink::with_types(&mut buf, OutputTypes::OnlyNonString);
+    for r in records {
+        sink.send(r).await.unwrap();
+    }
+    sink.close().await.unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[tokio::test]
+async fn write_document() {
+    let mut header = Record::new_header();
+    header.insert("adif_ver", "3.1.4").unwrap();
+    header.insert("userdef1", "SWEATERSIZE,{S,M,L}").unwrap();
+    header.insert("userdef2", "SCORE,{0:100}").unwrap();
+
+    let mut rec = Record::new();
+    rec.insert("call", "W1AW").unwrap();
+    rec.insert("freq", Decimal::from_str("14.074").unwrap())
+        .unwrap();
+    rec.insert("app_monolog_Compression", Decimal::from(12))
+        .unwrap();
+    rec.insert("sweatersize", "M").unwrap();
+
+    let output = write(vec![header, rec]).await;
+    let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ADX>
+  <HEADER>
+    <ADIF_VER>3.1.4</ADIF_VER>
+    <USERDEF FIELDID="1" ENUM="{S,M,L}">SWEATERSIZE</USERDEF>
+    <USERDEF FIELDID="2" RANGE="{0:100}">SCORE</USERDEF>
+  </HEADER>
+  <RECORDS>
+    <RECORD>
+      <CALL>W1AW</CALL>
+      <FREQ>14.074</FREQ>
+      <APP PROGRAMID="monolog" FIELDNAME="Compression" TYPE="N">12</APP>
+      <USERDEF FIELDNAME="sweatersize">M</USERDEF>
+    </RECORD>
+  </RECORDS>
+</ADX>
+"#;
+    assert_eq!(output, expected);
+}
+
+#[tokio::test]
+async fn write_keep_all() {
+    let mut rec = Record::new();
+    rec.insert("comment", "a").unwrap();
+    rec.insert("call", "W1AW").unwrap();
+    rec.insert_with("comment", "b", Duplicates::KeepAll)
+        .unwrap();
+    let output = write(vec![rec]).await;
+    let expected = "      <COMMENT>a</COMMENT>
+      <COMMENT>b</COMMENT>
+      <CALL>W1AW</CALL>
+";
+    assert!(output.contains(expected), "{output}");
+}
+
+#[tokio::test]
+async fn write_userdef() {
+    let mut header = Record::new_header();
+    let values = UserDefValues::Enum(vec!["S".into(), "M".into()]);
+    let def =
+        UserDef::new("size", Some(DataType::Enumeration)).with_values(values);
+    header.insert("userdef1", def.clone()).unwrap();
+    let mut rec = Record::new();
+    rec.insert("size", "M").unwrap();
+
+    let output = write(vec![header, rec]).await;
+    assert!(output.contains(
+        r#"<USERDEF FIELDID="1" TYPE="E" ENUM="{S,M}">size</USERDEF>"#
+    ));
+    assert!(output.contains(r#"<USERDEF FIELDNAME="size">M</USERDEF>"#));
+
+    let mut f = records(&output, false);
+    let header = next(&mut f).await;
+    assert_eq!(header.get("userdef1"), Some(&Datum::from(def)));
+}
+
+#[tokio::test]
+async fn write_empty() {
+    let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ADX>
+  <RECORDS>
+  </RECORDS>
+</ADX>
+"#;
+    assert_eq!(write(vec![]).await, expected);
+
+    let mut f = records(expected, false);
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn write_escapes() {
+    let mut rec = Record::new();
+    rec.insert("comment", "Tom & Jerry <3>\r\n\u{263A}")
+        .unwrap();
+    rec.insert("app_x_y", "\"quoted\"\ttab").unwrap();
+    let output = write(vec![rec.clone()]).await;
+    assert!(output.contains(
+        "<COMMENT>Tom &amp; Jerry &lt;3&gt;&#13;\n\u{263A}</COMMENT>"
+    ));
+    assert!(
+        output
+            .contains(r#"<APP PROGRAMID="x" FIELDNAME="y">"quoted"	tab</APP>"#)
+    );
+
+    let mut f = records(&output, false);
+    assert_eq!(next(&mut f).await, rec);
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn write_control_characters() {
+    let mut buf = Vec::new();
+    let mut sink = AdxSink::new(&mut buf);
+    for value in ["a\u{1}b", "\u{1b}", "\u{ffff}"] {
+        let mut rec = Record::new();
+        rec.insert("comment", Datum::IntlString(value.into()))
+            .unwrap();
+        assert_eq!(
+            sink.send(rec).await.unwrap_err(),
+            cannot_output(
+                "field",
+                "value contains characters not allowed in XML"
+            ),
+            "{value:?}"
+        );
+    }
+    let mut rec = Record::new();
+    rec.insert("call", "W1AW").unwrap();
+    sink.send(rec.clone()).await.unwrap();
+    sink.close().await.unwrap();
+
+    let output = String::from_utf8(buf).unwrap();
+    let mut f = records(&output, false);
+    assert_eq!(next(&mut f).await, rec);
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn write_roundtrip_sample() {
+    let f = records(SAMPLE, false);
+    let input: Vec<_> = f.map(|r| r.unwrap()).collect().await;
+    let output = write(input).await;
+    check_sample(records(&output, false)).await;
+}
+
+#[tokio::test]
+async fn write_trickle() {
+    let mut rec = Record::new();
+    rec.insert("call", "W1AW").unwrap();
+
+    let mut w = TrickleWriter::new(3);
+    let mut sink = AdxSink::with_types(&mut w, OutputTypes::OnlyNonString);
+    sink.send(rec.clone()).await.unwrap();
+    sink.close().await.unwrap();
+    // closing twice must not write a second document trailer
+    sink.close().await.unwrap();
+    let expected = write(vec![rec]).await;
+    assert_eq!(String::from_utf8_lossy(w.written()), expected);
+    assert!(expected.ends_with("</ADX>\n"));
+}
+
+#[tokio::test]
+async fn write_header_errors() {
+    let mut buf = Vec::new();
+    let mut sink = AdxSink::new(&mut buf);
+    sink.send(Record::new_header()).await.unwrap();
+    let err = sink.send(Record::new_header()).await.unwrap_err();
+    assert_eq!(err, Error::DuplicateHeader);
+
+    let mut buf = Vec::new();
+    let mut sink = AdxSink::new(&mut buf);
+    sink.send(Record::new()).await.unwrap();
+    let err = sink.send(Record::new_header()).await.unwrap_err();
+    assert_eq!(
+        err,
+        cannot_output("header", "header must precede all records")
+    );
+}
+
+#[tokio::test]
+async fn write_bad_names() {
+    for name in ["app_x", "app__y", "app_x_", "1abc", "a:b"] {
+        let mut buf = Vec::new();
+        let mut sink = AdxSink::new(&mut buf);
+        let mut rec = Record::new();
+        rec.insert(name, "1").unwrap();
+        assert!(matches!(
+            sink.send(rec).await.unwrap_err(),
+            Error::CannotOutput { typ: "field", .. }
+        ));
+    }
+}
+
+#[tokio::test]
+async fn write_datetime() {
+    let dt = chrono::NaiveDate::from_ymd_opt(2024, 1, 15)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    let mut buf = Vec::new();
+    let mut sink = AdxSink::new(&mut buf);
+    let mut rec = Record::new();
+    rec.insert(":time_on", dt).unwrap();
+    assert!(matches!(
+        sink.send(rec).await.unwrap_err(),
+        Error::CannotOutput { .. }
+    ));
+}
@@ -0,0 +1,265 @@
+//! Reading and writing ADIF without an async runtime
+//!
+//! These types wrap [std::io::Read] and [std::io::Write] and are driven by
+//! the same decoders and encoders as the async streams and sinks, so they
+//! produce the same tags, records and errors.
+
+use crate::parse::Aggregator;
+use crate::write::WriterTagEncoder;
+use crate::{
+    AdxDecoder, Duplicates, Error, OutputTypes, Record, Tag, TagDecoder,
+    TagEncoder,
+};
+use bytes::BytesMut;
+use std::io::{ErrorKind, Read, Write};
+use tokio_util::codec::Decoder;
+
+#[cfg(test)]
+mod test;
+
+pub(crate) const CHUNK: usize = 8192;
+
+/// Iterator over ADIF tags from a blocking reader
+pub struct TagReader<R, D = TagDecoder> {
+    reader: R,
+    decoder: D,
+    buf: BytesMut,
+    eof: bool,
+    done: bool,
+}
+
+impl<R> TagReader<R>
+where
+    R: Read,
+{
+    /// Create a new iterator that returns ADIF tags.
+    ///
+    /// See [TagDecoder::new_stream] for the meaning of `ignore_partial`.
+    ///
+    /// ```
+    /// use difa::blocking::TagReader;
+    /// let mut t = TagReader::new("<FOO:3>123".as_bytes(), true);
+    /// let tag = t.next().unwrap().unwrap();
+    /// let field = tag.as_field().unwrap();
+    /// assert_eq!(field.name(), "FOO");
+    /// assert_eq!(field.value().as_str(), "123");
+    /// assert!(t.next().is_none());
+    /// ```
+    pub fn new(reader: R, ignore_partial: bool) -> Self {
+        Self::with_decoder(reader, TagDecoder::new(ignore_partial))
+    }
+}
+
+impl<R> TagReader<R, AdxDecoder>
+where
+    R: Read,
+{
+    /// Create a new iterator that returns ADIF tags from an ADX document.
+    ///
+    /// See [AdxDecoder::new_stream] for the meaning of `ignore_partial`.
+    pub fn new_adx(reader: R, ignore_partial: bool) -> Self {
+        Self::with_decoder(reader, AdxDecoder::new(ignore_partial))
+    }
+}
+
+impl<R, D> TagReader<R, D>
+where
+    R: Read,
+    D: Decoder<Item = Tag, Error = Error>,
+{
+    /// Create a new iterator that returns tags from the given decoder.
+    ///
+    /// ```
+    /// use difa::blocking::TagReader;
+    /// use difa::{Encoding, TagDecoder};
+    /// let decoder = TagDecoder::new(true).with_encoding(Encoding::Latin1);
+    /// let mut t = TagReader::with_decoder(&b"<NAME:3>J\xf6n"[..], decoder);
+    /// let tag = t.next().unwrap().unwrap();
+    /// assert_eq!(tag.as_field().unwrap().value().as_str(), "J\u{f6}n");
+    /// ```
+    pub fn with_decoder(reader: R, decoder: D) -> Self {
+        Self {
+            reader,
+            decoder,
+            buf: BytesMut::new(),
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Return the decoder, e.g. to find the position of the last tag.
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    /// Aggregate tags into records.
+    pub fn records(self) -> RecordReader<Self> {
+        RecordReader {
+            tags: self,
+            aggregator: Aggregator::default(),
+        }
+    }
+
+    fn read_tag(&mut self) -> Result<Option<Tag>, Error> {
+        let mut chunk = [0; CHUNK];
+        loop {
+            if self.eof {
+                return self.decoder.decode_eof(&mut self.buf);
+            }
+            if let Some(tag) = self.decoder.decode(&mut self.buf)? {
+                return Ok(Some(tag));
+            }
+            match self.reader.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl<R, D> Iterator for TagReader<R, D>
+where
+    R: Read,
+    D: Decoder<Item = Tag, Error = Error>,
+{
+    type Item = Result<Tag, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        // like a stream, end after the first error
+        let res = self.read_tag().transpose();
+        self.done = !matches!(res, Some(Ok(_)));
+        res
+    }
+}
+
+/// Iterator that aggregates ADIF tags into complete records
+pub struct RecordReader<I> {
+    tags: I,
+    aggregator: Aggregator,
+}
+
+impl<R> RecordReader<TagReader<R>>
+where
+    R: Read,
+{
+    /// Create a new iterator that returns ADIF records.
+    ///
+    /// See [RecordStream::new](crate::RecordStream::new) for the meaning
+    /// of `ignore_partial`.
+    ///
+    /// ```
+    /// use difa::blocking::RecordReader;
+    /// let mut r = RecordReader::new("<FOO:3>123<eor>".as_bytes(), true);
+    /// let rec = r.next().unwrap().unwrap();
+    /// assert_eq!(rec.get("foo").unwrap().as_number().unwrap(), 123.into());
+    /// assert!(r.next().is_none());
+    /// ```
+    pub fn new(reader: R, ignore_partial: bool) -> Self {
+        TagReader::new(reader, ignore_partial).records()
+    }
+}
+
+impl<R> RecordReader<TagReader<R, AdxDecoder>>
+where
+    R: Read,
+{
+    /// Create a new iterator that returns ADIF records from an ADX
+    /// document.
+    pub fn new_adx(reader: R, ignore_partial: bool) -> Self {
+        TagReader::new_adx(reader, ignore_partial).records()
+    }
+}
+
+impl<I> RecordReader<I> {
+    /// Handle fields that occur more than once in a record as specified.
+    ///
+    /// See [RecordStream::with_duplicates](crate::RecordStream::with_duplicates).
+    pub fn with_duplicates(mut self, duplicates: Duplicates) -> Self {
+        self.aggregator.set_duplicates(duplicates);
+        self
+    }
+}
+
+impl<I> Iterator for RecordReader<I>
+where
+    I: Iterator<Item = Result<Tag, Error>>,
+{
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let tag = self.tags.next()?;
+            if let Some(r) = self.aggregator.push(tag) {
+                return Some(r);
+            }
+        }
+    }
+}
+
+/// Writer of ADIF records to a blocking writer
+pub struct RecordWriter<W> {
+    writer: W,
+    encoder: WriterTagEncoder,
+    buf: BytesMut,
+}
+
+impl<W> RecordWriter<W>
+where
+    W: Write,
+{
+    /// Create a new RecordWriter with default configuration.
+    ///
+    /// ```
+    /// use difa::Record;
+    /// use difa::blocking::RecordWriter;
+    ///
+    /// let mut writer = RecordWriter::new(Vec::new());
+    /// let mut record = Record::new();
+    /// record.insert("call", "W1AW").unwrap();
+    /// writer.write(&record).unwrap();
+    /// assert_eq!(writer.into_inner(), b"<call:4>W1AW<eor>\n");
+    /// ```
+    pub fn new(writer: W) -> Self {
+        Self::with_encoder(writer, TagEncoder::new())
+    }
+
+    /// Create a new RecordWriter with given type specifier behavior.
+    pub fn with_types(writer: W, types: OutputTypes) -> Self {
+        Self::with_encoder(writer, TagEncoder::with_types(types))
+    }
+
+    /// Create a new RecordWriter that writes tags with the given encoder.
+    pub fn with_encoder(writer: W, encoder: TagEncoder) -> Self {
+        Self {
+            writer,
+            encoder: WriterTagEncoder::new(encoder),
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Write a record.
+    ///
+    /// If the record cannot be encoded, nothing is written.
+    pub fn write(&mut self, record: &Record) -> Result<(), Error> {
+        self.buf.clear();
+        self.encoder.encode_record(record, &mut self.buf)?;
+        self.writer.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
@@ -0,0 +1,190 @@
+use std::io;
+
+use rust_decimal::Decimal;
+
+use super::*;
+use crate::test::helpers::*;
+use crate::{Duplicates, Encoding, Field, LengthUnit};
+
+struct FailingReader;
+
+impl Read for FailingReader {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("boom"))
+    }
+}
+
+struct FailingWriter;
+
+impl Write for FailingWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("boom"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Err(io::Error::other("boom"))
+    }
+}
+
+#[test]
+fn tags() {
+    let mut t =
+        TagReader::new("Log <a:1>1<eoh>\n<b:1:n>2<eor>\n".as_bytes(), false);
+    assert_eq!(t.next().unwrap().unwrap(), Tag::Preamble("Log".to_string()));
+    assert_eq!(t.next().unwrap().unwrap(), Tag::Field(Field::new("a", "1")));
+    assert_eq!(t.next().unwrap().unwrap(), Tag::Eoh);
+    assert_eq!(
+        t.next().unwrap().unwrap(),
+        Tag::Field(Field::new("b", Decimal::from(2)))
+    );
+    assert_eq!(t.next().unwrap().unwrap(), Tag::Eor);
+    assert!(t.next().is_none());
+    assert!(t.next().is_none());
+}
+
+#[test]
+fn trickle() {
+    let s = "<adifver:5>3.1.4<eoh><call:4>W1AW<eor><call:5>AB9BH<eor>";
+    for chunk in 1..=s.len() {
+        let mut r =
+            TagReader::new(TrickleReader::new(s, chunk), false).records();
+        let rec = r.next().unwrap().unwrap();
+        assert!(rec.is_header());
+        assert_eq!(rec.get("adifver").unwrap().as_str(), "3.1.4");
+        let rec = r.next().unwrap().unwrap();
+        assert_eq!(rec.get("call").unwrap().as_str(), "W1AW");
+        let rec = r.next().unwrap().unwrap();
+        assert_eq!(rec.get("call").unwrap().as_str(), "AB9BH");
+        assert!(r.next().is_none());
+    }
+}
+
+#[test]
+fn large_input() {
+    let s = "<call:4>W1AW<eor>\n".repeat(2 * CHUNK);
+    let r = RecordReader::new(s.as_bytes(), false);
+    let records: Vec<_> = r.collect::<Result<_, _>>().unwrap();
+    assert_eq!(records.len(), 2 * CHUNK);
+}
+
+#[test]
+fn partial() {
+    let mut r =
+        RecordReader::new("<call:4>W1AW<eor><call:4>W1".as_bytes(), false);
+    assert!(r.next().unwrap().is_ok());
+    assert_eq!(r.next().unwrap().unwrap_err(), partial_data(1, 18, 17));
+    assert!(r.next().is_none());
+
+    let mut r =
+        RecordReader::new("<call:4>W1AW<eor><call:4>W1".as_bytes(), true);
+    assert!(r.next().unwrap().is_ok());
+    assert!(r.next().is_none());
+}
+
+#[test]
+fn errors_end_iteration() {
+    let mut t = TagReader::new("<a:x>1<b:1>2".as_bytes(), false);
+    assert_eq!(
+        t.next().unwrap().unwrap_err(),
+        invalid_format(bad_length("a", "x"), 1, 1, 0)
+    );
+    assert!(t.next().is_none());
+
+    let mut r =
+        RecordReader::new("<a:1>1<a:1>2<eor><b:1>3<eor>".as_bytes(), true);
+    let mut dup = Record::new();
+    dup.insert("a", "1").unwrap();
+    let err = duplicate_key_at("a", dup, span((1, 7, 6), (1, 13, 12)));
+    assert_eq!(r.next().unwrap().unwrap_err(), err);
+}
+
+#[test]
+fn duplicates() {
+    let mut r =
+        RecordReader::new("<a:1>1<a:1>2<eor><b:1>3<eor>".as_bytes(), true)
+            .with_duplicates(Duplicates::Last);
+    assert_eq!(r.next().unwrap().unwrap().get("a").unwrap().as_str(), "2");
+    assert_eq!(r.next().unwrap().unwrap().get("b").unwrap().as_str(), "3");
+    assert!(r.next().is_none());
+}
+
+#[test]
+fn io_error() {
+    let mut r = RecordReader::new(FailingReader, false);
+    assert!(matches!(r.next().unwrap().unwrap_err(), Error::Io(_)));
+    assert!(r.next().is_none());
+}
+
+#[test]
+fn decoder() {
+    let decoder = TagDecoder::new(false)
+        .with_encoding(Encoding::Windows1252)
+        .with_length_unit(LengthUnit::Chars);
+    let mut r =
+        TagReader::with_decoder(&b"<qth:4>K\xf6ln<eor>"[..], decoder).records();
+    let rec = r.next().unwrap().unwrap();
+    assert_eq!(rec.get("qth").unwrap().as_str(), "K\u{f6}ln");
+    assert!(r.next().is_none());
+}
+
+#[test]
+fn adx() {
+    let adx = r#"<?xml version="1.0"?>
+<ADX><HEADER><ADIF_VER>3.1.4</ADIF_VER></HEADER>
+<RECORDS><RECORD><CALL>W1AW</CALL></RECORD></RECORDS></ADX>"#;
+    let mut r = RecordReader::new_adx(adx.as_bytes(), false);
+    assert!(r.next().unwrap().unwrap().is_header());
+    let rec = r.next().unwrap().unwrap();
+    assert_eq!(rec.get("call").unwrap().as_str(), "W1AW");
+    assert!(r.next().is_none());
+}
+
+#[test]
+fn write() {
+    let mut header = Record::new_header();
+    header.set_preamble("Exported by difa");
+    header.insert("adifver", "3.1.4").unwrap();
+    let mut record = Record::new();
+    record.insert("call", "W1AW").unwrap();
+    record.insert("freq", Decimal::new(14074, 3)).unwrap();
+
+    let mut w =
+        RecordWriter::with_types(Vec::new(), OutputTypes::OnlyNonString);
+    w.write(&header).unwrap();
+    w.write(&record).unwrap();
+    w.flush().unwrap();
+    let buf = w.into_inner();
+    assert_eq!(
+        buf,
+        b"Exported by difa\n<adifver:5>3.1.4<eoh>\n\
+          <call:4>W1AW<freq:6:n>14.074<eor>\n"
+    );
+
+    let mut r = RecordReader::new(&buf[..], false);
+    assert_eq!(r.next().unwrap().unwrap(), header);
+    assert_eq!(r.next().unwrap().unwrap(), record);
+    assert!(r.next().is_none());
+}
+
+#[test]
+fn write_failure_writes_nothing() {
+    let mut record = Record::new();
+    record.insert("call", "W1AW").unwrap();
+    record.insert("comment", "\u{263a}").unwrap();
+
+    let encoder = TagEncoder::new().with_encoding(Encoding::Latin1);
+    let mut w = RecordWriter::with_encoder(Vec::new(), encoder);
+    let err = w.write(&record).unwrap_err();
+    assert_eq!(
+        err,
+        cannot_output("field", "not representable in output encoding")
+    );
+    assert!(w.into_inner().is_empty());
+}
+
+#[test]
+fn write_io_error() {
+    let mut w = RecordWriter::new(FailingWriter);
+    assert!(matches!(w.write(&Record::new()).unwrap_err(), Error::Io(_)));
+    assert!(matches!(w.flush().unwrap_err(), Error::Io(_)));
+}
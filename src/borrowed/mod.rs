@@ -0,0 +1,350 @@
+//! Zero-copy parsing of ADIF data already in memory
+//!
+//! A [RecordParser] scans a byte slice, such as the contents of a file
+//! that has been read or mapped into memory, and returns [RecordRef]s that
+//! borrow their names and values from it instead of allocating.  Input
+//! must be UTF-8 with lengths counted in bytes.
+//!
+//! Records are validated as they are parsed, so the only error that can
+//! occur when converting one into an owned [Record] is a duplicate field.
+
+use crate::parse::{lossy, parse_field_value, parse_length, parse_type};
+use crate::{
+    CiStr, Datum, Error, Field, InvalidFormatKind, Position, Record, UserDef,
+};
+
+#[cfg(test)]
+mod test;
+
+/// Split a field tag into its name, length and type indicator.
+pub(crate) fn split_tag(
+    tag: &[u8],
+) -> Result<(&str, usize, Option<&str>), InvalidFormatKind> {
+    let mut parts = tag.split(|&b| b == b':');
+    let (name, len, typ) =
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(name), Some(len), typ, None) => (name, len, typ),
+            _ => return Err(InvalidFormatKind::BadTag { tag: lossy(tag) }),
+        };
+    let name = to_str(name, None)?;
+    let len = parse_length(name, len)?;
+    let typ = typ.map(|t| parse_type(name, t)).transpose()?;
+    Ok((name, len, typ))
+}
+
+fn to_str<'a>(
+    text: &'a [u8], name: Option<&str>,
+) -> Result<&'a str, InvalidFormatKind> {
+    str::from_utf8(text).map_err(|_| InvalidFormatKind::BadEncoding {
+        name: name.map(str::to_string),
+        text: lossy(text),
+    })
+}
+
+/// A field borrowed from the parser's input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldRef<'a> {
+    name: &'a str,
+    value: &'a str,
+    typ: Option<&'a str>,
+    header: bool,
+}
+
+impl<'a> FieldRef<'a> {
+    /// Return name of the field.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Return the unparsed value of the field.
+    pub fn value(&self) -> &'a str {
+        self.value
+    }
+
+    /// Return the type indicator of the field, if any.
+    pub fn type_indicator(&self) -> Option<&'a str> {
+        self.typ
+    }
+
+    /// Return the value of the field parsed according to its type.
+    pub fn datum(&self) -> Datum {
+        // values were validated during parsing, so this cannot fail
+        parse_field_value(self.name, self.value, self.typ, self.header)
+            .unwrap_or_else(|_| Datum::String(self.value.to_string()))
+    }
+
+    /// Convert into an owned field.
+    pub fn to_owned(&self) -> Field {
+        Field::new(self.name, self.datum())
+    }
+}
+
+/// Iterator over the fields of a [RecordRef]
+#[derive(Debug, Clone)]
+pub struct Fields<'a> {
+    data: &'a [u8],
+    header: bool,
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = FieldRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let begin = self.data.iter().position(|&b| b == b'<')?;
+        let rest = &self.data[begin..];
+        let end = rest.iter().position(|&b| b == b'>')?;
+        let (name, len, typ) = split_tag(&rest[1..end]).ok()?;
+        let value = rest.get(end + 1..end + 1 + len)?;
+        let value = str::from_utf8(value).ok()?;
+        self.data = &rest[end + 1 + len..];
+        Some(FieldRef {
+            name,
+            value,
+            typ,
+            header: self.header,
+        })
+    }
+}
+
+/// A record borrowed from the parser's input
+///
+/// Fields are not stored but rescanned from the input when requested,
+/// which is cheap because the record has already been validated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordRef<'a> {
+    data: &'a [u8],
+    header: bool,
+    preamble: Option<&'a str>,
+    position: Position,
+}
+
+impl<'a> RecordRef<'a> {
+    /// True if this record represents an ADIF header.
+    pub fn is_header(&self) -> bool {
+        self.header
+    }
+
+    /// Return the free text preceding the header, if any.
+    pub fn preamble(&self) -> Option<&'a str> {
+        self.preamble
+    }
+
+    /// Return the position of the first tag of the record.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Return an iterator over all fields in this record.
+    pub fn fields(&self) -> Fields<'a> {
+        Fields {
+            data: self.data,
+            header: self.header,
+        }
+    }
+
+    /// Return the requested field, compared case-insensitively.
+    pub fn get(&self, name: &str) -> Option<FieldRef<'a>> {
+        self.fields()
+            .find(|f| CiStr::new(f.name) == CiStr::new(name))
+    }
+
+    /// Convert into an owned record.
+    ///
+    /// Returns an error if the record contains a duplicate field.
+    pub fn to_owned(&self) -> Result<Record, Error> {
+        let mut record = if self.header {
+            Record::new_header()
+        } else {
+            Record::new()
+        };
+        if let Some(preamble) = self.preamble {
+            record.set_preamble(preamble);
+        }
+        for f in self.fields() {
+            record.insert(f.name, f.datum())?;
+        }
+        Ok(record)
+    }
+}
+
+/// Iterator over the records in a byte slice
+///
+/// ```
+/// use difa::borrowed::RecordParser;
+/// let data = b"<adifver:5>3.1.4<eoh><call:4>W1AW<freq:6:n>14.074<eor>";
+/// let mut p = RecordParser::new(data, false);
+/// assert!(p.next().unwrap().unwrap().is_header());
+/// let rec = p.next().unwrap().unwrap();
+/// assert_eq!(rec.get("CALL").unwrap().value(), "W1AW");
+/// let owned = rec.to_owned().unwrap();
+/// assert_eq!(owned.get("freq").unwrap().as_number().unwrap(),
+///            "14.074".parse().unwrap());
+/// assert!(p.next().is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecordParser<'a> {
+    data: &'a [u8],
+    ignore_partial: bool,
+    started: bool,
+    // no record returned yet, so fields may be in a header
+    header: bool,
+    done: bool,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> RecordParser<'a> {
+    /// Create a new parser over the given data.
+    ///
+    /// See [RecordStream::new](crate::RecordStream::new) for the meaning
+    /// of `ignore_partial`.
+    pub fn new(data: &'a [u8], ignore_partial: bool) -> Self {
+        Self {
+            data,
+            ignore_partial,
+            started: false,
+            header: true,
+            done: false,
+            pos: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Create a parser that begins partway through the given data.
+    ///
+    /// `position` must be that of `data[position.byte]`, and no preamble
+    /// or `USERDEFn` declaration is recognized unless it is the start of
+    /// the data.
+    pub(crate) fn resume(
+        data: &'a [u8], ignore_partial: bool, position: Position,
+    ) -> Self {
+        Self {
+            data,
+            ignore_partial,
+            started: position.byte > 0,
+            header: position.byte == 0,
+            done: false,
+            pos: position.byte,
+            line: position.line,
+            column: position.column,
+        }
+    }
+
+    // not `position`, which would be shadowed by Iterator::position
+    fn current(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+            byte: self.pos,
+        }
+    }
+
+    fn advance(&mut self, n: usize) {
+        for &byte in &self.data[self.pos..self.pos + n] {
+            if byte == b'\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.pos += n;
+    }
+
+    fn invalid(&self, kind: InvalidFormatKind) -> Error {
+        Error::InvalidFormat {
+            kind,
+            position: self.current(),
+        }
+    }
+
+    fn partial(&mut self) -> Result<Option<RecordRef<'a>>, Error> {
+        if self.ignore_partial {
+            return Ok(None);
+        }
+        Err(self.invalid(InvalidFormatKind::PartialData))
+    }
+
+    fn parse_record(&mut self) -> Result<Option<RecordRef<'a>>, Error> {
+        let data = self.data;
+        let mut preamble = None;
+        let mut start = None;
+        loop {
+            let rest = &data[self.pos..];
+            let whitespace = rest
+                .iter()
+                .position(|&b| !b.is_ascii_whitespace())
+                .unwrap_or(rest.len());
+            self.advance(whitespace);
+
+            let rest = &data[self.pos..];
+            if rest.is_empty() {
+                return Ok(None);
+            }
+            let Some(begin) = rest.iter().position(|&b| b == b'<') else {
+                return self.partial();
+            };
+            if !std::mem::replace(&mut self.started, true) && begin > 0 {
+                let text = &rest[..begin];
+                let text = to_str(text, None).map_err(|k| self.invalid(k))?;
+                preamble = Some(text.trim_end());
+            }
+            self.advance(begin);
+
+            let rest = &data[self.pos..];
+            let Some(end) = rest.iter().position(|&b| b == b'>') else {
+                return self.partial();
+            };
+            let tag = &rest[1..end];
+            let (first, position) =
+                *start.get_or_insert((self.pos, self.current()));
+
+            let header = tag.eq_ignore_ascii_case(b"eoh");
+            if header || tag.eq_ignore_ascii_case(b"eor") {
+                let record = RecordRef {
+                    data: &data[first..self.pos],
+                    header,
+                    preamble: preamble.filter(|_| header),
+                    position,
+                };
+                self.advance(end + 1);
+                self.header = false;
+                return Ok(Some(record));
+            } else if tag.eq_ignore_ascii_case(b"app_lotw_eof") {
+                self.advance(rest.len());
+                return Ok(None);
+            }
+
+            let (name, len, typ) =
+                split_tag(tag).map_err(|k| self.invalid(k))?;
+            let Some(value) = rest.get(end + 1..end + 1 + len) else {
+                return self.partial();
+            };
+            let value =
+                to_str(value, Some(name)).map_err(|k| self.invalid(k))?;
+            // untyped values are always valid, so skip allocating them
+            let userdef = self.header && UserDef::is_userdef_name(name);
+            if (typ.is_some() || userdef)
+                && let Err(k) = parse_field_value(name, value, typ, self.header)
+            {
+                return Err(self.invalid(k));
+            }
+            self.advance(end + 1 + len);
+        }
+    }
+}
+
+impl<'a> Iterator for RecordParser<'a> {
+    type Item = Result<RecordRef<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.parse_record().transpose();
+        self.done = !matches!(res, Some(Ok(_)));
+        res
+    }
+}
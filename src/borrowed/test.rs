@@ -0,0 +1,146 @@
+use futures::StreamExt;
+use rust_decimal::Decimal;
+
+use super::*;
+use crate::RecordStream;
+use crate::test::helpers::*;
+
+/// Parse with both the streaming and borrowed parsers and compare.
+async fn same_as_stream(data: &[u8], ignore_partial: bool) {
+    let expected: Vec<_> =
+        RecordStream::new(data, ignore_partial).collect().await;
+    let actual: Vec<_> = RecordParser::new(data, ignore_partial)
+        .map(|r| r.and_then(|r| r.to_owned()))
+        .collect();
+    assert_eq!(actual, expected, "{}", String::from_utf8_lossy(data));
+}
+
+#[tokio::test]
+async fn matches_stream() {
+    let cases: &[&[u8]] = &[
+        b"",
+        b"  \n ",
+        b"<call:4>W1AW<eor>",
+        b"ADIF Export\r\n<adifver:5>3.1.4<eoh>\n<call:4>W1AW<eor>\n",
+        b"Log <eoh><call:4>W1AW junk <freq:6:n>14.074<eor>",
+        b"<eor><eoh><eor>",
+        b"<call:4>W1AW<eor><call:3>",
+        b"<call:4>W1AW<eor><call:3>W1",
+        b"<call:4>W1AW<eor>junk",
+        b"<call:4>W1AW",
+        b"<call:x>W1AW<eor>",
+        b"<call>W1AW<eor>",
+        b"<call:1:2:3>W<eor>",
+        b"<\xff:1>W<eor>",
+        b"<call:\xff>W<eor>",
+        b"<call:1:\xff>W<eor>",
+        b"<call:2>\xff\xfe<eor>",
+        b"Gr\xfc\xdfe<eoh>",
+        b"<freq:2:n>xx<eor>",
+        b"<qsl:1:b>Y<date:8:d>20240115<time:6:t>123456<eor>",
+        b"<a:2:i>\xc3\xb6<b:11:l>N042 21.123<c:3:e>SSB<eor>",
+        b"<a:2:s>\xc3\xb6<eor>",
+        b"<userdef1:15:N>ShoeSize,{5:20}<eoh><shoesize:2>11<eor>",
+        b"<userdef1:3:N>,{}<eoh>",
+        b"<eoh><userdef1:3>,{}<eor>",
+        b"<a:1>x<eor><userdef1:3:n>,{}<eor>",
+        b"<call:4>W1AW<eor><app_lotw_eof><call:4>N0AA<eor>",
+        b"<call:4>W1AW<eor>\n<call:4>N0AA\n<eor>\n<call:4",
+    ];
+    for data in cases {
+        same_as_stream(data, false).await;
+        same_as_stream(data, true).await;
+    }
+}
+
+#[tokio::test]
+async fn matches_stream_sample() {
+    let data = std::fs::read("examples/sample.adif").unwrap();
+    same_as_stream(&data, false).await;
+}
+
+#[test]
+fn fields() {
+    let data =
+        b"Log\n<adifver:5>3.1.4<eoh>\n<call:4>W1AW <Freq:6:N>14.074\n<eor>";
+    let mut p = RecordParser::new(data, false);
+
+    let header = p.next().unwrap().unwrap();
+    assert!(header.is_header());
+    assert_eq!(header.preamble(), Some("Log"));
+    assert_eq!(
+        header.position(),
+        Position {
+            line: 2,
+            column: 1,
+            byte: 4
+        }
+    );
+
+    let rec = p.next().unwrap().unwrap();
+    assert!(!rec.is_header());
+    assert_eq!(rec.preamble(), None);
+    assert_eq!(
+        rec.position(),
+        Position {
+            line: 3,
+            column: 1,
+            byte: 26
+        }
+    );
+    let fields: Vec<_> = rec.fields().map(|f| f.name()).collect();
+    assert_eq!(fields, ["call", "Freq"]);
+
+    let freq = rec.get("FREQ").unwrap();
+    assert_eq!(freq.name(), "Freq");
+    assert_eq!(freq.value(), "14.074");
+    assert_eq!(freq.type_indicator(), Some("N"));
+    assert_eq!(freq.datum(), Datum::Number(Decimal::new(14074, 3)));
+    assert_eq!(
+        freq.to_owned(),
+        Field::new("Freq", Datum::Number(Decimal::new(14074, 3)))
+    );
+    let call = rec.get("call").unwrap();
+    assert_eq!(call.type_indicator(), None);
+    assert_eq!(call.datum(), Datum::String("W1AW".to_string()));
+    assert!(rec.get("mode").is_none());
+
+    assert!(p.next().is_none());
+    assert!(p.next().is_none());
+}
+
+#[test]
+fn empty_record_position() {
+    let mut p = RecordParser::new(b"  <eor>", false);
+    let rec = p.next().unwrap().unwrap();
+    assert_eq!(rec.fields().count(), 0);
+    assert_eq!(
+        rec.position(),
+        Position {
+            line: 1,
+            column: 3,
+            byte: 2
+        }
+    );
+}
+
+#[test]
+fn duplicate() {
+    let mut p = RecordParser::new(b"<a:1>1<A:1>2<eor><b:1>3<eor>", false);
+    let rec = p.next().unwrap().unwrap();
+    let mut expected = Record::new();
+    expected.insert("a", "1").unwrap();
+    assert_eq!(rec.to_owned().unwrap_err(), duplicate_key("a", expected));
+    assert!(p.next().unwrap().is_ok());
+}
+
+#[test]
+fn errors_end_iteration() {
+    let mut p = RecordParser::new(b"<a:1>1<eor><b:x>2<eor><c:1>3<eor>", false);
+    assert!(p.next().unwrap().is_ok());
+    assert_eq!(
+        p.next().unwrap().unwrap_err(),
+        invalid_format(bad_length("b", "x"), 1, 12, 11)
+    );
+    assert!(p.next().is_none());
+}
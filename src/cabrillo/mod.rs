@@ -41,7 +41,7 @@ impl CabrilloEncoder {
         self.started = true;
         dst.put_slice(b"START-OF-LOG: 3.0\n");
 
-        for (name, value) in r.fields() {
+        for (name, value) in r.output_fields() {
             let key = name.to_uppercase();
             let val = value.to_cabrillo();
 
@@ -59,7 +59,8 @@ impl CabrilloEncoder {
         for (i, f) in self.fields.iter().enumerate() {
             let d = r.get(f).ok_or_else(|| Error::MissingField {
                 field: f.clone(),
-                record: r.clone(),
+                record: Box::new(r.clone()),
+                span: r.span(),
             })?;
 
             if i > 0 {
@@ -6,8 +6,9 @@ use futures::SinkExt;
 use futures::future::poll_fn;
 use futures::sink::Sink;
 
-use crate::test::helpers::TrickleWriter;
-use crate::{CabrilloSink, Error, Record};
+use crate::test::helpers::{TrickleWriter, span};
+use crate::{CabrilloSink, Error, Record, RecordStream};
+use futures::StreamExt;
 
 #[tokio::test]
 async fn basic() {
@@ -75,7 +76,8 @@ async fn missing_field() {
         result.unwrap_err(),
         Error::MissingField {
             field: "freq".to_string(),
-            record: qso_clone
+            record: Box::new(qso_clone),
+            span: None,
         }
     );
 }
@@ -175,3 +177,21 @@ async fn poll_flush_backpressure() {
     .await;
     assert!(pending);
 }
+
+#[tokio::test]
+async fn missing_field_span() {
+    let mut buf = Vec::new();
+    let mut sink = CabrilloSink::new(&mut buf, vec!["call", "freq"]);
+    sink.send(Record::new_header()).await.unwrap();
+
+    let mut s = RecordStream::new("\n<call:4>W1AW<eor>".as_bytes(), true);
+    let qso = s.next().await.unwrap().unwrap();
+    assert_eq!(
+        sink.send(qso.clone()).await.unwrap_err(),
+        Error::MissingField {
+            field: "freq".to_string(),
+            record: Box::new(qso),
+            span: Some(span((2, 1, 1), (2, 13, 13))),
+        }
+    );
+}
@@ -0,0 +1,229 @@
+//! Reading and writing compressed ADIF
+//!
+//! Logs are often archived compressed.  A [Decompress] reader recognizes
+//! gzip and zstd data by their magic bytes and decompresses it on the fly,
+//! passing anything else through unchanged, and a [Compress] writer
+//! compresses output.  [RecordStream::new_decompressed] and
+//! [RecordSink::new_compressed] wrap them around records.
+//!
+//! Each format is enabled by the cargo feature of the same name.
+//!
+//! [RecordStream::new_decompressed]: crate::RecordStream::new_decompressed
+//! [RecordSink::new_compressed]: crate::RecordSink::new_compressed
+
+#[cfg(feature = "gzip")]
+use async_compression::tokio::{bufread::GzipDecoder, write::GzipEncoder};
+#[cfg(feature = "zstd")]
+use async_compression::tokio::{bufread::ZstdDecoder, write::ZstdEncoder};
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{
+    AsyncRead, AsyncReadExt, AsyncWrite, BufReader, Chain, ReadBuf,
+};
+
+#[cfg(test)]
+mod test;
+
+#[cfg(feature = "gzip")]
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const MAGIC_LEN: usize = 4;
+
+/// Compression format of ADIF data
+///
+/// Which formats exist depends on the features enabled, so matches must
+/// have a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Uncompressed
+    None,
+    /// gzip, as in `.adi.gz` files
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Zstandard, as in `.adi.zst` files
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    fn detect(header: &[u8]) -> Self {
+        #[cfg(feature = "gzip")]
+        if header.starts_with(GZIP_MAGIC) {
+            return Self::Gzip;
+        }
+        #[cfg(feature = "zstd")]
+        if header.starts_with(ZSTD_MAGIC) {
+            return Self::Zstd;
+        }
+        Self::None
+    }
+}
+
+type Peeked<R> = Chain<Cursor<Vec<u8>>, R>;
+
+enum Reader<R> {
+    Plain(Peeked<R>),
+    #[cfg(feature = "gzip")]
+    Gzip(GzipDecoder<BufReader<Peeked<R>>>),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdDecoder<BufReader<Peeked<R>>>),
+}
+
+/// Reader that decompresses data if it is compressed
+pub struct Decompress<R> {
+    reader: Reader<R>,
+}
+
+impl<R> Decompress<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Read the start of the data to detect its compression.
+    ///
+    /// Concatenated gzip members are read as one stream.
+    pub async fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(MAGIC_LEN);
+        while header.len() < MAGIC_LEN {
+            let mut buf = [0; MAGIC_LEN];
+            let n = reader.read(&mut buf[..MAGIC_LEN - header.len()]).await?;
+            if n == 0 {
+                break;
+            }
+            header.extend_from_slice(&buf[..n]);
+        }
+        let compression = Compression::detect(&header);
+        let peeked = Cursor::new(header).chain(reader);
+        let reader = match compression {
+            Compression::None => Reader::Plain(peeked),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut d = GzipDecoder::new(BufReader::new(peeked));
+                d.multiple_members(true);
+                Reader::Gzip(d)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                let mut d = ZstdDecoder::new(BufReader::new(peeked));
+                d.multiple_members(true);
+                Reader::Zstd(d)
+            }
+        };
+        Ok(Self { reader })
+    }
+
+    /// Return the compression detected.
+    pub fn compression(&self) -> Compression {
+        match self.reader {
+            Reader::Plain(_) => Compression::None,
+            #[cfg(feature = "gzip")]
+            Reader::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            Reader::Zstd(_) => Compression::Zstd,
+        }
+    }
+}
+
+impl<R> AsyncRead for Decompress<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.reader {
+            Reader::Plain(r) => Pin::new(r).poll_read(cx, buf),
+            #[cfg(feature = "gzip")]
+            Reader::Gzip(r) => Pin::new(r).poll_read(cx, buf),
+            #[cfg(feature = "zstd")]
+            Reader::Zstd(r) => Pin::new(r).poll_read(cx, buf),
+        }
+    }
+}
+
+enum Writer<W> {
+    Plain(W),
+    #[cfg(feature = "gzip")]
+    Gzip(GzipEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdEncoder<W>),
+}
+
+/// Writer that compresses data
+///
+/// The compressed stream is only complete once the writer is shut down,
+/// e.g. by closing the sink writing to it.
+pub struct Compress<W> {
+    writer: Writer<W>,
+}
+
+impl<W> Compress<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Compress data written to the given writer.
+    pub fn new(writer: W, compression: Compression) -> Self {
+        let writer = match compression {
+            Compression::None => Writer::Plain(writer),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Writer::Gzip(GzipEncoder::new(writer)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Writer::Zstd(ZstdEncoder::new(writer)),
+        };
+        Self { writer }
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        match self.writer {
+            Writer::Plain(w) => w,
+            #[cfg(feature = "gzip")]
+            Writer::Gzip(w) => w.into_inner(),
+            #[cfg(feature = "zstd")]
+            Writer::Zstd(w) => w.into_inner(),
+        }
+    }
+}
+
+impl<W> AsyncWrite for Compress<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.writer {
+            Writer::Plain(w) => Pin::new(w).poll_write(cx, buf),
+            #[cfg(feature = "gzip")]
+            Writer::Gzip(w) => Pin::new(w).poll_write(cx, buf),
+            #[cfg(feature = "zstd")]
+            Writer::Zstd(w) => Pin::new(w).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.writer {
+            Writer::Plain(w) => Pin::new(w).poll_flush(cx),
+            #[cfg(feature = "gzip")]
+            Writer::Gzip(w) => Pin::new(w).poll_flush(cx),
+            #[cfg(feature = "zstd")]
+            Writer::Zstd(w) => Pin::new(w).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.writer {
+            Writer::Plain(w) => Pin::new(w).poll_shutdown(cx),
+            #[cfg(feature = "gzip")]
+            Writer::Gzip(w) => Pin::new(w).poll_shutdown(cx),
+            #[cfg(feature = "zstd")]
+            Writer::Zstd(w) => Pin::new(w).poll_shutdown(cx),
+        }
+    }
+}
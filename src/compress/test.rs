@@ -0,0 +1,114 @@
+use futures::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::*;
+use crate::test::helpers::*;
+use crate::{Error, Record, RecordSink, RecordStream};
+
+const ADIF: &str = "<adifver:5>3.1.4<eoh>\n<call:4>W1AW<eor>\n";
+
+struct FailingReader;
+
+impl AsyncRead for FailingReader {
+    fn poll_read(
+        self: Pin<&mut Self>, _cx: &mut Context<'_>, _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Err(io::Error::other("boom")))
+    }
+}
+
+async fn compress(data: &[u8], compression: Compression) -> Vec<u8> {
+    let mut w = Compress::new(Vec::new(), compression);
+    w.write_all(data).await.unwrap();
+    w.flush().await.unwrap();
+    w.shutdown().await.unwrap();
+    w.into_inner()
+}
+
+async fn decompress<R>(reader: R, expected: Compression) -> String
+where
+    R: AsyncRead + Unpin,
+{
+    let mut r = Decompress::new(reader).await.unwrap();
+    assert_eq!(r.compression(), expected);
+    let mut s = String::new();
+    r.read_to_string(&mut s).await.unwrap();
+    s
+}
+
+async fn roundtrip(compression: Compression) {
+    let mut buf = Vec::new();
+    let mut sink = RecordSink::new_compressed(&mut buf, compression);
+    let mut header = Record::new_header();
+    header.insert("adifver", "3.1.4").unwrap();
+    sink.send(header).await.unwrap();
+    let mut record = Record::new();
+    record.insert("call", "W1AW").unwrap();
+    sink.send(record.clone()).await.unwrap();
+    sink.close().await.unwrap();
+    assert_eq!(buf.starts_with(b"<"), compression == Compression::None);
+
+    let mut s = RecordStream::new_decompressed(&buf[..], false)
+        .await
+        .unwrap();
+    assert!(s.next().await.unwrap().unwrap().is_header());
+    assert_eq!(s.next().await.unwrap().unwrap(), record);
+    assert!(s.next().await.is_none());
+}
+
+#[tokio::test]
+async fn plain() {
+    roundtrip(Compression::None).await;
+    let data = compress(ADIF.as_bytes(), Compression::None).await;
+    assert_eq!(data, ADIF.as_bytes());
+    for adif in ["", "<", "<eo", "<eor", "<eor>"] {
+        let s = decompress(adif.as_bytes(), Compression::None).await;
+        assert_eq!(s, adif);
+    }
+    let reader = TrickleReader::new(ADIF, 1);
+    assert_eq!(decompress(reader, Compression::None).await, ADIF);
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn gzip() {
+    roundtrip(Compression::Gzip).await;
+    let mut data = compress(ADIF.as_bytes(), Compression::Gzip).await;
+    assert!(data.starts_with(GZIP_MAGIC));
+    data.extend(compress(b"<call:4>N0AA<eor>\n", Compression::Gzip).await);
+    let expected = format!("{ADIF}<call:4>N0AA<eor>\n");
+    let s = decompress(&data[..], Compression::Gzip).await;
+    assert_eq!(s, expected);
+    let reader = TrickleReader::new(&data, 1);
+    assert_eq!(decompress(reader, Compression::Gzip).await, expected);
+}
+
+#[cfg(feature = "zstd")]
+#[tokio::test]
+async fn zstd() {
+    roundtrip(Compression::Zstd).await;
+    let mut data = compress(ADIF.as_bytes(), Compression::Zstd).await;
+    assert!(data.starts_with(ZSTD_MAGIC));
+    data.extend(compress(b"<call:4>N0AA<eor>\n", Compression::Zstd).await);
+    let expected = format!("{ADIF}<call:4>N0AA<eor>\n");
+    let s = decompress(&data[..], Compression::Zstd).await;
+    assert_eq!(s, expected);
+    let reader = TrickleReader::new(&data, 1);
+    assert_eq!(decompress(reader, Compression::Zstd).await, expected);
+}
+
+#[tokio::test]
+async fn errors() {
+    let res = RecordStream::new_decompressed(FailingReader, true).await;
+    assert!(matches!(res, Err(Error::Io(_))));
+
+    // corrupt data after a valid magic number
+    #[cfg(feature = "gzip")]
+    {
+        let data = [0x1f, 0x8b, 0, 0, 0, 0];
+        let mut s = RecordStream::new_decompressed(&data[..], true)
+            .await
+            .unwrap();
+        assert!(matches!(s.next().await, Some(Err(Error::Io(_)))));
+    }
+}
@@ -0,0 +1,167 @@
+//! Conversion of field values to and from Rust types
+
+use crate::parse::parse_as;
+use crate::{DataType, Datum, Duplicates, Error, Location, Record};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use rust_decimal::Decimal;
+use std::borrow::Cow;
+
+/// Conversion of a field value to a Rust type
+///
+/// Implemented for the types to which a [Datum] can be coerced, this
+/// is what reads the fields of a struct deriving
+/// [AdifRecord](crate::AdifRecord).
+///
+/// ```
+/// use difa::{Datum, FromDatum};
+/// let datum = Datum::String("291".to_string());
+/// assert_eq!(u16::from_datum(&datum), Some(291));
+/// assert_eq!(u8::from_datum(&datum), None);
+/// ```
+pub trait FromDatum: Sized {
+    /// Convert a value, returning [None] if it is not valid for the type.
+    fn from_datum(datum: &Datum) -> Option<Self>;
+}
+
+impl FromDatum for Datum {
+    fn from_datum(datum: &Datum) -> Option<Self> {
+        Some(datum.clone())
+    }
+}
+
+impl FromDatum for String {
+    fn from_datum(datum: &Datum) -> Option<Self> {
+        Some(datum.as_str().into_owned())
+    }
+}
+
+impl FromDatum for bool {
+    fn from_datum(datum: &Datum) -> Option<Self> {
+        datum.as_bool()
+    }
+}
+
+impl FromDatum for Decimal {
+    fn from_datum(datum: &Datum) -> Option<Self> {
+        datum.as_number()
+    }
+}
+
+impl FromDatum for NaiveDate {
+    fn from_datum(datum: &Datum) -> Option<Self> {
+        datum.as_date()
+    }
+}
+
+impl FromDatum for NaiveTime {
+    fn from_datum(datum: &Datum) -> Option<Self> {
+        datum.as_time()
+    }
+}
+
+impl FromDatum for NaiveDateTime {
+    fn from_datum(datum: &Datum) -> Option<Self> {
+        datum.as_datetime()
+    }
+}
+
+impl FromDatum for Location {
+    fn from_datum(datum: &Datum) -> Option<Self> {
+        datum.as_location()
+    }
+}
+
+macro_rules! integers {
+    ($($t:ty),*) => {
+        $(
+            impl FromDatum for $t {
+                fn from_datum(datum: &Datum) -> Option<Self> {
+                    datum.as_integer()?.try_into().ok()
+                }
+            }
+
+            impl From<$t> for Datum {
+                fn from(value: $t) -> Self {
+                    Datum::Number(value.into())
+                }
+            }
+        )*
+    };
+}
+
+integers!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+/// Read an optional field of a struct, treating an empty value as absent.
+///
+/// An untyped string is first parsed as the given type, if any.
+pub fn optional<T>(
+    record: &Record, name: &str, typ: Option<DataType>,
+) -> Result<Option<T>, Error>
+where
+    T: FromDatum,
+{
+    let Some(value) = record.get(name) else {
+        return Ok(None);
+    };
+    let text = value.as_str();
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    let typed = match (typ, value) {
+        (Some(typ), Datum::String(s)) => parse_as(s, typ).map(Cow::Owned),
+        _ => Some(Cow::Borrowed(value)),
+    };
+    match typed.as_deref().and_then(T::from_datum) {
+        Some(v) => Ok(Some(v)),
+        None => Err(Error::InvalidValue {
+            field: name.to_string(),
+            value: text.into_owned(),
+            record: Box::new(record.clone()),
+            span: record.field_span(name),
+        }),
+    }
+}
+
+/// Read a required field of a struct.
+pub fn required<T>(
+    record: &Record, name: &str, typ: Option<DataType>,
+) -> Result<T, Error>
+where
+    T: FromDatum,
+{
+    optional(record, name, typ)?.ok_or_else(|| Error::MissingField {
+        field: name.to_string(),
+        record: Box::new(record.clone()),
+        span: record.span(),
+    })
+}
+
+/// Remove the fields of a record that were read into a struct.
+///
+/// Later occurrences kept with [Duplicates::KeepAll] stay, even of the
+/// fields removed, so that they are written out again after the struct
+/// is converted back.
+pub fn remaining(mut record: Record, known: &[&str]) -> Record {
+    let extra = std::mem::take(&mut record.extra);
+    record
+        .retain(|name, _| !known.iter().any(|k| k.eq_ignore_ascii_case(name)));
+    record.extra = extra;
+    record
+}
+
+/// Write a field of a struct, replacing any field of the same name.
+///
+/// A string is written as the given type if it is valid for it.
+pub fn put<V>(record: &mut Record, name: &str, value: V, typ: Option<DataType>)
+where
+    V: Into<Datum>,
+{
+    let value = match (typ, value.into()) {
+        (Some(typ), Datum::String(s)) => {
+            parse_as(&s, typ).unwrap_or(Datum::String(s))
+        }
+        (_, value) => value,
+    };
+    // cannot fail when replacing duplicates
+    let _ = record.insert_with(name, value, Duplicates::Last);
+}
@@ -0,0 +1,242 @@
+//! Rendering errors with the input that caused them
+//!
+//! A [Source] holds the data that was parsed and renders each [Error] with
+//! a [Position] as a diagnostic in the style of a compiler, showing the
+//! offending line with the bad tag underlined:
+//!
+//! ```text
+//! error: Invalid ADIF format: invalid length 'x' for field 'freq' (at line 2, column 13 (byte 30))
+//!  --> log.adi:2:13
+//!   |
+//! 2 | <call:4>W1AW<freq:x>14<eor>
+//!   |             ^^^^^^^^
+//! ```
+//!
+//! The errors returned by a lenient parse can be rendered together as a
+//! single report.  Long lines, such as those of files that put every
+//! record on one line, are shortened around the offending tag.
+//!
+//! [Error]: enum@crate::Error
+//! [Position]: crate::Position
+
+use crate::Error;
+use std::fmt::Write;
+
+#[cfg(test)]
+mod test;
+
+// characters of a line shown, and of those, how many precede the error
+const WIDTH: usize = 100;
+const CONTEXT: usize = 20;
+const ELLIPSIS: &str = "...";
+
+struct Style {
+    error: &'static str,
+    gutter: &'static str,
+    caret: &'static str,
+    bold: &'static str,
+    reset: &'static str,
+}
+
+const PLAIN: Style = Style {
+    error: "",
+    gutter: "",
+    caret: "",
+    bold: "",
+    reset: "",
+};
+
+const COLOR: Style = Style {
+    error: "\x1b[1;31m",
+    gutter: "\x1b[1;34m",
+    caret: "\x1b[1;31m",
+    bold: "\x1b[1m",
+    reset: "\x1b[0m",
+};
+
+/// ADIF data against which errors are rendered
+///
+/// The data must be the whole input from its beginning, so that the byte
+/// offsets of positions locate the offending lines.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use difa::diagnostic::Source;
+/// use difa::{RecordStream, Recovery};
+/// use futures::StreamExt;
+/// let data = b"<call:4>W1AW<eor>\n<call:4>N0AA<freq:x>14<eor>\n";
+/// let s = RecordStream::new_lenient(&data[..], Recovery::Drop);
+/// let errors: Vec<_> = s.filter_map(async |r| r.err()).collect().await;
+/// let report = Source::new(data).with_name("log.adi").report(&errors);
+/// assert!(report.contains(" --> log.adi:2:13\n"));
+/// assert!(report.contains("2 | <call:4>N0AA<freq:x>14<eor>\n"));
+/// assert!(report.contains("  |             ^^^^^^^^\n"));
+/// # });
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Source<'a> {
+    data: &'a [u8],
+    name: Option<&'a str>,
+    color: bool,
+}
+
+impl<'a> Source<'a> {
+    /// Render errors against the given data.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            name: None,
+            color: false,
+        }
+    }
+
+    /// Refer to the data by the given name, e.g. that of its file.
+    pub fn with_name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Highlight diagnostics with ANSI escape sequences for a terminal.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Render a single error.
+    ///
+    /// An error without a position, or with one outside the data, is
+    /// rendered as its message alone.
+    pub fn render(&self, error: &Error) -> String {
+        let mut out = String::new();
+        self.render_to(&mut out, error);
+        out
+    }
+
+    /// Render any number of errors as one report, followed by a count.
+    pub fn report<'e, I>(&self, errors: I) -> String
+    where
+        I: IntoIterator<Item = &'e Error>,
+    {
+        let style = self.style();
+        let mut out = String::new();
+        let mut n = 0;
+        for error in errors {
+            if n > 0 {
+                out.push('\n');
+            }
+            self.render_to(&mut out, error);
+            n += 1;
+        }
+        if n > 0 {
+            let s = if n == 1 { "" } else { "s" };
+            out.push('\n');
+            let _ = writeln!(out, "{}{n} error{s}{}", style.bold, style.reset);
+        }
+        out
+    }
+
+    fn style(&self) -> &'static Style {
+        if self.color { &COLOR } else { &PLAIN }
+    }
+
+    fn render_to(&self, out: &mut String, error: &Error) {
+        let style = self.style();
+        let Style {
+            error: red,
+            gutter: blue,
+            caret,
+            bold,
+            reset,
+        } = style;
+        let _ = writeln!(out, "{red}error{reset}{bold}: {error}{reset}");
+        let Some(position) = error.position() else {
+            return;
+        };
+        let Some((text, width)) = self.snippet(error) else {
+            return;
+        };
+
+        let line = position.line.to_string();
+        let pad = " ".repeat(line.len());
+        if let Some(name) = self.name {
+            let (l, c) = (position.line, position.column);
+            let _ = writeln!(out, "{pad}{blue}-->{reset} {name}:{l}:{c}");
+        }
+        let _ = writeln!(out, "{pad} {blue}|{reset}");
+        let _ = writeln!(out, "{blue}{line} |{reset} {}", text.shown);
+        let carets = "^".repeat(width);
+        let _ = writeln!(
+            out,
+            "{pad} {blue}|{reset} {}{caret}{carets}{reset}",
+            text.indent
+        );
+    }
+
+    /// Find the line containing the error and the extent of the error
+    /// within it.
+    fn snippet(&self, error: &Error) -> Option<(Snippet, usize)> {
+        let data = self.data;
+        let byte = error.position()?.byte;
+        let begin = data.get(..byte)?.iter().rposition(|&b| b == b'\n');
+        let begin = begin.map_or(0, |n| n + 1);
+        let end = data[byte..].iter().position(|&b| b == b'\n');
+        let mut end = end.map_or(data.len(), |n| byte + n);
+        if end > byte && data[end - 1] == b'\r' {
+            end -= 1;
+        }
+
+        // underline the field or record, or else the tag at the position
+        let len = match error.span() {
+            Some(span) => span.end.byte.min(end).saturating_sub(byte),
+            None if data.get(byte) == Some(&b'<') => {
+                let tag = data[byte..end].iter().position(|&b| b == b'>');
+                tag.map_or(end - byte, |n| n + 1)
+            }
+            None => 0,
+        };
+        let before = String::from_utf8_lossy(&data[begin..byte]);
+        let marked = String::from_utf8_lossy(&data[byte..byte + len]);
+        let after = String::from_utf8_lossy(&data[byte + len..end]);
+        let chars: Vec<char> = before
+            .chars()
+            .chain(marked.chars())
+            .chain(after.chars())
+            .collect();
+        let start = before.chars().count();
+        let len = marked.chars().count();
+        Some(Snippet::new(&chars, start, len))
+    }
+}
+
+struct Snippet {
+    shown: String,
+    indent: String,
+}
+
+impl Snippet {
+    /// Show a window of a line containing the marked characters, returning
+    /// the number of them still visible.
+    fn new(chars: &[char], start: usize, len: usize) -> (Self, usize) {
+        let first = if chars.len() > WIDTH {
+            start.saturating_sub(CONTEXT)
+        } else {
+            0
+        };
+        let last = chars.len().min(first + WIDTH);
+        let mut shown = String::new();
+        let mut indent = String::new();
+        if first > 0 {
+            shown.push_str(ELLIPSIS);
+            indent.push_str(&" ".repeat(ELLIPSIS.len()));
+        }
+        shown.extend(&chars[first..last]);
+        if last < chars.len() {
+            shown.push_str(ELLIPSIS);
+        }
+        // keep tabs so that the carets line up however they are displayed
+        let tab = |&c: &char| if c == '\t' { '\t' } else { ' ' };
+        indent.extend(chars[first..start].iter().map(tab));
+        let width = len.min(last.saturating_sub(start)).max(1);
+        (Self { shown, indent }, width)
+    }
+}
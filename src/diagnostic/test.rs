@@ -0,0 +1,159 @@
+use futures::StreamExt;
+
+use super::*;
+use crate::test::helpers::*;
+use crate::{Record, RecordStream, Recovery};
+
+async fn errors(data: &[u8]) -> Vec<Error> {
+    let s = RecordStream::new_lenient(data, Recovery::Drop);
+    s.filter_map(async |r| r.err()).collect().await
+}
+
+async fn render(data: &[u8]) -> String {
+    let errors = errors(data).await;
+    Source::new(data).render(&errors[0])
+}
+
+#[tokio::test]
+async fn invalid_tag() {
+    let data = b"<call:4>W1AW<eor>\n<call:4>N0AA<freq:x>14<eor>\n";
+    let errors = errors(data).await;
+    let expected = "\
+error: Invalid ADIF format: invalid length 'x' for field 'freq' \
+(at line 2, column 13 (byte 30))
+ --> log.adi:2:13
+  |
+2 | <call:4>N0AA<freq:x>14<eor>
+  |             ^^^^^^^^
+";
+    let source = Source::new(data).with_name("log.adi");
+    assert_eq!(source.render(&errors[0]), expected);
+
+    // unterminated tags are underlined to the end of the line
+    let s = render(b"<call:4>W1AW<freq:6\r\n<eor>").await;
+    assert!(
+        s.ends_with("\n1 | <call:4>W1AW<freq:6\n  |             ^^^^^^^\n")
+    );
+}
+
+#[tokio::test]
+async fn spans() {
+    // a duplicate field is underlined in full
+    let s = render(b"<a:1>1 <a:2>22 <b:1>3<eor>").await;
+    assert!(
+        s.ends_with("1 | <a:1>1 <a:2>22 <b:1>3<eor>\n  |        ^^^^^^^\n")
+    );
+
+    // a record spanning lines is underlined to the end of its first line
+    let mut record = Record::new();
+    record.insert("a", "1").unwrap();
+    let err = Error::MissingField {
+        field: "call".into(),
+        record: Box::new(record),
+        span: Some(span((3, 3, 4), (4, 7, 17))),
+    };
+    let data = b"\n\n  <a:1>1\n<b:1>2<eor>";
+    let expected = "\
+error: missing required field 'call' in record (at line 3, column 3 (byte 4))
+  |
+3 |   <a:1>1
+  |   ^^^^^^
+";
+    assert_eq!(Source::new(data).render(&err), expected);
+}
+
+#[tokio::test]
+async fn positions() {
+    // partial data at the end is marked from the incomplete tag
+    let s = render(b"<call:4>W1AW<eor>\n<call:4>W1").await;
+    assert!(s.ends_with("2 | <call:4>W1\n  | ^^^^^^^^\n"));
+
+    // or after the last line if nothing is left
+    let err = partial_data(2, 1, 6);
+    let s = Source::new(b"<eor>\n").render(&err);
+    assert!(s.ends_with("2 | \n  | ^\n"));
+
+    // positions not at a tag are marked alone
+    let err = invalid_format(other("bad"), 1, 3, 2);
+    let s = Source::new(b"abcdef").render(&err);
+    assert!(s.ends_with("1 | abcdef\n  |   ^\n"));
+
+    // errors without a known position are shown without a snippet
+    let s = Source::new(b"<eor>").render(&Error::MissingHeader);
+    assert_eq!(s, "error: first record must be a header record\n");
+    let err = invalid_format(other("bad"), 1, 7, 6);
+    let s = Source::new(b"<eor>").with_name("log.adi").render(&err);
+    assert_eq!(s.lines().count(), 1);
+
+    // line numbers widen the gutter
+    let data = "\n".repeat(99) + "<a:x>";
+    let errors = errors(data.as_bytes()).await;
+    let s = Source::new(data.as_bytes())
+        .with_name("x")
+        .render(&errors[0]);
+    let expected = "\
+   --> x:100:1
+    |
+100 | <a:x>
+    | ^^^^^
+";
+    assert!(s.ends_with(expected), "{s}");
+}
+
+#[tokio::test]
+async fn long_lines() {
+    let data =
+        format!("<a:60>{}<b:x>2{}<eor>", "x".repeat(60), "x".repeat(120));
+    let s = render(data.as_bytes()).await;
+    let shown = format!("...{}...", &data[46..146]);
+    let expected = format!("1 | {shown}\n  | {}^^^^^\n", " ".repeat(23));
+    assert!(s.ends_with(&expected), "{s}");
+
+    // the underline stops at the end of the window
+    let data = format!("<a:x{}>", "x".repeat(200));
+    let s = render(data.as_bytes()).await;
+    let shown = format!("{}...", &data[..100]);
+    let expected = format!("1 | {shown}\n  | {}\n", "^".repeat(100));
+    assert!(s.ends_with(&expected), "{s}");
+}
+
+#[tokio::test]
+async fn text() {
+    // tabs are kept to align the carets
+    let s = render(b"\t<a:1>1 \t<b:x>2").await;
+    assert!(s.ends_with("1 | \t<a:1>1 \t<b:x>2\n  | \t       \t^^^^^\n"));
+
+    // characters rather than bytes are counted
+    let s = render("<a:2>\u{e9}<b:x>".as_bytes()).await;
+    assert!(s.ends_with("1 | <a:2>\u{e9}<b:x>\n  |       ^^^^^\n"));
+}
+
+#[tokio::test]
+async fn color() {
+    let data = b"<a:x>";
+    let errors = errors(data).await;
+    let s = Source::new(data).with_name("f").with_color(true);
+    let expected = "\
+\x1b[1;31merror\x1b[0m\x1b[1m: Invalid ADIF format: invalid length 'x' \
+for field 'a' (at line 1, column 1 (byte 0))\x1b[0m
+ \x1b[1;34m-->\x1b[0m f:1:1
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1 |\x1b[0m <a:x>
+  \x1b[1;34m|\x1b[0m \x1b[1;31m^^^^^\x1b[0m
+";
+    assert_eq!(s.render(&errors[0]), expected);
+    let report = s.report(&errors);
+    assert!(report.ends_with("\n\n\x1b[1m2 errors\x1b[0m\n"));
+}
+
+#[tokio::test]
+async fn report() {
+    let data = b"<a:x>1<eor>\n<b:1>2<b:1>3<eor>\n<c:1:n>x<eor>";
+    let errors = errors(data).await;
+    assert_eq!(errors.len(), 3);
+    let source = Source::new(data);
+    let report = source.report(&errors);
+    let each: Vec<_> = errors.iter().map(|e| source.render(e)).collect();
+    assert_eq!(report, format!("{}\n3 errors\n", each.join("\n")));
+    assert_eq!(source.report([]), "");
+}
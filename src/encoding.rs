@@ -0,0 +1,113 @@
+use std::borrow::Cow;
+
+/// Character encoding of ADIF data
+///
+/// The ADIF specification calls for ASCII, but many loggers write their
+/// local code page instead, so UTF-8 and the two most common single-byte
+/// encodings are supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// UTF-8, of which ASCII is a subset
+    #[default]
+    Utf8,
+    /// ISO 8859-1, where every byte is the code point of the same value
+    Latin1,
+    /// Windows code page 1252, a superset of the printable part of
+    /// ISO 8859-1 with typographic characters in 0x80 through 0x9f
+    Windows1252,
+}
+
+/// Unit in which the length of an ADIF value is counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthUnit {
+    /// Lengths count bytes of the encoded value
+    #[default]
+    Bytes,
+    /// Lengths count characters, as some loggers write them
+    Chars,
+}
+
+/// Windows-1252 characters for bytes 0x80 through 0x9f.  The five
+/// unassigned bytes map to the C1 control of the same value, as in the
+/// WHATWG encoding standard, so that decoding never fails.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}',
+    '\u{2020}', '\u{2021}', '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}',
+    '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}', '\u{90}', '\u{2018}', '\u{2019}',
+    '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{2dc}',
+    '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}',
+    '\u{178}',
+];
+
+impl Encoding {
+    /// Decode bytes into a string, or [None] if they are invalid.
+    pub(crate) fn decode<'a>(&self, data: &'a [u8]) -> Option<Cow<'a, str>> {
+        match self {
+            Self::Utf8 => str::from_utf8(data).ok().map(Cow::Borrowed),
+            _ if data.is_ascii() => {
+                str::from_utf8(data).ok().map(Cow::Borrowed)
+            }
+            Self::Latin1 => {
+                Some(Cow::Owned(data.iter().map(|&b| b as char).collect()))
+            }
+            Self::Windows1252 => Some(Cow::Owned(
+                data.iter()
+                    .map(|&b| match b {
+                        0x80..=0x9f => WINDOWS_1252[usize::from(b - 0x80)],
+                        b => b as char,
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Encode a string into bytes, or [None] if it contains a character
+    /// that is not representable in this encoding.
+    pub(crate) fn encode<'a>(&self, s: &'a str) -> Option<Cow<'a, [u8]>> {
+        match self {
+            Self::Utf8 => Some(Cow::Borrowed(s.as_bytes())),
+            _ if s.is_ascii() => Some(Cow::Borrowed(s.as_bytes())),
+            Self::Latin1 => s
+                .chars()
+                .map(|c| u8::try_from(c).ok())
+                .collect::<Option<Vec<_>>>()
+                .map(Cow::Owned),
+            Self::Windows1252 => s
+                .chars()
+                .map(|c| match WINDOWS_1252.iter().position(|&w| w == c) {
+                    Some(i) => u8::try_from(i + 0x80).ok(),
+                    None => u8::try_from(c)
+                        .ok()
+                        .filter(|b| !(0x80..0xa0).contains(b)),
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(Cow::Owned),
+        }
+    }
+
+    /// Number of bytes taken by the first `chars` characters of `data`,
+    /// or [None] if `data` is too short to tell.
+    pub(crate) fn char_bytes(
+        &self, data: &[u8], chars: usize,
+    ) -> Option<usize> {
+        match self {
+            Self::Utf8 => {
+                let mut end = 0;
+                for _ in 0..chars {
+                    // invalid lead bytes count as one byte so that the
+                    // subsequent UTF-8 validation reports them
+                    end += match data.get(end)? {
+                        0xc0..=0xdf => 2,
+                        0xe0..=0xef => 3,
+                        0xf0..=0xf7 => 4,
+                        _ => 1,
+                    };
+                }
+                (end <= data.len()).then_some(end)
+            }
+            Self::Latin1 | Self::Windows1252 => {
+                (chars <= data.len()).then_some(chars)
+            }
+        }
+    }
+}
@@ -0,0 +1,657 @@
+//! Enumerations defined by the ADIF specification
+//!
+//! Each enumeration parses case-insensitively from its ADIF value and
+//! displays as the value appears in the specification.  Values are those
+//! of ADIF 3.1.4, including deprecated values such as the import-only
+//! modes that are now submodes, so that older logs can still be read.
+//!
+//! ```
+//! use difa::{Band, Datum, Mode, Submode};
+//! use rust_decimal::Decimal;
+//!
+//! let band: Band = "20M".parse().unwrap();
+//! assert_eq!(band, Band::M20);
+//! assert_eq!(band.to_string(), "20m");
+//! assert!(band.contains(Decimal::new(14074, 3)));
+//!
+//! let sub = Datum::String("ft4".into()).as_submode().unwrap();
+//! assert_eq!(sub, Submode::Ft4);
+//! assert_eq!(sub.mode(), Mode::Mfsk);
+//! ```
+
+use crate::{Datum, FromDatum};
+use rust_decimal::Decimal;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[cfg(test)]
+mod test;
+
+/// Error parsing the value of an enumeration
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid {kind} '{value}'")]
+pub struct ParseEnumError {
+    kind: &'static str,
+    value: String,
+}
+
+macro_rules! enumeration {
+    (
+        $(#[$meta:meta])*
+        $name:ident, $kind:literal {
+            $($variant:ident => $text:literal $(: $desc:literal)?),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum $name {
+            $(
+                #[doc = concat!("`", $text, "`" $(, " (", $desc, ")")?)]
+                $variant,
+            )*
+        }
+
+        impl $name {
+            /// All values, in the order of the specification
+            pub const ALL: &[$name] = &[$($name::$variant),*];
+
+            /// Return the value as it appears in the specification.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $text,)*
+                }
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseEnumError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::ALL
+                    .iter()
+                    .copied()
+                    .find(|v| v.as_str().eq_ignore_ascii_case(s))
+                    .ok_or_else(|| ParseEnumError {
+                        kind: $kind,
+                        value: s.to_string(),
+                    })
+            }
+        }
+
+        impl From<$name> for Datum {
+            fn from(value: $name) -> Self {
+                Datum::Enumeration(value.as_str().to_string())
+            }
+        }
+
+        impl FromDatum for $name {
+            fn from_datum(datum: &Datum) -> Option<Self> {
+                datum.as_str().parse().ok()
+            }
+        }
+    };
+}
+
+macro_rules! bands {
+    ($($variant:ident => $text:literal, $lower:literal, $upper:literal),* $(,)?) => {
+        enumeration! {
+            /// Amateur radio band, from the `BAND` enumeration
+            ///
+            /// Bands are ordered by frequency.
+            Band, "band" {
+                $($variant => $text),*
+            }
+        }
+
+        impl Band {
+            /// Return the lower and upper limits of the band in hertz.
+            fn limits(&self) -> (u64, u64) {
+                match self {
+                    $(Band::$variant => ($lower, $upper),)*
+                }
+            }
+        }
+    };
+}
+
+bands! {
+    M2190 => "2190m", 135_700, 137_800,
+    M630 => "630m", 472_000, 479_000,
+    M560 => "560m", 501_000, 504_000,
+    M160 => "160m", 1_800_000, 2_000_000,
+    M80 => "80m", 3_500_000, 4_000_000,
+    M60 => "60m", 5_060_000, 5_450_000,
+    M40 => "40m", 7_000_000, 7_300_000,
+    M30 => "30m", 10_100_000, 10_150_000,
+    M20 => "20m", 14_000_000, 14_350_000,
+    M17 => "17m", 18_068_000, 18_168_000,
+    M15 => "15m", 21_000_000, 21_450_000,
+    M12 => "12m", 24_890_000, 24_990_000,
+    M10 => "10m", 28_000_000, 29_700_000,
+    M8 => "8m", 40_000_000, 45_000_000,
+    M6 => "6m", 50_000_000, 54_000_000,
+    M5 => "5m", 54_000_001, 69_900_000,
+    M4 => "4m", 70_000_000, 71_000_000,
+    M2 => "2m", 144_000_000, 148_000_000,
+    M1_25 => "1.25m", 222_000_000, 225_000_000,
+    Cm70 => "70cm", 420_000_000, 450_000_000,
+    Cm33 => "33cm", 902_000_000, 928_000_000,
+    Cm23 => "23cm", 1_240_000_000, 1_300_000_000,
+    Cm13 => "13cm", 2_300_000_000, 2_450_000_000,
+    Cm9 => "9cm", 3_300_000_000, 3_500_000_000,
+    Cm6 => "6cm", 5_650_000_000, 5_925_000_000,
+    Cm3 => "3cm", 10_000_000_000, 10_500_000_000,
+    Cm1_25 => "1.25cm", 24_000_000_000, 24_250_000_000,
+    Mm6 => "6mm", 47_000_000_000, 47_200_000_000,
+    Mm4 => "4mm", 75_500_000_000, 81_000_000_000,
+    Mm2_5 => "2.5mm", 119_980_000_000, 123_000_000_000,
+    Mm2 => "2mm", 134_000_000_000, 149_000_000_000,
+    Mm1 => "1mm", 241_000_000_000, 250_000_000_000,
+    Submm => "submm", 300_000_000_000, 7_500_000_000_000,
+}
+
+fn mhz(hz: u64) -> Decimal {
+    Decimal::from(hz) / Decimal::from(1_000_000)
+}
+
+impl Band {
+    /// Return the lower limit of the band in megahertz.
+    pub fn lower(&self) -> Decimal {
+        mhz(self.limits().0)
+    }
+
+    /// Return the upper limit of the band in megahertz.
+    pub fn upper(&self) -> Decimal {
+        mhz(self.limits().1)
+    }
+
+    /// Return whether a frequency in megahertz lies within the band,
+    /// inclusive of its limits.
+    pub fn contains(&self, freq: Decimal) -> bool {
+        (self.lower()..=self.upper()).contains(&freq)
+    }
+
+    /// Return the band containing a frequency in megahertz, if any.
+    ///
+    /// ```
+    /// use difa::Band;
+    /// use rust_decimal::Decimal;
+    /// assert_eq!(Band::from_freq(Decimal::new(7074, 3)), Some(Band::M40));
+    /// assert_eq!(Band::from_freq(Decimal::new(27185, 3)), None);
+    /// ```
+    pub fn from_freq(freq: Decimal) -> Option<Band> {
+        Self::ALL.iter().copied().find(|b| b.contains(freq))
+    }
+}
+
+enumeration! {
+    /// Mode of a contact, from the `MODE` enumeration
+    Mode, "mode" {
+        Am => "AM",
+        AmtorFec => "AMTORFEC": "deprecated, submode of TOR",
+        Ardop => "ARDOP",
+        Asci => "ASCI": "deprecated, submode of RTTY",
+        Atv => "ATV",
+        C4fm => "C4FM": "deprecated, submode of DIGITALVOICE",
+        Chip => "CHIP",
+        Chip64 => "CHIP64": "deprecated, submode of CHIP",
+        Chip128 => "CHIP128": "deprecated, submode of CHIP",
+        Clo => "CLO",
+        Contesti => "CONTESTI",
+        Cw => "CW",
+        DigitalVoice => "DIGITALVOICE",
+        Domino => "DOMINO",
+        DominoF => "DOMINOF": "deprecated, submode of DOMINO",
+        Dstar => "DSTAR": "deprecated, submode of DIGITALVOICE",
+        Dynamic => "DYNAMIC",
+        Fax => "FAX",
+        Fm => "FM",
+        FmHell => "FMHELL": "deprecated, submode of HELL",
+        Fsk31 => "FSK31": "deprecated, submode of PSK",
+        Fsk441 => "FSK441",
+        Ft8 => "FT8",
+        Gtor => "GTOR": "deprecated, submode of TOR",
+        Hell => "HELL",
+        Hell80 => "HELL80": "deprecated, submode of HELL",
+        Hfsk => "HFSK": "deprecated, submode of HELL",
+        Iscat => "ISCAT",
+        Jt4 => "JT4",
+        Jt4a => "JT4A": "deprecated, submode of JT4",
+        Jt6m => "JT6M",
+        Jt9 => "JT9",
+        Jt44 => "JT44",
+        Jt65 => "JT65",
+        Jt65a => "JT65A": "deprecated, submode of JT65",
+        Jt65b => "JT65B": "deprecated, submode of JT65",
+        Jt65c => "JT65C": "deprecated, submode of JT65",
+        Mfsk => "MFSK",
+        Mfsk8 => "MFSK8": "deprecated, submode of MFSK",
+        Mfsk16 => "MFSK16": "deprecated, submode of MFSK",
+        Msk144 => "MSK144",
+        Mt63 => "MT63",
+        Olivia => "OLIVIA",
+        Opera => "OPERA",
+        Pac => "PAC",
+        Pac2 => "PAC2": "deprecated, submode of PAC",
+        Pac3 => "PAC3": "deprecated, submode of PAC",
+        Pax => "PAX",
+        Pax2 => "PAX2": "deprecated, submode of PAX",
+        Pcw => "PCW": "deprecated, submode of CW",
+        Pkt => "PKT",
+        Psk => "PSK",
+        Psk2k => "PSK2K",
+        Psk10 => "PSK10": "deprecated, submode of PSK",
+        Psk31 => "PSK31": "deprecated, submode of PSK",
+        Psk63 => "PSK63": "deprecated, submode of PSK",
+        Psk63f => "PSK63F": "deprecated, submode of PSK",
+        Psk125 => "PSK125": "deprecated, submode of PSK",
+        PskAm10 => "PSKAM10": "deprecated, submode of PSK",
+        PskAm31 => "PSKAM31": "deprecated, submode of PSK",
+        PskAm50 => "PSKAM50": "deprecated, submode of PSK",
+        PskFec31 => "PSKFEC31": "deprecated, submode of PSK",
+        PskHell => "PSKHELL": "deprecated, submode of HELL",
+        Q15 => "Q15",
+        Qpsk31 => "QPSK31": "deprecated, submode of PSK",
+        Qpsk63 => "QPSK63": "deprecated, submode of PSK",
+        Qpsk125 => "QPSK125": "deprecated, submode of PSK",
+        Qra64 => "QRA64",
+        Ros => "ROS",
+        Rtty => "RTTY",
+        Rttym => "RTTYM",
+        Ssb => "SSB",
+        Sstv => "SSTV",
+        T10 => "T10",
+        Thor => "THOR",
+        Thrb => "THRB",
+        Thrbx => "THRBX": "deprecated, submode of THRB",
+        Tor => "TOR",
+        V4 => "V4",
+        Voi => "VOI",
+        Winmor => "WINMOR",
+        Wspr => "WSPR",
+    }
+}
+
+macro_rules! submodes {
+    ($($mode:ident => { $($variant:ident => $text:literal),* $(,)? }),* $(,)?) => {
+        enumeration! {
+            /// Submode of a contact, from the `SUBMODE` enumeration
+            Submode, "submode" {
+                $($($variant => $text),*),*
+            }
+        }
+
+        impl Submode {
+            /// Return the mode of which this is a submode.
+            pub fn mode(&self) -> Mode {
+                match self {
+                    $($(Submode::$variant)|* => Mode::$mode,)*
+                }
+            }
+        }
+
+        impl Mode {
+            /// Return the submodes of the mode, which may be none.
+            pub fn submodes(&self) -> &'static [Submode] {
+                match self {
+                    $(Mode::$mode => &[$(Submode::$variant),*],)*
+                    _ => &[],
+                }
+            }
+        }
+    };
+}
+
+submodes! {
+    Chip => { Chip64 => "CHIP64", Chip128 => "CHIP128" },
+    Cw => { Pcw => "PCW" },
+    DigitalVoice => {
+        C4fm => "C4FM",
+        Dmr => "DMR",
+        Dstar => "DSTAR",
+        FreeDv => "FREEDV",
+        M17 => "M17",
+    },
+    Domino => {
+        DomM => "DOM-M",
+        Dom4 => "DOM4",
+        Dom5 => "DOM5",
+        Dom8 => "DOM8",
+        Dom11 => "DOM11",
+        Dom16 => "DOM16",
+        Dom22 => "DOM22",
+        Dom44 => "DOM44",
+        Dom88 => "DOM88",
+        DominoEx => "DOMINOEX",
+        DominoF => "DOMINOF",
+    },
+    Dynamic => {
+        VaraHf => "VARA HF",
+        VaraSatellite => "VARA SATELLITE",
+        VaraFm1200 => "VARA FM 1200",
+        VaraFm9600 => "VARA FM 9600",
+    },
+    Hell => {
+        FmHell => "FMHELL",
+        FskHell => "FSKHELL",
+        Hell80 => "HELL80",
+        HellX5 => "HELLX5",
+        HellX9 => "HELLX9",
+        Hfsk => "HFSK",
+        PskHell => "PSKHELL",
+        SlowHell => "SLOWHELL",
+    },
+    Iscat => { IscatA => "ISCAT-A", IscatB => "ISCAT-B" },
+    Jt4 => {
+        Jt4a => "JT4A",
+        Jt4b => "JT4B",
+        Jt4c => "JT4C",
+        Jt4d => "JT4D",
+        Jt4e => "JT4E",
+        Jt4f => "JT4F",
+        Jt4g => "JT4G",
+    },
+    Jt9 => {
+        Jt9_1 => "JT9-1",
+        Jt9_2 => "JT9-2",
+        Jt9_5 => "JT9-5",
+        Jt9_10 => "JT9-10",
+        Jt9_30 => "JT9-30",
+        Jt9a => "JT9A",
+        Jt9b => "JT9B",
+        Jt9c => "JT9C",
+        Jt9d => "JT9D",
+        Jt9e => "JT9E",
+        Jt9eFast => "JT9E FAST",
+        Jt9f => "JT9F",
+        Jt9fFast => "JT9F FAST",
+        Jt9g => "JT9G",
+        Jt9gFast => "JT9G FAST",
+        Jt9h => "JT9H",
+        Jt9hFast => "JT9H FAST",
+    },
+    Jt65 => {
+        Jt65a => "JT65A",
+        Jt65b => "JT65B",
+        Jt65b2 => "JT65B2",
+        Jt65c => "JT65C",
+        Jt65c2 => "JT65C2",
+    },
+    Mfsk => {
+        FsqCall => "FSQCALL",
+        Fst4 => "FST4",
+        Fst4w => "FST4W",
+        Ft2 => "FT2",
+        Ft4 => "FT4",
+        Js8 => "JS8",
+        Jtms => "JTMS",
+        Mfsk4 => "MFSK4",
+        Mfsk8 => "MFSK8",
+        Mfsk11 => "MFSK11",
+        Mfsk16 => "MFSK16",
+        Mfsk22 => "MFSK22",
+        Mfsk31 => "MFSK31",
+        Mfsk32 => "MFSK32",
+        Mfsk64 => "MFSK64",
+        Mfsk64l => "MFSK64L",
+        Mfsk128 => "MFSK128",
+        Mfsk128l => "MFSK128L",
+        Q65 => "Q65",
+    },
+    Olivia => {
+        Olivia4_125 => "OLIVIA 4/125",
+        Olivia4_250 => "OLIVIA 4/250",
+        Olivia8_250 => "OLIVIA 8/250",
+        Olivia8_500 => "OLIVIA 8/500",
+        Olivia16_500 => "OLIVIA 16/500",
+        Olivia16_1000 => "OLIVIA 16/1000",
+        Olivia32_1000 => "OLIVIA 32/1000",
+    },
+    Opera => { OperaBeacon => "OPERA-BEACON", OperaQso => "OPERA-QSO" },
+    Pac => { Pac2 => "PAC2", Pac3 => "PAC3", Pac4 => "PAC4" },
+    Pax => { Pax2 => "PAX2" },
+    Psk => {
+        Psk8_125 => "8PSK125",
+        Psk8_125f => "8PSK125F",
+        Psk8_125fl => "8PSK125FL",
+        Psk8_250 => "8PSK250",
+        Psk8_250f => "8PSK250F",
+        Psk8_250fl => "8PSK250FL",
+        Psk8_500 => "8PSK500",
+        Psk8_500f => "8PSK500F",
+        Psk8_1000 => "8PSK1000",
+        Psk8_1000f => "8PSK1000F",
+        Psk8_1200f => "8PSK1200F",
+        Fsk31 => "FSK31",
+        Psk10 => "PSK10",
+        Psk31 => "PSK31",
+        Psk63 => "PSK63",
+        Psk63f => "PSK63F",
+        Psk63Rc10 => "PSK63RC10",
+        Psk63Rc20 => "PSK63RC20",
+        Psk63Rc32 => "PSK63RC32",
+        Psk63Rc4 => "PSK63RC4",
+        Psk63Rc5 => "PSK63RC5",
+        Psk125 => "PSK125",
+        Psk125Rc10 => "PSK125RC10",
+        Psk125Rc12 => "PSK125RC12",
+        Psk125Rc16 => "PSK125RC16",
+        Psk125Rc4 => "PSK125RC4",
+        Psk125Rc5 => "PSK125RC5",
+        Psk250 => "PSK250",
+        Psk250Rc2 => "PSK250RC2",
+        Psk250Rc3 => "PSK250RC3",
+        Psk250Rc5 => "PSK250RC5",
+        Psk250Rc6 => "PSK250RC6",
+        Psk250Rc7 => "PSK250RC7",
+        Psk500 => "PSK500",
+        Psk500Rc2 => "PSK500RC2",
+        Psk500Rc3 => "PSK500RC3",
+        Psk500Rc4 => "PSK500RC4",
+        Psk800Rc2 => "PSK800RC2",
+        Psk1000 => "PSK1000",
+        Psk1000Rc2 => "PSK1000RC2",
+        PskAm10 => "PSKAM10",
+        PskAm31 => "PSKAM31",
+        PskAm50 => "PSKAM50",
+        PskFec31 => "PSKFEC31",
+        Qpsk31 => "QPSK31",
+        Qpsk63 => "QPSK63",
+        Qpsk125 => "QPSK125",
+        Qpsk250 => "QPSK250",
+        Qpsk500 => "QPSK500",
+        Sim31 => "SIM31",
+    },
+    Qra64 => {
+        Qra64a => "QRA64A",
+        Qra64b => "QRA64B",
+        Qra64c => "QRA64C",
+        Qra64d => "QRA64D",
+        Qra64e => "QRA64E",
+    },
+    Ros => { RosEme => "ROS-EME", RosHf => "ROS-HF", RosMf => "ROS-MF" },
+    Rtty => { Asci => "ASCI" },
+    Ssb => { Lsb => "LSB", Usb => "USB" },
+    Thor => {
+        ThorM => "THOR-M",
+        Thor4 => "THOR4",
+        Thor5 => "THOR5",
+        Thor8 => "THOR8",
+        Thor11 => "THOR11",
+        Thor16 => "THOR16",
+        Thor22 => "THOR22",
+        Thor25x4 => "THOR25X4",
+        Thor50x1 => "THOR50X1",
+        Thor50x2 => "THOR50X2",
+        Thor100 => "THOR100",
+    },
+    Thrb => {
+        Thrbx => "THRBX",
+        Thrbx1 => "THRBX1",
+        Thrbx2 => "THRBX2",
+        Thrbx4 => "THRBX4",
+        Throb1 => "THROB1",
+        Throb2 => "THROB2",
+        Throb4 => "THROB4",
+    },
+    Tor => {
+        AmtorFec => "AMTORFEC",
+        Gtor => "GTOR",
+        Navtex => "NAVTEX",
+        SitorB => "SITORB",
+    },
+}
+
+enumeration! {
+    /// Status of a received QSL, from the `QSL_RCVD` enumeration
+    QslRcvd, "QSL received status" {
+        Yes => "Y": "confirmed",
+        No => "N": "not confirmed",
+        Requested => "R": "requested",
+        Ignore => "I": "ignore or invalid",
+        Verified => "V": "verified, deprecated",
+    }
+}
+
+enumeration! {
+    /// Status of a sent QSL, from the `QSL_SENT` enumeration
+    QslSent, "QSL sent status" {
+        Yes => "Y": "sent",
+        No => "N": "not sent",
+        Requested => "R": "requested",
+        Queued => "Q": "queued to be sent",
+        Ignore => "I": "ignore or invalid",
+    }
+}
+
+enumeration! {
+    /// Means by which a QSL is sent or received, from the `QSL_VIA`
+    /// enumeration
+    QslVia, "QSL route" {
+        Bureau => "B": "bureau",
+        Direct => "D": "direct",
+        Electronic => "E": "electronic",
+        Manager => "M": "manager, deprecated",
+    }
+}
+
+enumeration! {
+    /// Mode of propagation, from the `PROP_MODE` enumeration
+    PropMode, "propagation mode" {
+        AircraftScatter => "AS": "aircraft scatter",
+        AuroraE => "AUE": "aurora-E",
+        Aurora => "AUR": "aurora",
+        Backscatter => "BS": "backscatter",
+        EchoLink => "ECH": "EchoLink",
+        Eme => "EME": "Earth-Moon-Earth",
+        SporadicE => "ES": "sporadic E",
+        F2 => "F2": "F2 reflection",
+        Fai => "FAI": "field aligned irregularities",
+        GroundWave => "GWAVE": "ground wave",
+        Internet => "INTERNET": "internet-assisted",
+        Ionoscatter => "ION": "ionoscatter",
+        Irlp => "IRL": "IRLP",
+        LineOfSight => "LOS": "line of sight",
+        MeteorScatter => "MS": "meteor scatter",
+        Repeater => "RPT": "terrestrial or atmospheric repeater",
+        RainScatter => "RS": "rain scatter",
+        Satellite => "SAT": "satellite",
+        TransEquatorial => "TEP": "trans-equatorial",
+        Tropospheric => "TR": "tropospheric ducting",
+    }
+}
+
+enumeration! {
+    /// Signal path, from the `ANT_PATH` enumeration
+    AntPath, "antenna path" {
+        Grayline => "G": "grayline",
+        Other => "O": "other",
+        Short => "S": "short path",
+        Long => "L": "long path",
+    }
+}
+
+enumeration! {
+    /// Continent, from the `CONTINENT` enumeration
+    Continent, "continent" {
+        NorthAmerica => "NA": "North America",
+        SouthAmerica => "SA": "South America",
+        Europe => "EU": "Europe",
+        Africa => "AF": "Africa",
+        Oceania => "OC": "Oceania",
+        Asia => "AS": "Asia",
+        Antarctica => "AN": "Antarctica",
+    }
+}
+
+impl Datum {
+    /// Coerce a value to a [Band].
+    ///
+    /// Returns [None] if the value is not a band.
+    pub fn as_band(&self) -> Option<Band> {
+        FromDatum::from_datum(self)
+    }
+
+    /// Coerce a value to a [Mode].
+    ///
+    /// Returns [None] if the value is not a mode.
+    pub fn as_mode(&self) -> Option<Mode> {
+        FromDatum::from_datum(self)
+    }
+
+    /// Coerce a value to a [Submode].
+    ///
+    /// Returns [None] if the value is not a submode.
+    pub fn as_submode(&self) -> Option<Submode> {
+        FromDatum::from_datum(self)
+    }
+
+    /// Coerce a value to a [QslRcvd].
+    ///
+    /// Returns [None] if the value is not a QSL received status.
+    pub fn as_qsl_rcvd(&self) -> Option<QslRcvd> {
+        FromDatum::from_datum(self)
+    }
+
+    /// Coerce a value to a [QslSent].
+    ///
+    /// Returns [None] if the value is not a QSL sent status.
+    pub fn as_qsl_sent(&self) -> Option<QslSent> {
+        FromDatum::from_datum(self)
+    }
+
+    /// Coerce a value to a [QslVia].
+    ///
+    /// Returns [None] if the value is not a QSL route.
+    pub fn as_qsl_via(&self) -> Option<QslVia> {
+        FromDatum::from_datum(self)
+    }
+
+    /// Coerce a value to a [PropMode].
+    ///
+    /// Returns [None] if the value is not a propagation mode.
+    pub fn as_prop_mode(&self) -> Option<PropMode> {
+        FromDatum::from_datum(self)
+    }
+
+    /// Coerce a value to an [AntPath].
+    ///
+    /// Returns [None] if the value is not an antenna path.
+    pub fn as_ant_path(&self) -> Option<AntPath> {
+        FromDatum::from_datum(self)
+    }
+
+    /// Coerce a value to a [Continent].
+    ///
+    /// Returns [None] if the value is not a continent.
+    pub fn as_continent(&self) -> Option<Continent> {
+        FromDatum::from_datum(self)
+    }
+}
@@ -0,0 +1,145 @@
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+
+use super::*;
+
+fn unique<T>(all: &[T])
+where
+    T: Display,
+{
+    let names: HashSet<_> =
+        all.iter().map(|v| v.to_string().to_uppercase()).collect();
+    assert_eq!(names.len(), all.len());
+}
+
+fn round_trip<T>(all: &[T])
+where
+    T: Display + FromStr<Err = ParseEnumError> + FromDatum + Into<Datum>,
+    T: PartialEq + Copy + fmt::Debug,
+{
+    for &v in all {
+        let s = v.to_string();
+        assert_eq!(s.parse::<T>(), Ok(v));
+        assert_eq!(s.to_lowercase().parse::<T>(), Ok(v));
+        assert_eq!(s.to_uppercase().parse::<T>(), Ok(v));
+        let datum: Datum = v.into();
+        assert_eq!(datum, Datum::Enumeration(s));
+        assert_eq!(T::from_datum(&datum), Some(v));
+    }
+    unique(all);
+}
+
+#[test]
+fn all_round_trip() {
+    round_trip(Band::ALL);
+    round_trip(Mode::ALL);
+    round_trip(Submode::ALL);
+    round_trip(QslRcvd::ALL);
+    round_trip(QslSent::ALL);
+    round_trip(QslVia::ALL);
+    round_trip(PropMode::ALL);
+    round_trip(AntPath::ALL);
+    round_trip(Continent::ALL);
+}
+
+#[test]
+fn display() {
+    assert_eq!(Band::M1_25.to_string(), "1.25m");
+    assert_eq!(Band::Cm70.to_string(), "70cm");
+    assert_eq!(Band::Submm.to_string(), "submm");
+    assert_eq!(Mode::DigitalVoice.to_string(), "DIGITALVOICE");
+    assert_eq!(Submode::VaraFm1200.to_string(), "VARA FM 1200");
+    assert_eq!(Submode::Psk8_125f.to_string(), "8PSK125F");
+    assert_eq!(QslRcvd::Requested.to_string(), "R");
+    assert_eq!(PropMode::SporadicE.to_string(), "ES");
+    assert_eq!(Continent::Oceania.as_str(), "OC");
+}
+
+#[test]
+fn parse_invalid() {
+    let err = "11m".parse::<Band>().unwrap_err();
+    assert_eq!(err.to_string(), "invalid band '11m'");
+    assert!(" 20m".parse::<Band>().is_err());
+    assert!("".parse::<Mode>().is_err());
+    let err = "X".parse::<QslSent>().unwrap_err();
+    assert_eq!(err.to_string(), "invalid QSL sent status 'X'");
+    assert!("V".parse::<QslSent>().is_err());
+    assert_eq!("v".parse(), Ok(QslRcvd::Verified));
+}
+
+#[test]
+fn band_limits() {
+    assert_eq!(Band::M2190.lower(), Decimal::new(1357, 4));
+    assert_eq!(Band::M2190.upper(), Decimal::new(1378, 4));
+    assert_eq!(Band::M5.lower(), Decimal::new(54000001, 6));
+    assert_eq!(Band::Submm.upper(), Decimal::from(7_500_000));
+    for pair in Band::ALL.windows(2) {
+        assert!(pair[0] < pair[1]);
+        assert!(pair[0].lower() < pair[0].upper());
+        assert!(pair[0].upper() < pair[1].lower(), "{}", pair[0]);
+    }
+}
+
+#[test]
+fn band_from_freq() {
+    let cases = [
+        (Decimal::new(1357, 4), Some(Band::M2190)),
+        (Decimal::new(1800, 3), Some(Band::M160)),
+        (Decimal::new(14074, 3), Some(Band::M20)),
+        (Decimal::new(14350, 3), Some(Band::M20)),
+        (Decimal::new(14351, 3), None),
+        (Decimal::from(54), Some(Band::M6)),
+        (Decimal::new(5400000001, 8), None),
+        (Decimal::new(54000001, 6), Some(Band::M5)),
+        (Decimal::new(144174, 3), Some(Band::M2)),
+        (Decimal::new(2400, 0), Some(Band::Cm13)),
+        (Decimal::from(10_368), Some(Band::Cm3)),
+        (Decimal::from(1_000_000), Some(Band::Submm)),
+        (Decimal::ZERO, None),
+        (Decimal::from(-14), None),
+    ];
+    for (freq, band) in cases {
+        assert_eq!(Band::from_freq(freq), band, "{freq}");
+    }
+}
+
+#[test]
+fn submodes() {
+    for &sub in Submode::ALL {
+        assert!(sub.mode().submodes().contains(&sub), "{sub}");
+    }
+    for &mode in Mode::ALL {
+        assert!(mode.submodes().iter().all(|s| s.mode() == mode));
+    }
+    assert_eq!(Mode::Ssb.submodes(), [Submode::Lsb, Submode::Usb]);
+    assert!(Mode::Ft8.submodes().is_empty());
+    assert_eq!(Submode::Ft4.mode(), Mode::Mfsk);
+    assert_eq!(Submode::Dstar.mode(), Mode::DigitalVoice);
+}
+
+#[test]
+fn deprecated_modes() {
+    assert_eq!("psk31".parse(), Ok(Mode::Psk31));
+    assert_eq!(Mode::Dstar.to_string(), "DSTAR");
+    assert!(Mode::Dstar.submodes().is_empty());
+    assert_eq!(Submode::Dstar.mode(), Mode::DigitalVoice);
+    assert_eq!(Datum::String("jt65a".into()).as_mode(), Some(Mode::Jt65a));
+}
+
+#[test]
+fn datum_accessors() {
+    let s = |s: &str| Datum::String(s.into());
+    assert_eq!(s("20M").as_band(), Some(Band::M20));
+    assert_eq!(s("ft8").as_mode(), Some(Mode::Ft8));
+    assert_eq!(s("usb").as_submode(), Some(Submode::Usb));
+    assert_eq!(s("y").as_qsl_rcvd(), Some(QslRcvd::Yes));
+    assert_eq!(s("Q").as_qsl_sent(), Some(QslSent::Queued));
+    assert_eq!(s("b").as_qsl_via(), Some(QslVia::Bureau));
+    assert_eq!(s("eme").as_prop_mode(), Some(PropMode::Eme));
+    assert_eq!(s("L").as_ant_path(), Some(AntPath::Long));
+    assert_eq!(s("eu").as_continent(), Some(Continent::Europe));
+    assert_eq!(Datum::Enumeration("40m".into()).as_band(), Some(Band::M40));
+    assert_eq!(s("FT8").as_band(), None);
+    assert_eq!(Datum::Boolean(true).as_qsl_rcvd(), Some(QslRcvd::Yes));
+    assert_eq!(Datum::Number(Decimal::from(20)).as_band(), None);
+}
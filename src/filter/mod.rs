@@ -1,8 +1,13 @@
 //! Optional ADIF data transformations
 
-use crate::{Datum, Error, Record};
-use chrono::{Days, NaiveDateTime};
+use crate::convert::optional;
+use crate::{
+    Band, Datum, Duplicates, Error, Mode, Record, Submode, UserDef,
+    split_app_name,
+};
+use chrono::{Days, NaiveDate, NaiveDateTime, NaiveTime};
 use futures::stream::Stream;
+use rust_decimal::Decimal;
 use std::collections::HashSet;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -146,6 +151,9 @@ impl<S> MapExt for S where S: Stream {}
 ///
 /// Create `:time_on` and `:time_off` fields from separate date/time
 /// components.  Handle date crossing when time_off is earlier than time_on.
+/// Times may be either HHMMSS or HHMM.  A date or time field that is
+/// present and not empty but cannot be parsed produces an
+/// [Error::InvalidValue].
 ///
 /// ```
 /// use chrono::{NaiveDate, NaiveTime, Timelike};
@@ -154,7 +162,7 @@ impl<S> MapExt for S where S: Stream {}
 /// use futures::StreamExt;
 ///
 /// # tokio_test::block_on(async {
-/// let data = b"<qso_date:8>20240101<time_on:6>230000<eor>";
+/// let data = b"<qso_date:8>20240101<time_on:4>2300<eor>";
 /// let stream = TagDecoder::new_stream(&data[..], true).records();
 /// let mut stream = normalize_times(stream);
 /// let record = stream.next().await.unwrap().unwrap();
@@ -176,10 +184,10 @@ where
     const TIME_OFF: &str = ":time_off";
 
     stream.normalize(|record| {
-        let date = record.get("qso_date").and_then(|d| d.as_date());
-        let date_off = record.get("qso_date_off").and_then(|d| d.as_date());
-        let time_on = record.get("time_on").and_then(|t| t.as_time());
-        let time_off = record.get("time_off").and_then(|t| t.as_time());
+        let date = optional::<NaiveDate>(record, "qso_date", None)?;
+        let date_off = optional::<NaiveDate>(record, "qso_date_off", None)?;
+        let time_on = optional::<NaiveTime>(record, "time_on", None)?;
+        let time_off = optional::<NaiveTime>(record, "time_off", None)?;
         let (Some(date), Some(time_on)) = (date, time_on) else {
             return Ok(());
         };
@@ -212,14 +220,14 @@ pub fn normalize_mode<S>(
 where
     S: Stream<Item = Result<Record, Error>>,
 {
-    const MFSK_SUBMODES: &[&str] = &["FT2", "FT4", "Q65"];
+    const PROMOTED: &[Submode] = &[Submode::Ft2, Submode::Ft4, Submode::Q65];
     const MODE: &str = ":mode";
 
     stream.normalize(|record| {
         let mode = record
             .get("mode")
-            .or_else(|| record.get("app_lotw_mode"))
-            .or_else(|| record.get("app_lotw_modegroup"))
+            .or_else(|| record.get_app("lotw", "mode"))
+            .or_else(|| record.get_app("lotw", "modegroup"))
             .map(|m| m.as_str());
 
         let Some(mode) = mode else { return Ok(()) };
@@ -227,10 +235,8 @@ where
 
         let mode = match sub {
             Some(sub)
-                if mode.eq_ignore_ascii_case("MFSK")
-                    && MFSK_SUBMODES
-                        .iter()
-                        .any(|m| m.eq_ignore_ascii_case(&sub)) =>
+                if mode.parse() == Ok(Mode::Mfsk)
+                    && sub.parse().is_ok_and(|s| PROMOTED.contains(&s)) =>
             {
                 sub
             }
@@ -275,6 +281,106 @@ where
     })
 }
 
+/// Frequency fields and the band fields derived from them
+const BANDS: &[(&str, &str)] = &[("freq", "band"), ("freq_rx", "band_rx")];
+
+/// Return a field unless it is absent or empty.
+fn present<'a>(record: &'a Record, field: &str) -> Option<&'a Datum> {
+    record.get(field).filter(|v| !v.as_str().trim().is_empty())
+}
+
+/// Fill in `band` and `band_rx` from `freq` and `freq_rx`.
+///
+/// A band field is only filled in if it is absent or empty, and only if
+/// the frequency lies within one of the bands of the ADIF band table;
+/// otherwise the record is left alone.  A frequency that is present and
+/// not empty but is not a number produces an [Error::InvalidValue].
+///
+/// ```
+/// use difa::{RecordStreamExt, TagDecoder, filter::derive_band};
+/// use futures::StreamExt;
+///
+/// # tokio_test::block_on(async {
+/// let data = b"<freq:6>14.074<freq_rx:6>21.074<eor>";
+/// let stream = TagDecoder::new_stream(&data[..], true).records();
+/// let mut stream = derive_band(stream);
+/// let record = stream.next().await.unwrap().unwrap();
+/// assert_eq!(record.get("band").unwrap().as_str(), "20m");
+/// assert_eq!(record.get("band_rx").unwrap().as_str(), "15m");
+/// # });
+/// ```
+pub fn derive_band<S>(
+    stream: S,
+) -> Normalize<S, impl FnMut(&mut Record) -> Result<(), Error> + Unpin>
+where
+    S: Stream<Item = Result<Record, Error>>,
+{
+    stream.normalize(|record| {
+        for &(freq, band) in BANDS {
+            if present(record, band).is_some() {
+                continue;
+            }
+            let Some(freq) = optional::<Decimal>(record, freq, None)? else {
+                continue;
+            };
+            let Some(b) = Band::from_freq(freq) else {
+                continue;
+            };
+            // replaces an empty band
+            record.insert_with(band, b, Duplicates::Last)?;
+        }
+        Ok(())
+    })
+}
+
+/// Check that `band` and `band_rx` agree with `freq` and `freq_rx`.
+///
+/// A record whose band is not a band of the ADIF band table containing
+/// its frequency produces an [Error::InvalidValue] for the band.  Fields
+/// that are absent or empty are not checked, and a frequency that is not
+/// a number produces an [Error::InvalidValue] for the frequency.
+///
+/// ```
+/// use difa::{Error, RecordStreamExt, TagDecoder, filter::check_band};
+/// use futures::StreamExt;
+///
+/// # tokio_test::block_on(async {
+/// let data = b"<freq:6>14.074<band:3>20M<eor><freq:5>7.074<band:3>20m<eor>";
+/// let stream = TagDecoder::new_stream(&data[..], true).records();
+/// let mut stream = check_band(stream);
+/// assert!(stream.next().await.unwrap().is_ok());
+/// let err = stream.next().await.unwrap().unwrap_err();
+/// assert!(matches!(err, Error::InvalidValue { field, .. } if field == "band"));
+/// # });
+/// ```
+pub fn check_band<S>(
+    stream: S,
+) -> Normalize<S, impl FnMut(&mut Record) -> Result<(), Error> + Unpin>
+where
+    S: Stream<Item = Result<Record, Error>>,
+{
+    stream.normalize(|record| {
+        for &(freq, band) in BANDS {
+            let Some(value) = present(record, band) else {
+                continue;
+            };
+            let Some(freq) = optional::<Decimal>(record, freq, None)? else {
+                continue;
+            };
+            if value.as_band().is_some_and(|b| b.contains(freq)) {
+                continue;
+            }
+            return Err(Error::InvalidValue {
+                field: band.to_string(),
+                value: value.as_str().into_owned(),
+                record: Box::new(record.clone()),
+                span: record.field_span(band),
+            });
+        }
+        Ok(())
+    })
+}
+
 /// Exclude records matching specified callsigns.
 ///
 /// Case-insensitive comparison.  Records without a `call` field pass through.
@@ -295,6 +401,65 @@ where
     })
 }
 
+/// Remove application-defined fields belonging to specified programs.
+///
+/// Program IDs are compared case-insensitively.  Other fields are kept.
+///
+/// ```
+/// use difa::{RecordStreamExt, TagDecoder, filter::exclude_app_fields};
+/// use futures::StreamExt;
+///
+/// # tokio_test::block_on(async {
+/// let data = b"<call:4>W1AW<app_lotw_mode:3>FT8<app_n1mm_id:1>7<eor>";
+/// let stream = TagDecoder::new_stream(&data[..], true).records();
+/// let mut stream = exclude_app_fields(stream, &["LOTW"]);
+/// let record = stream.next().await.unwrap().unwrap();
+/// let names: Vec<_> = record.fields().map(|(k, _)| k).collect();
+/// assert_eq!(names, ["call", "app_n1mm_id"]);
+/// # });
+/// ```
+pub fn exclude_app_fields<S>(
+    stream: S, programs: &[&str],
+) -> Normalize<S, impl FnMut(&mut Record) -> Result<(), Error> + Unpin>
+where
+    S: Stream<Item = Result<Record, Error>>,
+{
+    let programs = app_programs(programs);
+    stream.normalize(move |record| {
+        record.retain(|name, _| !has_app_program(&programs, name));
+        Ok(())
+    })
+}
+
+/// Remove application-defined fields not belonging to specified programs.
+///
+/// Program IDs are compared case-insensitively.  Fields that are not
+/// application-defined are kept.
+pub fn retain_app_fields<S>(
+    stream: S, programs: &[&str],
+) -> Normalize<S, impl FnMut(&mut Record) -> Result<(), Error> + Unpin>
+where
+    S: Stream<Item = Result<Record, Error>>,
+{
+    let programs = app_programs(programs);
+    stream.normalize(move |record| {
+        record.retain(|name, _| {
+            split_app_name(name).is_none() || has_app_program(&programs, name)
+        });
+        Ok(())
+    })
+}
+
+fn app_programs(programs: &[&str]) -> Vec<String> {
+    programs.iter().map(|p| p.to_string()).collect()
+}
+
+fn has_app_program(programs: &[String], name: &str) -> bool {
+    split_app_name(name).is_some_and(|(p, _)| {
+        programs.iter().any(|q| q.eq_ignore_ascii_case(p))
+    })
+}
+
 /// Exclude header records from the stream.
 ///
 /// ```
@@ -350,3 +515,73 @@ where
         }))
     })
 }
+
+/// Apply the header's `USERDEFn` declarations to record fields.
+///
+/// Values of declared fields are parsed as the declared type.  A value
+/// that is invalid for that type, or that is not among the declared
+/// enumeration or within the declared range, produces an
+/// [Error::InvalidValue].
+///
+/// ```
+/// use difa::{Datum, Error, RecordStreamExt, TagDecoder};
+/// use difa::filter::apply_userdefs;
+/// use futures::StreamExt;
+///
+/// # tokio_test::block_on(async {
+/// let data = b"<userdef1:15:n>ShoeSize,{5:20}<eoh>\
+///              <shoesize:2>11<eor><shoesize:2>40<eor>";
+/// let stream = TagDecoder::new_stream(&data[..], true).records();
+/// let mut stream = apply_userdefs(stream);
+/// let header = stream.next().await.unwrap().unwrap();
+/// let def = header.get("userdef1").unwrap();
+/// assert!(matches!(def, Datum::UserDef(_)));
+/// let record = stream.next().await.unwrap().unwrap();
+/// assert_eq!(record.get("shoesize").unwrap(), &Datum::Number(11.into()));
+/// let err = stream.next().await.unwrap().unwrap_err();
+/// assert!(matches!(err, Error::InvalidValue { .. }));
+/// # });
+/// ```
+pub fn apply_userdefs<S>(
+    stream: S,
+) -> Map<S, impl FnMut(Record) -> Result<Record, Error>>
+where
+    S: Stream<Item = Result<Record, Error>>,
+{
+    let mut defs: Vec<UserDef> = Vec::new();
+    stream.map(move |r| {
+        if r.is_header() {
+            defs = r
+                .fields()
+                .filter_map(|(_, v)| match v {
+                    Datum::UserDef(u) => Some(u.as_ref().clone()),
+                    _ => None,
+                })
+                .collect();
+            return Ok(r);
+        }
+
+        let mut typed = Vec::new();
+        for def in &defs {
+            let Some(value) = r.get(def.name()) else {
+                continue;
+            };
+            let Some(value) = def.apply(value.clone()) else {
+                let value = value.as_str().into_owned();
+                return Err(Error::InvalidValue {
+                    field: def.name().to_string(),
+                    value,
+                    span: r.field_span(def.name()),
+                    record: Box::new(r),
+                });
+            };
+            typed.push((def.name(), value));
+        }
+        Ok(r.map_fields(|name, v| {
+            match typed.iter().position(|(n, _)| n.eq_ignore_ascii_case(name)) {
+                Some(i) => typed.swap_remove(i).1,
+                None => v,
+            }
+        }))
+    })
+}
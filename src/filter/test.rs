@@ -1,5 +1,6 @@
 use chrono::{NaiveDate, NaiveDateTime};
 use futures::StreamExt;
+use rust_decimal::Decimal;
 
 use super::*;
 use crate::parse::{RecordStream, TagStream};
@@ -53,7 +54,8 @@ fn assert_both_times(
 fn duplicate_key_error(key: &str, record: Record) -> Error {
     Error::DuplicateKey {
         key: key.to_string(),
-        record,
+        record: Box::new(record),
+        span: None,
     }
 }
 
@@ -175,7 +177,8 @@ async fn normalize_mode_duplicate_key() {
         err,
         Error::DuplicateKey {
             key: ":mode".to_string(),
-            record: expected_record,
+            record: Box::new(expected_record),
+            span: None,
         }
     );
 }
@@ -213,7 +216,8 @@ async fn normalize_band_duplicate_key() {
         err,
         Error::DuplicateKey {
             key: ":band".to_string(),
-            record: expected_record,
+            record: Box::new(expected_record),
+            span: None,
         }
     );
 }
@@ -526,3 +530,272 @@ async fn trim_whitespace_error_passthrough() {
     next_err(&mut s, partial_data(1, 20, 19)).await;
     no_record(&mut s).await;
 }
+
+const USERDEFS: &str = "<userdef1:13:e>Size,{S, M,L}<userdef2:11:n>Shoe,{5:20}\
+                        <userdef3:5:d>Birth<userdef4:4>Note<eoh>";
+
+async fn userdef_record(fields: &str) -> Result<Record, Error> {
+    let adif = format!("{USERDEFS}{fields}<eor>");
+    let stream = RecordStream::new(adif.as_bytes(), false);
+    let mut s = apply_userdefs(stream);
+    assert!(next(&mut s).await.is_header());
+    s.next().await.unwrap()
+}
+
+#[tokio::test]
+async fn apply_userdefs_types_fields() {
+    let r = userdef_record(
+        "<size:1>m<shoe:4>10.5<birth:8>19700101<note:2>hi<call:4>W1AW",
+    )
+    .await
+    .unwrap();
+    let fields: Vec<_> = r.fields().collect();
+    let birth = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    let expected = [
+        ("size", &Datum::Enumeration("m".into())),
+        ("shoe", &Datum::Number(Decimal::new(105, 1))),
+        ("birth", &Datum::Date(birth)),
+        ("note", &Datum::String("hi".into())),
+        ("call", &Datum::String("W1AW".into())),
+    ];
+    assert_eq!(fields, expected);
+}
+
+#[tokio::test]
+async fn apply_userdefs_typed_fields() {
+    let r = userdef_record("<shoe:2:n>20").await.unwrap();
+    assert_eq!(r.get("shoe"), Some(&Datum::Number(20.into())));
+}
+
+#[tokio::test]
+async fn apply_userdefs_invalid() {
+    let cases = [
+        ("<size:2>XL", "Size", "XL"),
+        ("<shoe:1>4", "Shoe", "4"),
+        ("<shoe:2:n>21", "Shoe", "21"),
+        ("<shoe:1:b>Y", "Shoe", "Y"),
+        ("<shoe:3>big", "Shoe", "big"),
+        ("<birth:5>today", "Birth", "today"),
+    ];
+    for (fields, field, value) in cases {
+        let err = userdef_record(fields).await.unwrap_err();
+        let Error::InvalidValue {
+            field: f, value: v, ..
+        } = err
+        else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!((f.as_str(), v.as_str()), (field, value));
+    }
+}
+
+#[tokio::test]
+async fn apply_userdefs_without_header() {
+    let stream = RecordStream::new("<size:2>XL<eor>".as_bytes(), false);
+    let mut s = apply_userdefs(stream);
+    assert_eq!(next(&mut s).await.get("size").unwrap().as_str(), "XL");
+    no_record(&mut s).await;
+}
+
+const APPS: &str = "<call:4>W1AW<app_lotw_mode:3>FT8<APP_N1MM_ID:1>7\
+                    <app_x:1>?<eor>";
+
+async fn app_names<F, S>(f: F) -> Vec<String>
+where
+    F: FnOnce(RecordStream<TagStream<&'static [u8]>>) -> S,
+    S: Stream<Item = Result<Record, Error>> + Unpin,
+{
+    let mut s = f(RecordStream::new(APPS.as_bytes(), false));
+    let names = next(&mut s).await.fields().map(|(k, _)| k.into()).collect();
+    no_record(&mut s).await;
+    names
+}
+
+#[tokio::test]
+async fn exclude_app_fields_removes_programs() {
+    let names = app_names(|s| exclude_app_fields(s, &["lotw", "n1mm"])).await;
+    assert_eq!(names, ["call", "app_x"]);
+    let names = app_names(|s| exclude_app_fields(s, &[])).await;
+    assert_eq!(names.len(), 4);
+}
+
+#[tokio::test]
+async fn retain_app_fields_keeps_programs() {
+    let names = app_names(|s| retain_app_fields(s, &["N1MM"])).await;
+    assert_eq!(names, ["call", "APP_N1MM_ID", "app_x"]);
+    let names = app_names(|s| retain_app_fields(s, &[])).await;
+    assert_eq!(names, ["call", "app_x"]);
+}
+
+#[tokio::test]
+async fn normalize_times_hhmm() {
+    let rec = parse_one(
+        "<qso_date:8>20240101<time_on:4>2330<time_off:4>0015<eor>",
+        normalize_times,
+    )
+    .await;
+    assert_both_times(
+        &rec,
+        dt(2024, 1, 1, 23, 30, 0),
+        dt(2024, 1, 2, 0, 15, 0),
+    );
+}
+
+#[tokio::test]
+async fn normalize_times_empty() {
+    let rec = parse_one(
+        "<qso_date:8>20240101<time_on:4>2330<time_off:0><eor>",
+        normalize_times,
+    )
+    .await;
+    assert_time_on_only(&rec, dt(2024, 1, 1, 23, 30, 0));
+    let rec =
+        parse_one("<qso_date:1> <time_on:4>2330<eor>", normalize_times).await;
+    assert!(rec.get(":time_on").is_none());
+}
+
+#[tokio::test]
+async fn normalize_times_malformed() {
+    let cases = [
+        (
+            "<qso_date:7>2024011<time_on:4>1200<eor>",
+            "qso_date",
+            "2024011",
+        ),
+        (
+            "<qso_date:8>20240101<time_on:5>12:00<eor>",
+            "time_on",
+            "12:00",
+        ),
+        (
+            "<qso_date:8>20240101<time_on:4>1200<time_off:4>2400<eor>",
+            "time_off",
+            "2400",
+        ),
+        (
+            "<qso_date:8>20240101<time_on:4>1200<qso_date_off:3>tbd<eor>",
+            "qso_date_off",
+            "tbd",
+        ),
+    ];
+    for (adif, field, value) in cases {
+        let mut s = parse_many(adif, normalize_times);
+        let record = RecordStream::new(adif.as_bytes(), true)
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+        let err = Error::InvalidValue {
+            field: field.into(),
+            value: value.into(),
+            span: record.field_span(field),
+            record: Box::new(record),
+        };
+        next_err(&mut s, err).await;
+    }
+}
+
+#[tokio::test]
+async fn normalize_times_sample() {
+    let data = std::fs::read("examples/sample.adif").unwrap();
+    let stream = RecordStream::new(&data[..], false);
+    let records: Vec<_> =
+        normalize_times(exclude_header(stream)).collect().await;
+    assert!(!records.is_empty());
+    for r in records {
+        assert!(r.unwrap().get(":time_on").is_some());
+    }
+}
+
+#[tokio::test]
+async fn derive_band_from_freq() {
+    let cases = [
+        ("<freq:6>14.074", Some("20m"), None),
+        ("<freq:5>7.074<freq_rx:7>144.174", Some("40m"), Some("2m")),
+        ("<freq_rx:8>0.475000", None, Some("630m")),
+        ("<freq:6>14.074<band:3>40M", Some("40M"), None),
+        ("<freq:6>14.074<band:0>", Some("20m"), None),
+        ("<freq:6>27.185", None, None),
+        ("<freq:0><freq_rx:1> ", None, None),
+        ("<call:4>W1AW", None, None),
+    ];
+    for (fields, band, band_rx) in cases {
+        let adif = format!("{fields}<eor>");
+        let rec = parse_one(&adif, derive_band).await;
+        let get = |f| rec.get(f).map(|b| b.as_str().into_owned());
+        assert_eq!(get("band").as_deref(), band, "{adif}");
+        assert_eq!(get("band_rx").as_deref(), band_rx, "{adif}");
+    }
+    let rec = parse_one("<freq:6>14.074<eor>", derive_band).await;
+    assert_eq!(rec.get("band"), Some(&Datum::Enumeration("20m".into())));
+}
+
+#[tokio::test]
+async fn derive_band_invalid_freq() {
+    let adif = "<call:4>W1AW<freq:4>fast<eor>";
+    let mut s = parse_many(adif, derive_band);
+    let record = RecordStream::new(adif.as_bytes(), true)
+        .next()
+        .await
+        .unwrap()
+        .unwrap();
+    let err = Error::InvalidValue {
+        field: "freq".into(),
+        value: "fast".into(),
+        span: Some(span((1, 13, 12), (1, 25, 24))),
+        record: Box::new(record),
+    };
+    next_err(&mut s, err).await;
+}
+
+#[tokio::test]
+async fn check_band_agrees() {
+    let cases = [
+        "<freq:6>14.074<band:3>20M",
+        "<freq:6>14.074<band:3>20m<freq_rx:7>432.100<band_rx:4>70cm",
+        "<freq:6>14.074",
+        "<band:3>20m",
+        "<freq:6>14.074<band:0>",
+        "<freq:0><band:3>40m",
+    ];
+    for fields in cases {
+        let adif = format!("{fields}<eor>");
+        parse_one(&adif, check_band).await;
+    }
+}
+
+#[tokio::test]
+async fn check_band_disagrees() {
+    let cases = [
+        ("<freq:6>14.074<band:3>40m", "band", "40m", (1, 15, 14)),
+        ("<freq:6>27.185<band:3>11m", "band", "11m", (1, 15, 14)),
+        ("<freq:6>27.185<band:3>10m", "band", "10m", (1, 15, 14)),
+        (
+            "<freq_rx:5>7.074<band_rx:3>20m",
+            "band_rx",
+            "20m",
+            (1, 17, 16),
+        ),
+        ("<freq:4>fast<band:3>20m", "freq", "fast", (1, 1, 0)),
+    ];
+    for (fields, field, value, (line, column, byte)) in cases {
+        let adif = format!("{fields}<eor>");
+        let mut s = parse_many(&adif, check_band);
+        let record = RecordStream::new(adif.as_bytes(), true)
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+        let span = record.field_span(field);
+        let start = crate::Position { line, column, byte };
+        assert_eq!(span.map(|s| s.start), Some(start), "{adif}");
+        let err = Error::InvalidValue {
+            field: field.into(),
+            value: value.into(),
+            span,
+            record: Box::new(record),
+        };
+        next_err(&mut s, err).await;
+        no_record(&mut s).await;
+    }
+}
@@ -0,0 +1,240 @@
+//! Following a log file as a logger appends to it
+//!
+//! Loggers such as WSJT-X append each contact to their ADIF file as it is
+//! made.  A [Follower] reads the records already in the file and then,
+//! instead of ending at the end of the file, polls for more data and
+//! returns each new record as it is completed.  A record that is only
+//! partially written is held until the rest arrives.
+//!
+//! If the file is truncated or replaced, as when a log is rotated, the new
+//! file is read from the beginning.  Truncation is noticed when the file
+//! becomes shorter than the data already read from it.
+//!
+//! This module requires the `follow` feature.
+
+use crate::blocking::CHUNK;
+use crate::parse::{Aggregator, LenientDecoder};
+use crate::{Duplicates, Error, Record, Recovery, TagDecoder};
+use bytes::BytesMut;
+use futures::stream::{self, Stream};
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::fs::{self, File};
+use tokio::io::AsyncReadExt;
+use tokio::time;
+
+#[cfg(test)]
+mod test;
+
+/// Builder for a stream that follows a growing ADIF file
+///
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use difa::follow::Follower;
+/// use futures::StreamExt;
+/// use std::time::Duration;
+/// let mut s = Follower::new("wsjtx_log.adi")
+///     .with_interval(Duration::from_secs(5))
+///     .records();
+/// while let Some(record) = s.next().await {
+///     if let Some(call) = record.unwrap().get("call") {
+///         println!("worked {}", call.as_str());
+///     }
+/// }
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Follower {
+    path: PathBuf,
+    interval: Duration,
+    decoder: TagDecoder,
+    recovery: Recovery,
+    duplicates: Duplicates,
+}
+
+impl Follower {
+    /// Follow the file at the given path.
+    ///
+    /// The file need not exist yet.  It is checked for new data every
+    /// second, and invalid tags are reported and then skipped, dropping
+    /// the records containing them.
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            path: path.into(),
+            interval: Duration::from_secs(1),
+            decoder: TagDecoder::new(true),
+            recovery: Recovery::Drop,
+            duplicates: Duplicates::Error,
+        }
+    }
+
+    /// Check for new data at the given interval.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Decode the file with the given decoder, e.g. to set its encoding.
+    pub fn with_decoder(mut self, decoder: TagDecoder) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Handle records affected by errors as specified.
+    pub fn with_recovery(mut self, recovery: Recovery) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    /// Handle fields that occur more than once in a record as specified.
+    pub fn with_duplicates(mut self, duplicates: Duplicates) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    /// Return a stream of the records in the file, which never ends.
+    pub fn records(self) -> FollowStream {
+        let follow = Follow {
+            path: self.path,
+            interval: self.interval,
+            decoder: LenientDecoder(self.decoder),
+            recovery: self.recovery,
+            duplicates: self.duplicates,
+            aggregator: Aggregator::default(),
+            buf: BytesMut::new(),
+            file: None,
+            id: None,
+            offset: 0,
+            failed: false,
+        };
+        let inner = stream::unfold(follow, |mut f| async move {
+            let record = f.next().await;
+            Some((record, f))
+        });
+        FollowStream {
+            inner: Box::pin(inner),
+        }
+    }
+}
+
+/// Stream of records from a file that is being appended to
+///
+/// See [Follower].
+pub struct FollowStream {
+    inner: Pin<Box<dyn Stream<Item = Result<Record, Error>> + Send>>,
+}
+
+impl Stream for FollowStream {
+    type Item = Result<Record, Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+struct Follow {
+    path: PathBuf,
+    interval: Duration,
+    decoder: LenientDecoder,
+    recovery: Recovery,
+    duplicates: Duplicates,
+    aggregator: Aggregator,
+    buf: BytesMut,
+    file: Option<File>,
+    id: Option<(u64, u64)>,
+    offset: u64,
+    failed: bool,
+}
+
+impl Follow {
+    async fn next(&mut self) -> Result<Record, Error> {
+        loop {
+            while let Some(tag) = self.decoder.decode(&mut self.buf, false)? {
+                if let Some(r) = self.aggregator.push(tag) {
+                    return r;
+                }
+            }
+            if std::mem::take(&mut self.failed) {
+                time::sleep(self.interval).await;
+            }
+            if self.read().await? == 0 {
+                time::sleep(self.interval).await;
+                self.check().await;
+            }
+        }
+    }
+
+    async fn read(&mut self) -> Result<usize, Error> {
+        if self.file.is_none() {
+            self.open().await?;
+        }
+        let Some(file) = &mut self.file else {
+            return Ok(0);
+        };
+        self.buf.reserve(CHUNK);
+        match file.read_buf(&mut self.buf).await {
+            Ok(n) => {
+                self.offset += n as u64;
+                Ok(n)
+            }
+            Err(e) => {
+                self.failed = true;
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn open(&mut self) -> Result<(), Error> {
+        let file = match File::open(&self.path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                self.failed = true;
+                return Err(e.into());
+            }
+        };
+        self.id = file_id(&file.metadata().await?);
+        self.file = Some(file);
+        self.offset = 0;
+        self.buf.clear();
+        self.decoder.0.restart();
+        self.aggregator = Aggregator::default();
+        self.aggregator.set_recovery(self.recovery);
+        self.aggregator.set_duplicates(self.duplicates.clone());
+        Ok(())
+    }
+
+    /// Close the file if it has been truncated or replaced.
+    async fn check(&mut self) {
+        if self.file.is_none() {
+            return;
+        }
+        // if missing, it was moved away but perhaps not yet replaced
+        let Ok(meta) = fs::metadata(&self.path).await else {
+            return;
+        };
+        if file_id(&meta) != self.id || meta.len() < self.offset {
+            self.file = None;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
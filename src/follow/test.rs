@@ -0,0 +1,170 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use futures::StreamExt;
+use tokio::time::timeout;
+
+use super::*;
+use crate::Encoding;
+use crate::test::helpers::*;
+
+const INTERVAL: Duration = Duration::from_millis(5);
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("difa-follow-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn append(path: &Path, data: &str) {
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    f.write_all(data.as_bytes()).unwrap();
+}
+
+fn follow(path: &Path) -> FollowStream {
+    Follower::new(path).with_interval(INTERVAL).records()
+}
+
+async fn next(s: &mut FollowStream) -> Result<Record, Error> {
+    let next = timeout(Duration::from_secs(5), s.next());
+    next.await.unwrap().unwrap()
+}
+
+async fn next_call(s: &mut FollowStream) -> String {
+    let record = next(s).await.unwrap();
+    record.get("call").unwrap().as_str().into_owned()
+}
+
+async fn idle(s: &mut FollowStream) {
+    let next = timeout(INTERVAL * 10, s.next());
+    assert!(next.await.is_err());
+}
+
+#[tokio::test]
+async fn appends() {
+    let dir = temp_dir("appends");
+    let path = dir.join("log.adi");
+    let mut s = follow(&path);
+    idle(&mut s).await;
+
+    append(&path, "WSJT-X ADIF Export\n<adif_ver:5>3.1.0\n<eoh>\n");
+    let header = next(&mut s).await.unwrap();
+    assert!(header.is_header());
+    assert_eq!(header.preamble(), Some("WSJT-X ADIF Export"));
+    idle(&mut s).await;
+
+    append(&path, "<call:4>W1");
+    idle(&mut s).await;
+    append(&path, "AW <mode:3>FT8 <eo");
+    idle(&mut s).await;
+    append(&path, "r>\n<call:4>N0AA <eor>\n");
+    let record = next(&mut s).await.unwrap();
+    assert_eq!(record.get("call").unwrap().as_str(), "W1AW");
+    assert_eq!(record.span(), Some(span((4, 1, 43), (4, 25, 67))));
+    assert_eq!(next_call(&mut s).await, "N0AA");
+    idle(&mut s).await;
+}
+
+#[tokio::test]
+async fn truncation() {
+    let dir = temp_dir("truncation");
+    let path = dir.join("log.adi");
+    append(&path, "<call:4>W1AW<eor>\n<call:4>N0AA<eor>\n<call:2>K1");
+    let mut s = follow(&path);
+    assert_eq!(next_call(&mut s).await, "W1AW");
+    assert_eq!(next_call(&mut s).await, "N0AA");
+    idle(&mut s).await;
+
+    std::fs::write(&path, "<call:5>AB9BH<eor>\n").unwrap();
+    let record = next(&mut s).await.unwrap();
+    assert_eq!(record.get("call").unwrap().as_str(), "AB9BH");
+    assert_eq!(record.span(), Some(span((1, 1, 0), (1, 14, 13))));
+    idle(&mut s).await;
+}
+
+#[tokio::test]
+async fn rotation() {
+    let dir = temp_dir("rotation");
+    let path = dir.join("log.adi");
+    append(&path, "<call:4>W1AW<eor>\n");
+    let mut s = follow(&path);
+    assert_eq!(next_call(&mut s).await, "W1AW");
+
+    std::fs::rename(&path, dir.join("log.adi.1")).unwrap();
+    idle(&mut s).await;
+    append(&path, "<call:4>N0AA<eor>\n<call:5>AB9BH<eor>\n");
+    assert_eq!(next_call(&mut s).await, "N0AA");
+    assert_eq!(next_call(&mut s).await, "AB9BH");
+    idle(&mut s).await;
+}
+
+#[tokio::test]
+async fn invalid_tags() {
+    let dir = temp_dir("invalid_tags");
+    let path = dir.join("log.adi");
+    append(&path, "<call:4>W1AW<freq:x>14<eor><call:4>N0AA<eor>");
+    let mut s = follow(&path);
+    let err = next(&mut s).await.unwrap_err();
+    assert_eq!(err, invalid_format(bad_length("freq", "x"), 1, 13, 12));
+    assert_eq!(next_call(&mut s).await, "N0AA");
+
+    let mut s = Follower::new(&path)
+        .with_interval(INTERVAL)
+        .with_recovery(Recovery::Flag)
+        .records();
+    assert!(next(&mut s).await.is_err());
+    let record = next(&mut s).await.unwrap();
+    assert!(record.is_damaged());
+    assert_eq!(record.get("call").unwrap().as_str(), "W1AW");
+}
+
+#[tokio::test]
+async fn duplicates() {
+    let dir = temp_dir("duplicates");
+    let path = dir.join("log.adi");
+    append(&path, "<call:4>W1AW<call:4>N0AA<eor>");
+    let mut s = follow(&path);
+    let err = next(&mut s).await.unwrap_err();
+    assert!(matches!(err, Error::DuplicateKey { .. }));
+
+    let mut s = Follower::new(&path)
+        .with_interval(INTERVAL)
+        .with_duplicates(Duplicates::Last)
+        .records();
+    assert_eq!(next_call(&mut s).await, "N0AA");
+}
+
+#[tokio::test]
+async fn decoder() {
+    let dir = temp_dir("decoder");
+    let path = dir.join("log.adi");
+    std::fs::write(&path, b"<name:3>J\xf6n<eor>").unwrap();
+    let decoder = TagDecoder::new(true).with_encoding(Encoding::Latin1);
+    let mut s = Follower::new(&path)
+        .with_interval(INTERVAL)
+        .with_decoder(decoder)
+        .records();
+    let record = next(&mut s).await.unwrap();
+    assert_eq!(record.get("name").unwrap().as_str(), "J\u{f6}n");
+}
+
+#[tokio::test]
+async fn io_errors() {
+    let dir = temp_dir("io_errors");
+    let mut s = follow(&dir);
+    assert!(matches!(next(&mut s).await, Err(Error::Io(_))));
+    assert!(matches!(next(&mut s).await, Err(Error::Io(_))));
+
+    let path = dir.join("log.adi");
+    append(&path, "");
+    let mut s = follow(&path.join("log.adi"));
+    assert!(matches!(next(&mut s).await, Err(Error::Io(_))));
+    assert!(matches!(next(&mut s).await, Err(Error::Io(_))));
+}
@@ -0,0 +1,234 @@
+//! Random access to records in large ADIF files
+//!
+//! A [RecordIndex] records the [Position] at which each record begins.  It
+//! is built with a single pass over the file and can be saved alongside
+//! it, so that later a particular record can be read by seeking to its
+//! offset and resuming parsing there with [seek] or [seek_blocking].
+//! Records and errors read after seeking carry the same [Position]s as
+//! they would in a sequential parse.
+
+use crate::blocking::{RecordReader, TagReader};
+use crate::{
+    Error, InvalidFormatKind, Position, RecordStream, RecordStreamExt, Tag,
+};
+use crate::{TagDecoder, TagStream};
+use futures::StreamExt;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt};
+
+#[cfg(test)]
+mod test;
+
+const MAGIC: &str = "difa-index 1";
+
+/// Starting positions of the records in an ADIF file
+///
+/// Header records are not indexed, so the `n`th entry is the position of
+/// the `n`th record returned by a [RecordStream] after the header.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use difa::TagDecoder;
+/// use difa::index::{RecordIndex, seek};
+/// use futures::StreamExt;
+/// use std::io::Cursor;
+/// let data = b"<adifver:5>3.1.4<eoh>\n<call:4>W1AW<eor>\n<call:4>N0AA<eor>\n";
+/// let index = RecordIndex::build(&data[..], TagDecoder::new(false))
+///     .await
+///     .unwrap();
+/// assert_eq!(index.len(), 2);
+/// let position = index.get(1).unwrap();
+/// assert_eq!((position.line, position.column), (3, 1));
+/// let mut saved = Vec::new();
+/// index.write_to(&mut saved).unwrap();
+/// let index = RecordIndex::read_from(&saved[..]).unwrap();
+/// let reader = Cursor::new(&data[..]);
+/// let mut s = seek(reader, TagDecoder::new(false), index.get(1).unwrap())
+///     .await
+///     .unwrap();
+/// let rec = s.next().await.unwrap().unwrap();
+/// assert_eq!(rec.get("call").unwrap().as_str(), "N0AA");
+/// assert!(s.next().await.is_none());
+/// # });
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordIndex {
+    positions: Vec<Position>,
+}
+
+impl RecordIndex {
+    /// Build an index of the records read by the given decoder.
+    ///
+    /// The whole input is read.  Any error in the input is returned.
+    pub async fn build<R>(reader: R, decoder: TagDecoder) -> Result<Self, Error>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut tags = decoder.tag_stream_with(reader);
+        let mut builder = Builder::default();
+        while let Some(tag) = tags.next().await {
+            builder.push(&tag?, tags.decoder().last_position());
+        }
+        Ok(builder.index)
+    }
+
+    /// Build an index from a blocking reader.
+    ///
+    /// See [build](Self::build).
+    pub fn build_blocking<R>(
+        reader: R, decoder: TagDecoder,
+    ) -> Result<Self, Error>
+    where
+        R: Read,
+    {
+        let mut tags = TagReader::with_decoder(reader, decoder);
+        let mut builder = Builder::default();
+        while let Some(tag) = tags.next() {
+            builder.push(&tag?, tags.decoder().last_position());
+        }
+        Ok(builder.index)
+    }
+
+    /// Return the number of records indexed.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// True if no records were indexed.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Return the position at which the `n`th record begins.
+    pub fn get(&self, n: usize) -> Option<Position> {
+        self.positions.get(n).copied()
+    }
+
+    /// Return the positions of all records.
+    pub fn positions(&self) -> &[Position] {
+        &self.positions
+    }
+
+    /// Save the index, e.g. to a sidecar file next to the ADIF file.
+    ///
+    /// The format is text, with one line per record giving its byte
+    /// offset, line and column.
+    pub fn write_to<W>(&self, writer: W) -> Result<(), Error>
+    where
+        W: Write,
+    {
+        let mut writer = io::BufWriter::new(writer);
+        writeln!(writer, "{MAGIC}")?;
+        for Position { line, column, byte } in &self.positions {
+            writeln!(writer, "{byte} {line} {column}")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load an index saved by [write_to](Self::write_to).
+    ///
+    /// A malformed line is an [InvalidFormatKind::BadIndex] error at its
+    /// position in the saved index.
+    pub fn read_from<R>(reader: R) -> Result<Self, Error>
+    where
+        R: Read,
+    {
+        let mut positions = Vec::new();
+        let mut byte = 0;
+        for (n, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            if n == 0 {
+                if line != MAGIC {
+                    return Err(invalid_index(1, 0));
+                }
+            } else {
+                let Some(p) = parse_entry(&line) else {
+                    return Err(invalid_index(n + 1, byte));
+                };
+                positions.push(p);
+            }
+            byte += line.len() + 1;
+        }
+        if byte == 0 {
+            return Err(invalid_index(1, 0));
+        }
+        Ok(Self { positions })
+    }
+}
+
+fn parse_entry(line: &str) -> Option<Position> {
+    let mut numbers = line.split(' ').map(|n| n.parse().ok());
+    let byte = numbers.next()??;
+    let line = numbers.next()??;
+    let column = numbers.next()??;
+    let valid = numbers.next().is_none() && line > 0 && column > 0;
+    valid.then_some(Position { line, column, byte })
+}
+
+fn invalid_index(line: usize, byte: usize) -> Error {
+    Error::InvalidFormat {
+        kind: InvalidFormatKind::BadIndex,
+        position: Position {
+            line,
+            column: 1,
+            byte,
+        },
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    index: RecordIndex,
+    start: Option<Position>,
+}
+
+impl Builder {
+    fn push(&mut self, tag: &Tag, position: Position) {
+        match tag {
+            Tag::Field(_) => {
+                self.start.get_or_insert(position);
+            }
+            Tag::Eor => {
+                let start = self.start.take().unwrap_or(position);
+                self.index.positions.push(start);
+            }
+            Tag::Eoh => self.start = None,
+            Tag::Preamble(_) => {}
+        }
+    }
+}
+
+/// Seek to a position and return a stream of the records from there.
+///
+/// The position should be one from a [RecordIndex] built from the same
+/// data.  The decoder is configured to report positions relative to the
+/// start of the data.
+pub async fn seek<R>(
+    mut reader: R, decoder: TagDecoder, position: Position,
+) -> Result<RecordStream<TagStream<R>>, Error>
+where
+    R: AsyncRead + AsyncSeek + Unpin,
+{
+    reader.seek(SeekFrom::Start(position.byte as u64)).await?;
+    Ok(decoder
+        .with_position(position)
+        .tag_stream_with(reader)
+        .records())
+}
+
+/// Seek to a position in a blocking reader and read records from there.
+///
+/// See [seek].
+pub fn seek_blocking<R>(
+    mut reader: R, decoder: TagDecoder, position: Position,
+) -> Result<RecordReader<TagReader<R>>, Error>
+where
+    R: Read + Seek,
+{
+    reader.seek(SeekFrom::Start(position.byte as u64))?;
+    Ok(
+        TagReader::with_decoder(reader, decoder.with_position(position))
+            .records(),
+    )
+}
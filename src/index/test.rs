@@ -0,0 +1,203 @@
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::ReadBuf;
+
+use super::*;
+use crate::Record;
+use crate::test::helpers::*;
+
+struct FailingIo;
+
+impl Read for FailingIo {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("boom"))
+    }
+}
+
+impl Write for FailingIo {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("boom"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Err(io::Error::other("boom"))
+    }
+}
+
+impl Seek for FailingIo {
+    fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::other("boom"))
+    }
+}
+
+impl AsyncRead for FailingIo {
+    fn poll_read(
+        self: Pin<&mut Self>, _cx: &mut Context<'_>, _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Err(io::Error::other("boom")))
+    }
+}
+
+impl AsyncSeek for FailingIo {
+    fn start_seek(self: Pin<&mut Self>, _pos: SeekFrom) -> io::Result<()> {
+        Err(io::Error::other("boom"))
+    }
+
+    fn poll_complete(
+        self: Pin<&mut Self>, _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<u64>> {
+        Poll::Ready(Err(io::Error::other("boom")))
+    }
+}
+
+fn sequential(data: &[u8]) -> Vec<Result<Record, Error>> {
+    RecordReader::new(data, false)
+        .filter(|r| !r.as_ref().is_ok_and(|r| r.is_header()))
+        .collect()
+}
+
+async fn build(data: &[u8]) -> RecordIndex {
+    let index = RecordIndex::build(data, TagDecoder::new(false))
+        .await
+        .unwrap();
+    let blocking =
+        RecordIndex::build_blocking(data, TagDecoder::new(false)).unwrap();
+    assert_eq!(index, blocking);
+    index
+}
+
+/// Seek to every record in `data` and compare with a sequential parse.
+async fn same_as_sequential(data: &[u8], index: &RecordIndex) {
+    let expected = sequential(data);
+    for (n, &p) in index.positions().iter().enumerate() {
+        let msg = format!("{n} {}", String::from_utf8_lossy(data));
+        let cursor = Cursor::new(data);
+        let s = seek(cursor, TagDecoder::new(false), p).await.unwrap();
+        let records: Vec<_> = s.collect().await;
+        assert_eq!(records, expected[n..], "{msg}");
+        let cursor = Cursor::new(data);
+        let r = seek_blocking(cursor, TagDecoder::new(false), p).unwrap();
+        let records: Vec<_> = r.collect();
+        assert_eq!(records, expected[n..], "{msg}");
+    }
+}
+
+#[tokio::test]
+async fn matches_sequential() {
+    let cases: &[&[u8]] = &[
+        b"<call:4>W1AW<eor>",
+        b"ADIF Export\r\n<adifver:5>3.1.4<eoh>\n<call:4>W1AW<eor>\n",
+        b"<eor><eor>\n<eor>\r\n<a:1>x<EOR>",
+        b"<a:5><eor><eor><b:5><eoh><eor><c:1>x<eor>",
+        b"<a:1>x<eor>\n  <b:1>y\r\n<c:2>zz<eor>\n<b:1>z<eor>  \n",
+        b"<a:1>x<eor><app_lotw_eof><b:1>y<eor><c:1>z<eor>",
+    ];
+    for data in cases {
+        let index = build(data).await;
+        assert_eq!(index.len(), sequential(data).len());
+        same_as_sequential(data, &index).await;
+    }
+}
+
+#[tokio::test]
+async fn positions() {
+    let data = b"junk\n<a:1>x<eoh>\n<b:1>y<eor>  <eor>\n\n <c:1>z<eor>";
+    let index = build(data).await;
+    let p = |line, column, byte| Position { line, column, byte };
+    assert_eq!(index.positions(), &[p(3, 1, 17), p(3, 14, 30), p(5, 2, 38)]);
+    assert_eq!(index.get(2), Some(p(5, 2, 38)));
+    assert_eq!(index.get(3), None);
+    assert!(!index.is_empty());
+    assert!(build(b"<a:1>x<eoh>").await.is_empty());
+}
+
+#[tokio::test]
+async fn error_positions() {
+    let good = b"<a:1>x<eor>\n<b:1>y<eor>\n<c:1>z<eor>\n";
+    let bad = b"<a:1>x<eor>\n<b:1>y<eor>\n<c:x>z<eor>\n";
+    let index = build(good).await;
+    let err = || invalid_format(bad_length("c", "x"), 3, 1, 24);
+    let cursor = Cursor::new(&bad[..]);
+    let p = index.get(1).unwrap();
+    let mut s = seek(cursor, TagDecoder::new(false), p).await.unwrap();
+    assert_eq!(
+        s.next().await.unwrap().unwrap().get("b").unwrap().as_str(),
+        "y"
+    );
+    assert_eq!(s.next().await.unwrap(), Err(err()));
+    assert_eq!(sequential(bad).pop().unwrap(), Err(err()));
+
+    let cursor = Cursor::new(&good[..27]);
+    let p = index.get(2).unwrap();
+    let mut r = seek_blocking(cursor, TagDecoder::new(false), p).unwrap();
+    assert_eq!(r.next().unwrap(), Err(partial_data(3, 1, 24)));
+}
+
+#[tokio::test]
+async fn build_errors() {
+    let data = b"<a:1>x<eor><b:1>";
+    let res = RecordIndex::build(&data[..], TagDecoder::new(false)).await;
+    assert_eq!(res, Err(partial_data(1, 12, 11)));
+    let res = RecordIndex::build_blocking(&data[..], TagDecoder::new(false));
+    assert_eq!(res, Err(partial_data(1, 12, 11)));
+    let res = RecordIndex::build_blocking(FailingIo, TagDecoder::new(false));
+    assert!(matches!(res, Err(Error::Io(_))));
+}
+
+#[tokio::test]
+async fn seek_errors() {
+    let p = Position {
+        line: 1,
+        column: 1,
+        byte: 0,
+    };
+    let res = seek_blocking(FailingIo, TagDecoder::new(false), p);
+    assert!(matches!(res, Err(Error::Io(_))));
+    let res = seek(FailingIo, TagDecoder::new(false), p).await;
+    assert!(matches!(res, Err(Error::Io(_))));
+}
+
+#[tokio::test]
+async fn save_and_load() {
+    let data = b"<a:1>x<eoh>\n<b:1>y<eor>  <eor>\n\n <c:1>z<eor>";
+    let index = build(data).await;
+    let mut saved = Vec::new();
+    index.write_to(&mut saved).unwrap();
+    let text = "difa-index 1\n12 2 1\n25 2 14\n33 4 2\n";
+    assert_eq!(String::from_utf8(saved).unwrap(), text);
+    assert_eq!(RecordIndex::read_from(text.as_bytes()), Ok(index));
+
+    let empty = RecordIndex::default();
+    let mut saved = Vec::new();
+    empty.write_to(&mut saved).unwrap();
+    assert_eq!(RecordIndex::read_from(&saved[..]), Ok(empty));
+
+    let res = RecordIndex::default().write_to(FailingIo);
+    assert!(matches!(res, Err(Error::Io(_))));
+}
+
+#[test]
+fn load_errors() {
+    let err = |line, byte| {
+        Err(invalid_format(InvalidFormatKind::BadIndex, line, 1, byte))
+    };
+    let cases = [
+        ("", err(1, 0)),
+        ("\n", err(1, 0)),
+        ("difa-index 2\n", err(1, 0)),
+        ("difa-index 1\n1 1\n", err(2, 13)),
+        ("difa-index 1\n1 1 1\n1 1 1 1\n", err(3, 19)),
+        ("difa-index 1\n1 1 1\n1 0 1\n", err(3, 19)),
+        ("difa-index 1\n1 1 1\n1 1 0\n", err(3, 19)),
+        ("difa-index 1\n1 1 1\n1 1 x\n", err(3, 19)),
+        ("difa-index 1\nx 1 1\n", err(2, 13)),
+        ("difa-index 1\n1 x 1\n", err(2, 13)),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(RecordIndex::read_from(text.as_bytes()), expected, "{text}");
+    }
+    let res = RecordIndex::read_from(FailingIo);
+    assert!(matches!(res, Err(Error::Io(_))));
+}
@@ -11,29 +11,120 @@
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use indexmap::{IndexMap, map::Entry};
 use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
 use std::io;
 use std::str::FromStr;
 use thiserror::Error;
 
+pub mod adx;
+pub mod blocking;
+pub mod borrowed;
 pub mod cabrillo;
 mod cistring;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub mod compress;
+mod convert;
+pub mod diagnostic;
+mod encoding;
+pub mod enums;
 pub mod filter;
+#[cfg(feature = "follow")]
+pub mod follow;
+pub mod index;
+mod location;
+pub mod parallel;
 pub mod parse;
+pub mod qso;
+#[cfg(feature = "serde")]
+mod serialize;
+mod userdef;
 pub mod write;
 
 #[cfg(test)]
 mod test;
 
+pub use adx::{AdxDecoder, AdxSink, AdxStream};
 pub use cabrillo::CabrilloSink;
 pub use cistring::{CiStr, CiString};
+pub use convert::FromDatum;
+pub use encoding::{Encoding, LengthUnit};
+pub use enums::{
+    AntPath, Band, Continent, Mode, PropMode, QslRcvd, QslSent, QslVia, Submode,
+};
 pub use filter::{FilterExt, MapExt, NormalizeExt};
-pub use parse::{RecordStream, RecordStreamExt, TagDecoder, TagStream};
+pub use location::Location;
+pub use parse::{
+    LenientTagStream, Limit, Limits, RecordStream, RecordStreamExt, Recovery,
+    TagDecoder, TagStream,
+};
+pub use qso::Qso;
+#[cfg(feature = "serde")]
+pub use serialize::{from_reader, to_writer};
+pub use userdef::{UserDef, UserDefValues};
 pub use write::{OutputTypes, RecordSink, TagEncoder, TagSink, TagSinkExt};
 
+/// Derive conversions between a struct and a [Record].
+///
+/// The derive implements `TryFrom<Record>` for the struct and
+/// `From<Struct>` for [Record], so that a stream of records can be read as
+/// structs and structs can be sent to a [RecordSink].  Each field is read
+/// with [FromDatum] and written with `Into<Datum>`.  Fields of type
+/// [Option] may be absent, and the rest are required.  An empty value
+/// counts as absent.
+///
+/// Fields are named after their struct fields unless given a name with
+/// `#[adif(name = "...")]`.  Given an ADIF type indicator with
+/// `#[adif(type = "...")]`, a string value is parsed as that type when read,
+/// failing if it is not valid, and is written as that type.  A field of
+/// type [Record] marked `#[adif(extra)]` receives all fields of the record
+/// that the struct does not name, and they are written back out with the
+/// rest.
+///
+/// Reading a struct returns [Error::MissingField] for a missing required
+/// field and [Error::InvalidValue] for a value that cannot be converted to
+/// the type of its field.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use chrono::NaiveDate;
+/// use difa::{AdifRecord, Record, RecordStream};
+/// use futures::StreamExt;
+/// use rust_decimal::Decimal;
+///
+/// #[derive(AdifRecord)]
+/// struct Contact {
+///     call: String,
+///     #[adif(name = "qso_date", type = "d")]
+///     date: NaiveDate,
+///     freq: Option<Decimal>,
+///     #[adif(extra)]
+///     rest: Record,
+/// }
+///
+/// let data = b"<call:4>W1AW<qso_date:8>20240115<sig:4>POTA<eor>";
+/// let mut contacts = RecordStream::new(&data[..], true)
+///     .map(|r| r.and_then(Contact::try_from));
+/// let contact = contacts.next().await.unwrap().unwrap();
+/// assert_eq!(contact.date, NaiveDate::from_ymd_opt(2024, 1, 15).unwrap());
+/// assert_eq!(contact.freq, None);
+/// assert_eq!(contact.rest.get("sig").unwrap().as_str(), "POTA");
+///
+/// let record = Record::from(contact);
+/// assert_eq!(record.get("qso_date").unwrap().as_str(), "20240115");
+/// # });
+/// ```
+#[cfg(feature = "derive")]
+pub use difa_derive::AdifRecord;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::convert::{optional, put, remaining, required};
+}
+
 /// Position information for errors in the input stream.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Position {
     /// Line number where error occurred (1-based)
     pub line: usize,
@@ -50,6 +141,26 @@ impl Display for Position {
     }
 }
 
+/// Region of the input from which a record or field was parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// Position of the first byte
+    pub start: Position,
+    /// Position following the last byte
+    pub end: Position,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} to {}", self.start, self.end)
+    }
+}
+
+fn at(span: &Option<Span>) -> String {
+    span.map(|s| format!(" (at {})", s.start))
+        .unwrap_or_default()
+}
+
 /// Errors that can occur during ADIF parsing and processing.
 #[derive(Debug, Error)]
 pub enum Error {
@@ -58,21 +169,34 @@ pub enum Error {
     Io(#[from] io::Error),
     /// Invalid ADIF format encountered during parsing.
     ///
-    /// This includes malformed tags and invalid type specifiers.
-    #[error("Invalid ADIF format: {message} (at {position})")]
+    /// This includes malformed tags and values that do not match their
+    /// type indicators.
+    #[error("Invalid ADIF format: {kind} (at {position})")]
     InvalidFormat {
-        /// Error message describing what went wrong
-        message: Cow<'static, str>,
+        /// What was wrong with the input
+        kind: InvalidFormatKind,
+        /// Position in the input stream
+        position: Position,
+    },
+    /// Input exceeded a limit set on the decoder.
+    #[error("Input exceeds limit: {limit} over {max} (at {position})")]
+    LimitExceeded {
+        /// Limit exceeded
+        limit: Limit,
+        /// Value of the limit
+        max: usize,
         /// Position in the input stream
         position: Position,
     },
     /// Duplicate key encountered in a record.
-    #[error("Duplicate key in record: {key}")]
+    #[error("Duplicate key in record: {key}{}", at(.span))]
     DuplicateKey {
         /// Duplicate key name
         key: String,
         /// Record containing the duplicate
-        record: Record,
+        record: Box<Record>,
+        /// Location of the duplicate field, if read from a stream
+        span: Option<Span>,
     },
     /// Value cannot be output in ADIF format.
     #[error("Cannot output {typ}: {reason}")]
@@ -83,12 +207,32 @@ pub enum Error {
         reason: &'static str,
     },
     /// Missing required field in record.
-    #[error("missing required field '{field}' in record")]
+    #[error("missing required field '{field}' in record{}", at(.span))]
     MissingField {
         /// Missing field name
         field: String,
         /// Record missing the field
-        record: Record,
+        record: Box<Record>,
+        /// Location of the record, if read from a stream
+        span: Option<Span>,
+    },
+    /// Field name is not valid.
+    #[error("invalid field name '{name}'")]
+    InvalidName {
+        /// Offending name
+        name: String,
+    },
+    /// Field value not permitted by its declaration.
+    #[error("invalid value '{value}' for field '{field}'{}", at(.span))]
+    InvalidValue {
+        /// Field name
+        field: String,
+        /// Offending value
+        value: String,
+        /// Record containing the field
+        record: Box<Record>,
+        /// Location of the field, if read from a stream
+        span: Option<Span>,
     },
     /// First record must be a header record.
     #[error("first record must be a header record")]
@@ -99,6 +243,9 @@ pub enum Error {
     /// Error from a normalizer or filter.
     #[error("filter error: {0}")]
     Filter(Cow<'static, str>),
+    /// Error reported by a serde implementation.
+    #[error("serde error: {0}")]
+    Serde(String),
 }
 
 impl PartialEq for Error {
@@ -107,24 +254,38 @@ impl PartialEq for Error {
             (Error::Io(a), Error::Io(b)) => a.kind() == b.kind(),
             (
                 Error::InvalidFormat {
-                    message: ma,
+                    kind: ka,
                     position: pa,
                 },
                 Error::InvalidFormat {
-                    message: mb,
+                    kind: kb,
+                    position: pb,
+                },
+            ) => ka == kb && pa == pb,
+            (
+                Error::LimitExceeded {
+                    limit: la,
+                    max: ma,
+                    position: pa,
+                },
+                Error::LimitExceeded {
+                    limit: lb,
+                    max: mb,
                     position: pb,
                 },
-            ) => ma == mb && pa == pb,
+            ) => la == lb && ma == mb && pa == pb,
             (
                 Error::DuplicateKey {
                     key: ka,
                     record: ra,
+                    span: sa,
                 },
                 Error::DuplicateKey {
                     key: kb,
                     record: rb,
+                    span: sb,
                 },
-            ) => ka == kb && ra == rb,
+            ) => ka == kb && ra == rb && sa == sb,
             (
                 Error::CannotOutput {
                     typ: ta,
@@ -139,20 +300,155 @@ impl PartialEq for Error {
                 Error::MissingField {
                     field: fa,
                     record: ra,
+                    span: sa,
                 },
                 Error::MissingField {
                     field: fb,
                     record: rb,
+                    span: sb,
                 },
-            ) => fa == fb && ra == rb,
+            ) => fa == fb && ra == rb && sa == sb,
+            (
+                Error::InvalidName { name: na },
+                Error::InvalidName { name: nb },
+            ) => na == nb,
+            (
+                Error::InvalidValue {
+                    field: fa,
+                    value: va,
+                    record: ra,
+                    span: sa,
+                },
+                Error::InvalidValue {
+                    field: fb,
+                    value: vb,
+                    record: rb,
+                    span: sb,
+                },
+            ) => fa == fb && va == vb && ra == rb && sa == sb,
             (Error::MissingHeader, Error::MissingHeader) => true,
             (Error::DuplicateHeader, Error::DuplicateHeader) => true,
             (Error::Filter(a), Error::Filter(b)) => a == b,
+            (Error::Serde(a), Error::Serde(b)) => a == b,
             _ => false,
         }
     }
 }
 
+impl Error {
+    /// Return the position in the input at which the error occurred.
+    ///
+    /// Errors about a field or record read from a stream give the start of
+    /// its [span](Self::span).  Returns [None] if the position is unknown.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Error::InvalidFormat { position, .. }
+            | Error::LimitExceeded { position, .. } => Some(*position),
+            _ => self.span().map(|s| s.start),
+        }
+    }
+
+    /// Return the region of the input containing the offending field or
+    /// record, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::DuplicateKey { span, .. }
+            | Error::MissingField { span, .. }
+            | Error::InvalidValue { span, .. } => *span,
+            _ => None,
+        }
+    }
+}
+
+/// Kind of [Error::InvalidFormat]
+///
+/// Names, values and tags that are not valid text are given with the
+/// invalid bytes replaced by U+FFFD.  An unknown type indicator that is
+/// valid text is not an error; the value is read as a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvalidFormatKind {
+    /// Tag not of the form `<name:length>` or `<name:length:type>`
+    BadTag {
+        /// Text between the `<` and `>`
+        tag: String,
+    },
+    /// Length not a non-negative integer
+    BadLength {
+        /// Field name
+        name: String,
+        /// Offending length
+        length: String,
+    },
+    /// Type indicator not valid text
+    BadTypeEncoding {
+        /// Field name
+        name: String,
+        /// Offending type indicator
+        typ: String,
+    },
+    /// Value not valid for its type, e.g. an unparsable date
+    BadValue {
+        /// Field name
+        name: String,
+        /// Offending value
+        value: String,
+        /// Type indicated for the value
+        typ: DataType,
+    },
+    /// Value of a `USERDEFn` header field not a valid declaration
+    BadUserDef {
+        /// Field name
+        name: String,
+        /// Offending value
+        value: String,
+    },
+    /// Text not valid in the input's encoding, e.g. invalid UTF-8
+    BadEncoding {
+        /// Field name, if the text is a value and the name is valid
+        name: Option<String>,
+        /// Offending text
+        text: String,
+    },
+    /// Incomplete tag or record at the end of the input
+    PartialData,
+    /// Malformed line in a saved [RecordIndex](index::RecordIndex), which
+    /// can be rebuilt from the input it indexes
+    BadIndex,
+    /// Other invalid input, such as malformed ADX markup
+    Other(Cow<'static, str>),
+}
+
+impl Display for InvalidFormatKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadTag { tag } => write!(f, "malformed tag <{tag}>"),
+            Self::BadLength { name, length } => {
+                write!(f, "invalid length '{length}' for field '{name}'")
+            }
+            Self::BadTypeEncoding { name, typ } => {
+                write!(f, "invalid type indicator '{typ}' for field '{name}'")
+            }
+            Self::BadValue { name, value, typ } => {
+                write!(f, "invalid {typ:?} '{value}' for field '{name}'")
+            }
+            Self::BadUserDef { name, value } => {
+                write!(f, "invalid declaration '{value}' for field '{name}'")
+            }
+            Self::BadEncoding { name: None, text } => {
+                write!(f, "invalid text '{text}'")
+            }
+            Self::BadEncoding {
+                name: Some(name),
+                text,
+            } => write!(f, "invalid text '{text}' for field '{name}'"),
+            Self::PartialData => f.write_str("partial data at end of stream"),
+            Self::BadIndex => f.write_str("invalid record index"),
+            Self::Other(message) => f.write_str(message),
+        }
+    }
+}
+
 /// Value for a field in an ADIF record.
 ///
 /// ADIF fields can have various types specified in their tags.  If no type
@@ -179,8 +475,25 @@ pub enum Datum {
     /// field.  Attempting to output a datetime with a type indicator will
     /// return an error.
     DateTime(NaiveDateTime),
-    /// String value (default when no type indicator is present).
+    /// String value (type indicator `s` in ADIF tags, and the default when
+    /// no type indicator is present).  Text read with a string type whose
+    /// rules it breaks, such as non-ASCII text marked `s`, is also a String.
     String(String),
+    /// International string (type indicator `i` in ADIF tags), which may
+    /// contain any Unicode characters except line breaks.
+    IntlString(String),
+    /// Multiline string (type indicator `m` in ADIF tags), which may
+    /// contain line breaks.
+    MultilineString(String),
+    /// International multiline string (type indicator `g` in ADIF tags).
+    IntlMultilineString(String),
+    /// Enumeration value (type indicator `e` in ADIF tags).
+    Enumeration(String),
+    /// Location value (type indicator `l` in ADIF tags).
+    Location(Location),
+    /// Declaration of a user-defined field, the value of a `USERDEFn`
+    /// header field.
+    UserDef(Box<UserDef>),
 }
 
 impl Datum {
@@ -188,18 +501,40 @@ impl Datum {
         if b { "Y" } else { "N" }
     }
 
+    fn text(&self) -> Option<&str> {
+        match self {
+            Self::String(s)
+            | Self::IntlString(s)
+            | Self::MultilineString(s)
+            | Self::IntlMultilineString(s)
+            | Self::Enumeration(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Join two values as text, keeping the type of this one if it is a
+    /// type of text that allows the result.
+    fn concatenate(&self, other: &Datum, separator: &str) -> Self {
+        let text = format!("{}{separator}{}", self.as_str(), other.as_str());
+        match self {
+            Self::IntlString(_) => Self::IntlString(text),
+            Self::MultilineString(_) => Self::MultilineString(text),
+            Self::IntlMultilineString(_) => Self::IntlMultilineString(text),
+            _ => Self::String(text),
+        }
+    }
+
     /// Return a [bool] value or coerce a string thereto.
     ///
     /// Returns [None] if a string value fails to parse.
     pub fn as_bool(&self) -> Option<bool> {
         match self {
             Self::Boolean(b) => Some(*b),
-            Self::String(s) => match s.as_str() {
+            _ => match self.text()? {
                 "Y" | "y" => Some(true),
                 "N" | "n" => Some(false),
                 _ => None,
             },
-            _ => None,
         }
     }
 
@@ -209,8 +544,27 @@ impl Datum {
     pub fn as_number(&self) -> Option<Decimal> {
         match self {
             Self::Number(n) => Some(*n),
-            Self::String(s) => Decimal::from_str(s).ok(),
-            _ => None,
+            _ => Decimal::from_str(self.text()?).ok(),
+        }
+    }
+
+    /// Return an integral numeric value as an [i64] or coerce a string
+    /// thereto.
+    ///
+    /// Returns [None] if the number has a fractional part or is out of
+    /// range, or if a string value fails to parse.
+    pub fn as_integer(&self) -> Option<i64> {
+        let n = self.as_number()?;
+        n.fract().is_zero().then(|| n.to_i64()).flatten()
+    }
+
+    /// Return a location value or coerce a string thereto.
+    ///
+    /// Returns [None] if a string value fails to parse.
+    pub fn as_location(&self) -> Option<Location> {
+        match self {
+            Self::Location(l) => Some(*l),
+            _ => Location::parse(self.text()?),
         }
     }
 
@@ -220,32 +574,30 @@ impl Datum {
     pub fn as_date(&self) -> Option<NaiveDate> {
         match self {
             Self::Date(d) => Some(*d),
-            Self::String(s) => NaiveDate::parse_from_str(s, "%Y%m%d").ok(),
-            _ => None,
+            _ => parse_date(self.text()?),
         }
     }
 
     /// Return a time value as a [NaiveTime] or coerce a string thereto.
     ///
-    /// Returns [None] if a string value fails to parse.
+    /// Strings may be either HHMMSS or HHMM.  Returns [None] if a string
+    /// value fails to parse.
     pub fn as_time(&self) -> Option<NaiveTime> {
         match self {
             Self::Time(t) => Some(*t),
-            Self::String(s) => NaiveTime::parse_from_str(s, "%H%M%S").ok(),
-            _ => None,
+            _ => parse_time(self.text()?),
         }
     }
 
     /// Return a datetime value as a [NaiveDateTime] or coerce a string thereto.
     ///
-    /// Returns [None] if a string value fails to parse.
+    /// Strings are a date and a time separated by a space, i.e. either
+    /// YYYYMMDD HHMMSS or YYYYMMDD HHMM.  Returns [None] if a string value
+    /// fails to parse.
     pub fn as_datetime(&self) -> Option<NaiveDateTime> {
         match self {
             Self::DateTime(dt) => Some(*dt),
-            Self::String(s) => {
-                NaiveDateTime::parse_from_str(s, "%Y%m%d %H%M%S").ok()
-            }
-            _ => None,
+            _ => parse_datetime(self.text()?),
         }
     }
 
@@ -255,7 +607,11 @@ impl Datum {
     /// ADIF format (boolean Y/N, date YYYYMMDD, time HHMMSS).
     pub fn as_str(&self) -> Cow<'_, str> {
         match self {
-            Self::String(s) => Cow::Borrowed(s),
+            Self::String(s)
+            | Self::IntlString(s)
+            | Self::MultilineString(s)
+            | Self::IntlMultilineString(s)
+            | Self::Enumeration(s) => Cow::Borrowed(s),
             Self::Boolean(b) => Cow::Borrowed(Self::bool_str(*b)),
             Self::Number(n) => Cow::Owned(n.to_string()),
             Self::Date(d) => Cow::Owned(d.format("%Y%m%d").to_string()),
@@ -263,13 +619,19 @@ impl Datum {
             Self::DateTime(dt) => {
                 Cow::Owned(dt.format("%Y%m%d %H%M%S").to_string())
             }
+            Self::Location(l) => Cow::Owned(l.to_string()),
+            Self::UserDef(u) => Cow::Owned(u.to_string()),
         }
     }
 
     /// Coerce any datum to Cabrillo format string representation.
     pub fn to_cabrillo(&self) -> Cow<'_, str> {
         match self {
-            Self::String(s) => Cow::Borrowed(s),
+            Self::String(s)
+            | Self::IntlString(s)
+            | Self::MultilineString(s)
+            | Self::IntlMultilineString(s)
+            | Self::Enumeration(s) => Cow::Borrowed(s),
             Self::Boolean(b) => Cow::Borrowed(Self::bool_str(*b)),
             Self::Number(n) => Cow::Owned(n.to_string()),
             Self::Date(d) => Cow::Owned(d.format("%Y-%m-%d").to_string()),
@@ -277,10 +639,39 @@ impl Datum {
             Self::DateTime(dt) => {
                 Cow::Owned(dt.format("%Y-%m-%d %H%M").to_string())
             }
+            Self::Location(l) => Cow::Owned(l.to_string()),
+            Self::UserDef(u) => Cow::Owned(u.to_string()),
         }
     }
 }
 
+/// Parse an ADIF date, YYYYMMDD.
+pub(crate) fn parse_date(s: &str) -> Option<NaiveDate> {
+    if s.len() != 8 || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    NaiveDate::parse_from_str(s, "%Y%m%d").ok()
+}
+
+/// Parse an ADIF time, HHMMSS or HHMM.
+pub(crate) fn parse_time(s: &str) -> Option<NaiveTime> {
+    if !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let format = match s.len() {
+        6 => "%H%M%S",
+        4 => "%H%M",
+        _ => return None,
+    };
+    NaiveTime::parse_from_str(s, format).ok()
+}
+
+/// Parse a date and time separated by a space.
+pub(crate) fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    let (date, time) = s.split_once(' ')?;
+    Some(NaiveDateTime::new(parse_date(date)?, parse_time(time)?))
+}
+
 impl From<&str> for Datum {
     fn from(value: &str) -> Self {
         Datum::String(value.to_string())
@@ -323,13 +714,107 @@ impl From<NaiveDateTime> for Datum {
     }
 }
 
+impl From<Location> for Datum {
+    fn from(value: Location) -> Self {
+        Datum::Location(value)
+    }
+}
+
+impl From<UserDef> for Datum {
+    fn from(value: UserDef) -> Self {
+        Datum::UserDef(Box::new(value))
+    }
+}
+
+/// Data type named by an ADIF type indicator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataType {
+    /// Boolean (`b`)
+    Boolean,
+    /// Number (`n`)
+    Number,
+    /// Date (`d`)
+    Date,
+    /// Time (`t`)
+    Time,
+    /// String (`s`)
+    String,
+    /// International string (`i`)
+    IntlString,
+    /// Multiline string (`m`)
+    MultilineString,
+    /// International multiline string (`g`)
+    IntlMultilineString,
+    /// Enumeration (`e`)
+    Enumeration,
+    /// Location (`l`)
+    Location,
+}
+
+impl DataType {
+    /// Return the type named by an indicator, compared case-insensitively.
+    ///
+    /// ```
+    /// use difa::DataType;
+    /// assert_eq!(DataType::from_indicator("N"), Some(DataType::Number));
+    /// assert_eq!(DataType::from_indicator("x"), None);
+    /// ```
+    pub fn from_indicator(typ: &str) -> Option<Self> {
+        let &[c] = typ.as_bytes() else {
+            return None;
+        };
+        let typ = match c.to_ascii_lowercase() {
+            b'b' => Self::Boolean,
+            b'n' => Self::Number,
+            b'd' => Self::Date,
+            b't' => Self::Time,
+            b's' => Self::String,
+            b'i' => Self::IntlString,
+            b'm' => Self::MultilineString,
+            b'g' => Self::IntlMultilineString,
+            b'e' => Self::Enumeration,
+            b'l' => Self::Location,
+            _ => return None,
+        };
+        Some(typ)
+    }
+
+    /// Return the lowercase type indicator.
+    pub fn indicator(&self) -> &'static str {
+        match self {
+            Self::Boolean => "b",
+            Self::Number => "n",
+            Self::Date => "d",
+            Self::Time => "t",
+            Self::String => "s",
+            Self::IntlString => "i",
+            Self::MultilineString => "m",
+            Self::IntlMultilineString => "g",
+            Self::Enumeration => "e",
+            Self::Location => "l",
+        }
+    }
+}
+
 /// A single tag in an ADIF stream and its associated value
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Fields compare equal if their names and values are equal, regardless
+/// of where they were read.
+#[derive(Debug, Clone)]
 pub struct Field {
     name: CiString,
     value: Datum,
+    span: Option<Span>,
+}
+
+impl PartialEq for Field {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value
+    }
 }
 
+impl Eq for Field {}
+
 impl Field {
     /// Create a new field.
     pub fn new<N, V>(name: N, value: V) -> Self
@@ -340,9 +825,15 @@ impl Field {
         Self {
             name: name.into(),
             value: value.into(),
+            span: None,
         }
     }
 
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
     /// Return name of the tag.
     pub fn name(&self) -> &str {
         self.name.as_str()
@@ -352,6 +843,24 @@ impl Field {
     pub fn value(&self) -> &Datum {
         &self.value
     }
+
+    /// Return the location of the tag and value in the input, if known.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::TagDecoder;
+    /// use futures::StreamExt;
+    /// let mut t = TagDecoder::new_stream(&b"\n <call:4>W1AW"[..], true);
+    /// let tag = t.next().await.unwrap().unwrap();
+    /// let span = tag.as_field().unwrap().span().unwrap();
+    /// assert_eq!((span.start.line, span.start.column), (2, 2));
+    /// assert_eq!((span.end.line, span.end.column), (2, 14));
+    /// assert_eq!((span.start.byte, span.end.byte), (2, 14));
+    /// # });
+    /// ```
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -363,6 +872,8 @@ pub enum Tag {
     Eoh,
     /// End of record
     Eor,
+    /// Free text preceding the header
+    Preamble(String),
 }
 
 impl Tag {
@@ -383,15 +894,62 @@ impl Tag {
     pub fn is_eor(&self) -> bool {
         matches!(self, Tag::Eor)
     }
+
+    /// Returns `Some` if this is a `Preamble` tag, otherwise `None`.
+    pub fn as_preamble(&self) -> Option<&str> {
+        let Tag::Preamble(text) = self else {
+            return None;
+        };
+        Some(text)
+    }
 }
 
-/// A single contact record, composed of multiple data fields
+/// Handling of a field that occurs more than once in a record
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Return [Error::DuplicateKey]
+    #[default]
+    Error,
+    /// Keep the first value
+    First,
+    /// Keep the last value
+    Last,
+    /// Join the values as text with the given separator
+    Concatenate(Cow<'static, str>),
+    /// Keep the first value, and make later ones available from
+    /// [Record::get_all].  Sinks write later values after the first.
+    KeepAll,
+}
+
+/// A single contact record, composed of multiple data fields
+///
+/// Records compare equal if their fields are equal, regardless of where
+/// they were read.
+#[derive(Debug, Default, Clone)]
 pub struct Record {
     header: bool,
+    damaged: bool,
+    preamble: Option<Box<str>>,
     fields: IndexMap<CiString, Datum>,
+    span: Option<Span>,
+    // parallel to fields, or empty if no field has a span
+    spans: Vec<Option<Span>>,
+    // later occurrences kept by Duplicates::KeepAll
+    extra: Vec<(CiString, Datum)>,
+}
+
+impl PartialEq for Record {
+    fn eq(&self, other: &Self) -> bool {
+        self.header == other.header
+            && self.damaged == other.damaged
+            && self.preamble == other.preamble
+            && self.fields == other.fields
+            && self.extra == other.extra
+    }
 }
 
+impl Eq for Record {}
+
 impl Record {
     /// Create a new record.
     ///
@@ -442,6 +1000,58 @@ impl Record {
         self.header
     }
 
+    /// True if errors were encountered while parsing this record.
+    ///
+    /// Only a [RecordStream] created with [Recovery::Flag] returns damaged
+    /// records.  Fields following an error may be missing from them.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::{Recovery, RecordStream};
+    /// use futures::StreamExt;
+    /// let mut s = RecordStream::new_lenient(
+    ///     "<call:4>W1AW<freq:x>14<eor>".as_bytes(),
+    ///     Recovery::Flag,
+    /// );
+    /// assert!(s.next().await.unwrap().is_err());
+    /// let record = s.next().await.unwrap().unwrap();
+    /// assert!(record.is_damaged());
+    /// assert_eq!(record.get("call").unwrap().as_str(), "W1AW");
+    /// # });
+    /// ```
+    pub fn is_damaged(&self) -> bool {
+        self.damaged
+    }
+
+    /// Return the free text preceding the header, if any.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::RecordStream;
+    /// use futures::StreamExt;
+    /// let mut s = RecordStream::new(
+    ///     "Exported by hand\n<adifver:5>3.1.4<eoh>".as_bytes(),
+    ///     true,
+    /// );
+    /// let header = s.next().await.unwrap().unwrap();
+    /// assert_eq!(header.preamble(), Some("Exported by hand"));
+    /// # });
+    /// ```
+    pub fn preamble(&self) -> Option<&str> {
+        self.preamble.as_deref()
+    }
+
+    /// Set the free text preceding the header.
+    ///
+    /// The preamble is only written for header records, and it may not
+    /// contain `<`, which would begin a tag.
+    pub fn set_preamble<S>(&mut self, preamble: S)
+    where
+        S: Into<String>,
+    {
+        self.preamble = Some(preamble.into().into_boxed_str());
+    }
+
     /// Return the value of the requested field.
     ///
     /// ```
@@ -491,19 +1101,155 @@ impl Record {
         N: Into<CiString>,
         V: Into<Datum>,
     {
-        let name = name.into();
-        match self.fields.entry(name) {
-            Entry::Occupied(e) => Err(Error::DuplicateKey {
-                key: e.key().to_string(),
-                record: self.clone(),
-            }),
+        self.insert_with(name, value, Duplicates::Error)
+    }
+
+    /// Add a field read from a stream, extending the record's span.
+    pub(crate) fn insert_field(
+        &mut self, field: Field, duplicates: &Duplicates,
+    ) -> Result<(), Error> {
+        self.insert_spanned(field.name, field.value, field.span, duplicates)
+    }
+
+    /// Add a field to the record, handling a duplicate as specified.
+    ///
+    /// ```
+    /// use difa::{Duplicates, Record};
+    /// let mut record = Record::new();
+    /// record.insert("comment", "nice signal").unwrap();
+    /// record
+    ///     .insert_with("COMMENT", "QSB", Duplicates::Concatenate("; ".into()))
+    ///     .unwrap();
+    /// assert_eq!(record.get("comment").unwrap().as_str(), "nice signal; QSB");
+    /// record.insert_with("comment", "73", Duplicates::Last).unwrap();
+    /// assert_eq!(record.get("comment").unwrap().as_str(), "73");
+    /// record.insert_with("comment", "TU", Duplicates::KeepAll).unwrap();
+    /// assert_eq!(record.get("comment").unwrap().as_str(), "73");
+    /// let all: Vec<_> = record.get_all("comment").map(|d| d.as_str()).collect();
+    /// assert_eq!(all, ["73", "TU"]);
+    /// ```
+    pub fn insert_with<N, V>(
+        &mut self, name: N, value: V, duplicates: Duplicates,
+    ) -> Result<(), Error>
+    where
+        N: Into<CiString>,
+        V: Into<Datum>,
+    {
+        self.insert_spanned(name.into(), value.into(), None, &duplicates)
+    }
+
+    fn insert_spanned(
+        &mut self, name: CiString, value: Datum, span: Option<Span>,
+        duplicates: &Duplicates,
+    ) -> Result<(), Error> {
+        let i = match self.fields.entry(name) {
             Entry::Vacant(e) => {
-                e.insert(value.into());
-                Ok(())
+                let i = e.index();
+                e.insert(value);
+                i
             }
+            Entry::Occupied(mut e) => match duplicates {
+                Duplicates::Error => {
+                    let key = e.key().to_string();
+                    // fall back to the field already present
+                    let span = span.or_else(|| {
+                        self.spans.get(e.index()).copied().flatten()
+                    });
+                    return Err(Error::DuplicateKey {
+                        key,
+                        record: Box::new(self.clone()),
+                        span,
+                    });
+                }
+                Duplicates::Last => {
+                    e.insert(value);
+                    e.index()
+                }
+                Duplicates::First => {
+                    self.extend_span(span);
+                    return Ok(());
+                }
+                Duplicates::Concatenate(separator) => {
+                    let v = e.get_mut();
+                    *v = v.concatenate(&value, separator);
+                    self.extend_span(span);
+                    return Ok(());
+                }
+                Duplicates::KeepAll => {
+                    let key = e.key().clone();
+                    self.extra.push((key, value));
+                    self.extend_span(span);
+                    return Ok(());
+                }
+            },
+        };
+        if span.is_some() || !self.spans.is_empty() {
+            self.spans.resize(self.fields.len(), None);
+            self.spans[i] = span;
+        }
+        self.extend_span(span);
+        Ok(())
+    }
+
+    fn extend_span(&mut self, span: Option<Span>) {
+        if let Some(span) = span {
+            let start = self.span.map_or(span.start, |s| s.start);
+            self.span = Some(Span {
+                start,
+                end: span.end,
+            });
         }
     }
 
+    /// Return all values of the requested field.
+    ///
+    /// A field has more than one value only if later occurrences were
+    /// kept with [Duplicates::KeepAll].  They are not returned by
+    /// [fields](Self::fields), but sinks write them after the first.
+    pub fn get_all<'a>(
+        &'a self, name: &'a str,
+    ) -> impl Iterator<Item = &'a Datum> + 'a {
+        let extra = self
+            .extra
+            .iter()
+            .filter(move |(k, _)| k.as_str().eq_ignore_ascii_case(name))
+            .map(|(_, v)| v);
+        self.get(name).into_iter().chain(extra)
+    }
+
+    /// Return the location of the record's fields in the input, if known.
+    ///
+    /// Records read from a [RecordStream] span from the start of their
+    /// first field to the end of their last.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::RecordStream;
+    /// use futures::StreamExt;
+    /// let mut s = RecordStream::new(
+    ///     "<call:4>W1AW\n<freq:6>14.074<eor>".as_bytes(),
+    ///     true,
+    /// );
+    /// let mut record = s.next().await.unwrap().unwrap();
+    /// let span = record.span().unwrap();
+    /// assert_eq!((span.start.line, span.start.column), (1, 1));
+    /// assert_eq!((span.end.line, span.end.column), (2, 15));
+    /// let span = record.field_span("FREQ").unwrap();
+    /// assert_eq!((span.start.line, span.start.column), (2, 1));
+    /// record.insert("band", "20m").unwrap();
+    /// assert!(record.field_span("band").is_none());
+    /// # });
+    /// ```
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Return the location of the requested field in the input, if known.
+    pub fn field_span(&self, name: &str) -> Option<Span> {
+        let i = self.fields.get_index_of(CiStr::new(name))?;
+        self.spans.get(i).copied().flatten()
+    }
+
     /// Consume the record and return an iterator over owned fields.
     pub fn into_fields(self) -> impl Iterator<Item = (String, Datum)> {
         self.fields.into_iter().map(|(k, v)| (k.into_string(), v))
@@ -514,17 +1260,20 @@ impl Record {
     where
         F: FnMut(&str, Datum) -> Datum,
     {
-        let fields = self
-            .fields
-            .into_iter()
-            .map(|(k, v)| {
-                let v = f(k.as_str(), v);
-                (k, v)
-            })
-            .collect();
+        let mut map = |(k, v): (CiString, Datum)| {
+            let v = f(k.as_str(), v);
+            (k, v)
+        };
+        let fields = self.fields.into_iter().map(&mut map).collect();
+        let extra = self.extra.into_iter().map(map).collect();
         Self {
             header: self.header,
+            damaged: self.damaged,
+            preamble: self.preamble,
             fields,
+            span: self.span,
+            spans: self.spans,
+            extra,
         }
     }
 
@@ -532,4 +1281,108 @@ impl Record {
     pub fn fields(&self) -> impl Iterator<Item = (&str, &Datum)> {
         self.fields.iter().map(|(k, v)| (k.as_str(), v))
     }
+
+    /// Return the fields to output, each followed by any later occurrences
+    /// kept with [Duplicates::KeepAll].  Occurrences whose first has been
+    /// removed come last.
+    pub(crate) fn output_fields(&self) -> impl Iterator<Item = (&str, &Datum)> {
+        let extra = &self.extra;
+        let orphans = extra
+            .iter()
+            .filter(|(k, _)| !self.fields.contains_key(k))
+            .map(|(k, v)| (k, v));
+        self.fields
+            .iter()
+            .flat_map(move |(name, v)| {
+                let later = extra.iter().filter(move |(k, _)| k == name);
+                std::iter::once((name, v)).chain(later.map(|(k, v)| (k, v)))
+            })
+            .chain(orphans)
+            .map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Remove fields for which the predicate returns false.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str, &Datum) -> bool,
+    {
+        let spans = &mut self.spans;
+        let (mut read, mut write) = (0, 0);
+        self.fields.retain(|k, v| {
+            let keep = f(k.as_str(), v);
+            if keep {
+                if let Some(&span) = spans.get(read) {
+                    spans[write] = span;
+                }
+                write += 1;
+            }
+            read += 1;
+            keep
+        });
+        spans.truncate(write);
+        self.extra.retain(|(k, v)| f(k.as_str(), v));
+    }
+
+    /// Return an iterator over application-defined fields, i.e. those named
+    /// `APP_{PROGRAMID}_{FIELDNAME}`, as program ID, field name and value.
+    ///
+    /// ```
+    /// use difa::Record;
+    /// let mut record = Record::new();
+    /// record.insert("call", "W1AW").unwrap();
+    /// record.insert("app_lotw_mode", "FT8").unwrap();
+    /// record.insert_app("N1MM", "RADIO_NR", "1").unwrap();
+    /// let apps: Vec<_> = record
+    ///     .app_fields()
+    ///     .map(|(p, f, v)| (p, f, v.as_str().into_owned()))
+    ///     .collect();
+    /// assert_eq!(
+    ///     apps,
+    ///     [("lotw", "mode", "FT8".into()), ("N1MM", "RADIO_NR", "1".into())]
+    /// );
+    /// assert_eq!(record.get_app("LoTW", "Mode").unwrap().as_str(), "FT8");
+    /// ```
+    pub fn app_fields(&self) -> impl Iterator<Item = (&str, &str, &Datum)> {
+        self.fields().filter_map(|(k, v)| {
+            let (program, field) = split_app_name(k)?;
+            Some((program, field, v))
+        })
+    }
+
+    /// Return the application-defined field for the given program,
+    /// compared case-insensitively.
+    pub fn get_app(&self, program: &str, field: &str) -> Option<&Datum> {
+        self.get(&app_name(program, field).ok()?)
+    }
+
+    /// Add an application-defined field to the record.
+    ///
+    /// Returns an error if the program ID contains an underscore, if either
+    /// part of the name is empty, or if the field is already present.
+    pub fn insert_app<V>(
+        &mut self, program: &str, field: &str, value: V,
+    ) -> Result<(), Error>
+    where
+        V: Into<Datum>,
+    {
+        self.insert(app_name(program, field)?, value)
+    }
+}
+
+/// Split an application-defined field name into program ID and field name.
+pub(crate) fn split_app_name(name: &str) -> Option<(&str, &str)> {
+    let prefix = name.get(..4)?;
+    if !prefix.eq_ignore_ascii_case("app_") {
+        return None;
+    }
+    let (program, field) = name[4..].split_once('_')?;
+    (!program.is_empty() && !field.is_empty()).then_some((program, field))
+}
+
+fn app_name(program: &str, field: &str) -> Result<String, Error> {
+    let name = format!("APP_{program}_{field}");
+    match split_app_name(&name) {
+        Some((p, _)) if p == program => Ok(name),
+        _ => Err(Error::InvalidName { name }),
+    }
 }
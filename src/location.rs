@@ -0,0 +1,117 @@
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use std::fmt::{Display, Formatter, Result};
+
+/// A latitude or longitude in ADIF location format.
+///
+/// Locations are written as `XDDD MM.MMM`, where `X` is the direction
+/// (`N`, `S`, `E` or `W`), `DDD` is degrees, and `MM.MMM` is minutes.
+///
+/// ```
+/// use difa::Location;
+/// let loc = Location::parse("W087 54.355").unwrap();
+/// assert_eq!(loc.direction(), 'W');
+/// assert_eq!(loc.degrees(), 87);
+/// assert_eq!(loc.minutes(), "54.355".parse().unwrap());
+/// assert!(loc.to_degrees() < 0.into());
+/// assert_eq!(loc.to_string(), "W087 54.355");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Location {
+    direction: char,
+    degrees: u8,
+    // thousandths of a minute
+    minutes: u16,
+}
+
+impl Location {
+    /// Create a location, or return [None] if out of range.
+    ///
+    /// Minutes must be less than 60 with at most three decimal places.
+    /// Latitudes are limited to 90 degrees and longitudes to 180.
+    pub fn new(direction: char, degrees: u8, minutes: Decimal) -> Option<Self> {
+        let direction = direction.to_ascii_uppercase();
+        let max = match direction {
+            'N' | 'S' => 90,
+            'E' | 'W' => 180,
+            _ => return None,
+        };
+        if minutes.is_sign_negative() || minutes.normalize().scale() > 3 {
+            return None;
+        }
+        let minutes = (minutes * Decimal::ONE_THOUSAND)
+            .to_u16()
+            .filter(|&m| m < 60000)?;
+        if degrees > max || (degrees == max && minutes > 0) {
+            return None;
+        }
+        Some(Self {
+            direction,
+            degrees,
+            minutes,
+        })
+    }
+
+    /// Parse a location in ADIF format, or return [None] if malformed.
+    pub fn parse(s: &str) -> Option<Self> {
+        let b = s.as_bytes();
+        let digits = |r: std::ops::Range<usize>| {
+            b[r].iter().try_fold(0u32, |n, &d| {
+                d.is_ascii_digit().then(|| n * 10 + u32::from(d - b'0'))
+            })
+        };
+        if b.len() != 11 || b[4] != b' ' || b[7] != b'.' {
+            return None;
+        }
+        let degrees = u8::try_from(digits(1..4)?).ok()?;
+        let minutes = digits(5..7)? * 1000 + digits(8..11)?;
+        let minutes = Decimal::new(i64::from(minutes), 3);
+        Self::new(char::from(b[0]), degrees, minutes)
+    }
+
+    /// Return the direction: `N`, `S`, `E` or `W`.
+    pub fn direction(&self) -> char {
+        self.direction
+    }
+
+    /// Return the whole degrees.
+    pub fn degrees(&self) -> u8 {
+        self.degrees
+    }
+
+    /// Return the minutes.
+    pub fn minutes(&self) -> Decimal {
+        Decimal::new(i64::from(self.minutes), 3)
+    }
+
+    /// True if this location is a latitude rather than a longitude.
+    pub fn is_latitude(&self) -> bool {
+        matches!(self.direction, 'N' | 'S')
+    }
+
+    /// Return signed decimal degrees, negative to the south and west.
+    pub fn to_degrees(&self) -> Decimal {
+        let degrees =
+            Decimal::from(self.degrees) + self.minutes() / Decimal::from(60);
+        match self.direction {
+            'S' | 'W' => -degrees,
+            _ => degrees,
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let Self {
+            direction,
+            degrees,
+            minutes,
+        } = self;
+        write!(
+            f,
+            "{direction}{degrees:03} {:02}.{:03}",
+            minutes / 1000,
+            minutes % 1000
+        )
+    }
+}
@@ -0,0 +1,154 @@
+//! Parsing large ADIF data in memory on multiple threads
+//!
+//! A [ParallelParser] makes a quick pass over the data to find record
+//! boundaries, skipping over values by their declared lengths so that text
+//! such as `<eor>` inside a value does not end a record early.  The data is
+//! split at those boundaries into one chunk per thread, and each chunk is
+//! parsed by a [RecordParser] that starts at the chunk's line and column,
+//! so records and errors carry the same [Position]s as a sequential parse.
+
+use crate::borrowed::{RecordParser, RecordRef, split_tag};
+use crate::{Error, Position, Record};
+use std::{panic, thread};
+
+#[cfg(test)]
+mod test;
+
+/// Parser that splits in-memory data across threads
+///
+/// ```
+/// use difa::parallel::ParallelParser;
+/// let data = b"<call:4>W1AW<comment:5><eor><eor><call:4>N0AA<eor>";
+/// let records = ParallelParser::new(data, false)
+///     .with_threads(2)
+///     .records()
+///     .unwrap();
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(records[0].get("comment").unwrap().as_str(), "<eor>");
+/// assert_eq!(records[1].get("call").unwrap().as_str(), "N0AA");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParallelParser<'a> {
+    data: &'a [u8],
+    ignore_partial: bool,
+    threads: usize,
+}
+
+impl<'a> ParallelParser<'a> {
+    /// Create a new parser over the given data using all available cores.
+    ///
+    /// See [RecordStream::new](crate::RecordStream::new) for the meaning
+    /// of `ignore_partial`.
+    pub fn new(data: &'a [u8], ignore_partial: bool) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Self {
+            data,
+            ignore_partial,
+            threads,
+        }
+    }
+
+    /// Use at most the given number of threads, but at least one.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Parse all records, borrowing from the input.
+    ///
+    /// Returns the first error in the data, as a [RecordParser] would.
+    pub fn parse(&self) -> Result<Vec<RecordRef<'a>>, Error> {
+        self.run(Ok)
+    }
+
+    /// Parse all records and convert them into owned records.
+    ///
+    /// Returns the first error in the data, including duplicate fields.
+    pub fn records(&self) -> Result<Vec<Record>, Error> {
+        self.run(|r| r.to_owned())
+    }
+
+    /// Return the positions at which each chunk starts.
+    fn chunks(&self) -> Vec<Position> {
+        let data = self.data;
+        let size = data.len() / self.threads + 1;
+        let mut starts = vec![Position {
+            line: 1,
+            column: 1,
+            byte: 0,
+        }];
+        let mut pos = 0;
+        while let Some(begin) = data[pos..].iter().position(|&b| b == b'<') {
+            let rest = &data[pos + begin..];
+            let Some(end) = rest.iter().position(|&b| b == b'>') else {
+                break;
+            };
+            let tag = &rest[1..end];
+            pos += begin + end + 1;
+            if tag.eq_ignore_ascii_case(b"eor")
+                || tag.eq_ignore_ascii_case(b"eoh")
+            {
+                let last = starts[starts.len() - 1];
+                if pos - last.byte >= size && pos < data.len() {
+                    starts.push(advance(last, &data[last.byte..pos]));
+                }
+                continue;
+            }
+            // anything unexpected is left to the parser of the last chunk
+            match split_tag(tag) {
+                Ok((_, len, _)) if len <= data.len() - pos => pos += len,
+                _ => break,
+            }
+        }
+        starts
+    }
+
+    fn run<T, F>(&self, f: F) -> Result<Vec<T>, Error>
+    where
+        T: Send,
+        F: Fn(RecordRef<'a>) -> Result<T, Error> + Sync,
+    {
+        let starts = self.chunks();
+        let ends = starts.iter().skip(1).map(|p| p.byte);
+        let ends = ends.chain([self.data.len()]);
+        let f = &f;
+        let results: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = starts
+                .iter()
+                .zip(ends)
+                .map(|(&start, end)| {
+                    let data = &self.data[..end];
+                    let ignore_partial = self.ignore_partial;
+                    s.spawn(move || {
+                        RecordParser::resume(data, ignore_partial, start)
+                            .map(|r| r.and_then(f))
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .collect()
+        });
+        let mut records = Vec::new();
+        for result in results {
+            records.extend(result?);
+        }
+        Ok(records)
+    }
+}
+
+/// Return the position following the given data.
+fn advance(start: Position, data: &[u8]) -> Position {
+    let lines = data.iter().filter(|&&b| b == b'\n').count();
+    let column = match data.iter().rposition(|&b| b == b'\n') {
+        Some(i) => data.len() - i,
+        None => start.column + data.len(),
+    };
+    Position {
+        line: start.line + lines,
+        column,
+        byte: start.byte + data.len(),
+    }
+}
@@ -0,0 +1,123 @@
+use super::*;
+use crate::test::helpers::*;
+
+/// Parse sequentially and on various numbers of threads and compare.
+fn same_as_sequential(data: &[u8], ignore_partial: bool) {
+    let expected: Result<Vec<_>, _> =
+        RecordParser::new(data, ignore_partial).collect();
+    let owned: Result<Vec<_>, _> = RecordParser::new(data, ignore_partial)
+        .map(|r| r.and_then(|r| r.to_owned()))
+        .collect();
+    for threads in 1..=8 {
+        let p = ParallelParser::new(data, ignore_partial).with_threads(threads);
+        let msg = String::from_utf8_lossy(data);
+        assert_eq!(p.parse(), expected, "{threads} {msg}");
+        assert_eq!(p.records(), owned, "{threads} {msg}");
+    }
+}
+
+#[test]
+fn matches_sequential() {
+    let cases: &[&[u8]] = &[
+        b"",
+        b"  \n ",
+        b"<call:4>W1AW<eor>",
+        b"ADIF Export\r\n<adifver:5>3.1.4<eoh>\n<call:4>W1AW<eor>\n",
+        b"<eor><eoh><eor><EOR>\n<eor>\r\n<eor>",
+        b"<a:5><eor><eor><b:5><eoh><eor><c:1>x<eor>",
+        b"<a:1>x<eor>\njunk\n<b:1>y<eor>\n junk",
+        b"<a:1>x<eor>\n<b:1>y<eor><b:1>z<eor><c:3>",
+        b"<a:1>x<eor>\n<b:1>y<eor><b:1>z<eor><c:3>ab",
+        b"<a:1>x<eor>\n<b:1>y<eor><b:1>z<eor><c:9>ab<eor><eor>",
+        b"<a:1>x<eor>\n<b:1>y<eor><b:1>z<eor><c",
+        b"<a:1>x<eor>\n<b:1>y<eor><b:x>z<eor><c:1>x<eor>",
+        b"<a:1>x<eor>\n<b:1>y<eor><b:1:n>z<eor><c:1>x<eor>",
+        b"<a:1>x<eor>\n<b:1>y<eor><b:1>\xff<eor><c:1>x<eor>",
+        b"<a:1>x<eor>\n<b:1>y<b:1>z<eor><c:1>x<eor><d:1>x<eor>",
+        b"<a:1>x<eor><app_lotw_eof><b:1>y<eor><c:1>z<eor>",
+    ];
+    for data in cases {
+        same_as_sequential(data, false);
+        same_as_sequential(data, true);
+    }
+}
+
+#[test]
+fn matches_sequential_sample() {
+    let data = std::fs::read("examples/sample.adif").unwrap();
+    same_as_sequential(&data, false);
+}
+
+#[test]
+fn eor_in_value() {
+    let mut data = Vec::new();
+    for i in 0..100 {
+        let comment = format!("<eor>\n{i}<eor>");
+        let field = format!("<comment:{}>{comment}<eor>\n", comment.len());
+        data.extend_from_slice(field.as_bytes());
+    }
+    let records = ParallelParser::new(&data, false)
+        .with_threads(7)
+        .records()
+        .unwrap();
+    assert_eq!(records.len(), 100);
+    for (i, r) in records.iter().enumerate() {
+        let comment = r.get("comment").unwrap().as_str();
+        assert_eq!(comment, format!("<eor>\n{i}<eor>"));
+    }
+}
+
+#[test]
+fn positions() {
+    let data = b"<a:1>x<eor>\n<b:1>y<eor>\n  <c:1>z<eor><d:2>\n1<eor>";
+    let p = ParallelParser::new(data, false).with_threads(4);
+    assert_eq!(p.chunks().len(), 3);
+    let positions: Vec<_> =
+        p.parse().unwrap().iter().map(|r| r.position()).collect();
+    let expected = [(1, 1, 0), (2, 1, 12), (3, 3, 26), (3, 14, 37)];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|&(line, column, byte)| Position { line, column, byte })
+        .collect();
+    assert_eq!(positions, expected);
+}
+
+#[test]
+fn error_in_later_chunk() {
+    let data = b"<a:1>x<eor>\n<b:1>y<eor>\n<c:1>z<eor>\n<d:x>1<eor>";
+    let p = ParallelParser::new(data, false).with_threads(4);
+    assert_eq!(
+        p.records().unwrap_err(),
+        invalid_format(bad_length("d", "x"), 4, 1, 36)
+    );
+}
+
+#[test]
+fn first_error_wins() {
+    let data = b"<a:1>x<a:1>x<eor>\n<b:1>y<eor>\n<c:x>z<eor>";
+    let p = ParallelParser::new(data, false).with_threads(4);
+    let mut expected = Record::new();
+    expected.insert("a", "x").unwrap();
+    assert_eq!(p.records().unwrap_err(), duplicate_key("a", expected));
+}
+
+#[test]
+fn zero_threads() {
+    let data = b"<a:1>x<eor><b:1>y<eor>";
+    let p = ParallelParser::new(data, false).with_threads(0);
+    assert_eq!(p.parse().unwrap().len(), 2);
+}
+
+#[test]
+fn default_threads() {
+    let data = b"<a:1>x<eor><b:1>y<eor>";
+    assert_eq!(ParallelParser::new(data, false).records().unwrap().len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "boom")]
+fn panic_propagates() {
+    let data = b"<a:1>x<eor><b:1>y<eor>";
+    let p = ParallelParser::new(data, false).with_threads(2);
+    let _: Result<Vec<()>, _> = p.run(|_| panic!("boom"));
+}
@@ -1,11 +1,15 @@
 //! Parsing of ADIF data at various levels of sophistication
 
-use crate::{Datum, Error, Field, Position, Record, Tag};
+use crate::{
+    DataType, Datum, Duplicates, Encoding, Error, Field, InvalidFormatKind,
+    LengthUnit, Location, Position, Record, Span, Tag, UserDef, parse_date,
+    parse_time,
+};
 use bytes::{Buf, BytesMut};
-use chrono::{NaiveDate, NaiveTime};
 use futures::stream::Stream;
 use rust_decimal::Decimal;
 use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
 use std::pin::Pin;
 use std::str::FromStr;
 use std::task::{Context, Poll};
@@ -18,6 +22,7 @@ mod test;
 #[derive(Debug)]
 enum ParserTag {
     Field(Field),
+    Preamble(String),
     Eoh,
     Eor,
     Eof,
@@ -26,16 +31,216 @@ enum ParserTag {
 /// Stream of ADIF tags from an async reader.
 pub type TagStream<R> = FramedRead<R, TagDecoder>;
 
+/// Limits on the input accepted by a [TagDecoder]
+///
+/// Without limits, a tag declaring a huge value length makes the decoder
+/// buffer input until that much has arrived.  Input exceeding a limit
+/// returns [Error::LimitExceeded] instead.  A limit of [None], the
+/// default, does not apply.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum declared length of a value, in the decoder's length unit
+    pub max_value_length: Option<usize>,
+    /// Maximum length in bytes of a tag between its `<` and `>`
+    pub max_tag_length: Option<usize>,
+    /// Maximum number of fields in a record
+    pub max_fields: Option<usize>,
+    /// Maximum size in bytes of a record, including any text preceding
+    /// it since the end of the previous record
+    pub max_record_bytes: Option<usize>,
+}
+
+/// Limit exceeded by the input, reported by [Error::LimitExceeded]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// [Limits::max_value_length]
+    ValueLength,
+    /// [Limits::max_tag_length]
+    TagLength,
+    /// [Limits::max_fields]
+    Fields,
+    /// [Limits::max_record_bytes]
+    RecordBytes,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::ValueLength => "value length",
+            Self::TagLength => "tag length",
+            Self::Fields => "number of fields",
+            Self::RecordBytes => "record size",
+        };
+        f.write_str(s)
+    }
+}
+
 /// Decoder for parsing individual ADIF tags from a byte stream.
 #[derive(Debug, Default)]
 pub struct TagDecoder {
     ignore_partial: bool,
+    encoding: Encoding,
+    length: LengthUnit,
+    limits: Limits,
+    started: bool,
+    // no end of header or record yet, so fields may be in a header
+    header: bool,
     consumed: usize,
     line: usize,
     column: usize,
+    last: Position,
+    // byte offset following the previous record, and fields since then
+    record_start: usize,
+    fields: usize,
 }
 
 impl TagDecoder {
+    /// Create a new TagDecoder.
+    ///
+    /// See [new_stream](Self::new_stream) for the meaning of
+    /// `ignore_partial`.  The decoder expects UTF-8 input with lengths
+    /// counted in bytes unless configured otherwise.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::{Encoding, LengthUnit, TagDecoder};
+    /// use futures::StreamExt;
+    /// let mut t = TagDecoder::new(true)
+    ///     .with_encoding(Encoding::Latin1)
+    ///     .with_length_unit(LengthUnit::Chars)
+    ///     .tag_stream_with(&b"<NAME:6>J\xfcrgen"[..]);
+    /// let tag = t.next().await.unwrap().unwrap();
+    /// assert_eq!(tag.as_field().unwrap().value().as_str(), "J\u{fc}rgen");
+    /// # });
+    /// ```
+    pub fn new(ignore_partial: bool) -> Self {
+        Self {
+            ignore_partial,
+            encoding: Encoding::default(),
+            length: LengthUnit::default(),
+            limits: Limits::default(),
+            started: false,
+            header: true,
+            consumed: 0,
+            line: 1,
+            column: 1,
+            last: Position {
+                line: 1,
+                column: 1,
+                byte: 0,
+            },
+            record_start: 0,
+            fields: 0,
+        }
+    }
+
+    /// Set the position of the first byte of input.
+    ///
+    /// This allows decoding to start partway through a file, e.g. at an
+    /// offset from a [RecordIndex](crate::index::RecordIndex), with errors
+    /// reporting positions within the whole file.  Text preceding the first
+    /// tag is treated as a preamble, and `USERDEFn` fields as declarations,
+    /// only at the start of a file.
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.started = position.byte > 0;
+        self.header = position.byte == 0;
+        self.consumed = position.byte;
+        self.line = position.line;
+        self.column = position.column;
+        self.last = position;
+        self.record_start = position.byte;
+        self
+    }
+
+    /// Forget all input, as if starting a new file.
+    #[cfg(feature = "follow")]
+    pub(crate) fn restart(&mut self) {
+        *self = Self::new(self.ignore_partial)
+            .with_encoding(self.encoding)
+            .with_length_unit(self.length)
+            .with_limits(self.limits);
+    }
+
+    /// Return the position at which the most recently decoded tag began.
+    pub fn last_position(&self) -> Position {
+        self.last
+    }
+
+    /// Set the character encoding of the input.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set the unit in which value lengths are counted.
+    pub fn with_length_unit(mut self, length: LengthUnit) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Limit the input accepted, e.g. from untrusted sources.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::{Error, Limit, Limits, TagDecoder};
+    /// use futures::StreamExt;
+    /// let limits = Limits {
+    ///     max_value_length: Some(1024),
+    ///     ..Limits::default()
+    /// };
+    /// let mut t = TagDecoder::new(true)
+    ///     .with_limits(limits)
+    ///     .tag_stream_with("<notes:99999999999>".as_bytes());
+    /// let err = t.next().await.unwrap().unwrap_err();
+    /// assert!(matches!(
+    ///     err,
+    ///     Error::LimitExceeded {
+    ///         limit: Limit::ValueLength,
+    ///         max: 1024,
+    ///         ..
+    ///     }
+    /// ));
+    /// # });
+    /// ```
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Create a stream from this decoder and a reader.
+    pub fn tag_stream_with<R>(self, reader: R) -> TagStream<R>
+    where
+        R: AsyncRead,
+    {
+        FramedRead::new(reader, self)
+    }
+
+    /// Create a lenient stream from this decoder and a reader.
+    ///
+    /// Rather than ending at the first invalid tag, the stream returns
+    /// the error and resumes parsing at the next `<`, so that the rest of
+    /// a damaged file can be salvaged.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::TagDecoder;
+    /// use futures::StreamExt;
+    /// let mut t = TagDecoder::new(true)
+    ///     .lenient_stream_with("<a:x>1<b:1>2".as_bytes());
+    /// assert!(t.next().await.unwrap().is_err());
+    /// let tag = t.next().await.unwrap().unwrap();
+    /// assert_eq!(tag.as_field().unwrap().name(), "b");
+    /// # });
+    /// ```
+    pub fn lenient_stream_with<R>(self, reader: R) -> LenientTagStream<R>
+    where
+        R: AsyncRead,
+    {
+        LenientTagStream {
+            inner: FramedRead::new(reader, LenientDecoder(self)),
+        }
+    }
+
     /// Create a new stream that returns ADIF tags.
     ///
     /// Tag names preserve their original case but are compared
@@ -60,13 +265,7 @@ impl TagDecoder {
     where
         R: AsyncRead,
     {
-        let decoder = Self {
-            ignore_partial,
-            consumed: 0,
-            line: 1,
-            column: 1,
-        };
-        FramedRead::new(reader, decoder)
+        Self::new(ignore_partial).tag_stream_with(reader)
     }
 
     fn position(&self) -> Position {
@@ -77,13 +276,46 @@ impl TagDecoder {
         }
     }
 
-    fn invalid_tag(&self, tag: &[u8]) -> Error {
+    fn invalid(&self, kind: InvalidFormatKind) -> Error {
         Error::InvalidFormat {
-            message: Cow::Owned(String::from_utf8_lossy(tag).into_owned()),
+            kind,
             position: self.position(),
         }
     }
 
+    fn check(
+        &self, limit: Limit, max: Option<usize>, n: usize,
+    ) -> Result<(), Error> {
+        match max {
+            Some(max) if n > max => Err(Error::LimitExceeded {
+                limit,
+                max,
+                position: self.position(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Discard buffered input once the record it belongs to is too large.
+    ///
+    /// Only the start of an incomplete tag or text is buffered here, so
+    /// discarding it bounds memory use even if the tag never ends.
+    fn check_buffered(&mut self, src: &mut BytesMut) -> Result<(), Error> {
+        let n = self.consumed + src.len() - self.record_start;
+        let max = self.limits.max_record_bytes;
+        self.check(Limit::RecordBytes, max, n).inspect_err(|_| {
+            let n = src.len();
+            self.advance(src, n);
+        })
+    }
+
+    fn end_record(&mut self, src: &mut BytesMut, n: usize) {
+        self.advance(src, n);
+        self.record_start = self.consumed;
+        self.fields = 0;
+        self.header = false;
+    }
+
     fn advance_slice(&mut self, data: &[u8]) {
         for &byte in data {
             if byte == b'\n' {
@@ -101,66 +333,58 @@ impl TagDecoder {
         src.advance(consumed);
     }
 
-    fn parse_typed_value(
-        &self, tag: &[u8], v: &str, typ: Option<&str>,
-    ) -> Result<Datum, Error> {
-        match typ {
-            Some("n") | Some("N") => {
-                let num = Decimal::from_str(v.trim())
-                    .map_err(|_| self.invalid_tag(tag))?;
-                Ok(Datum::Number(num))
-            }
-            Some("b") | Some("B") => {
-                let b = match v.trim() {
-                    "Y" | "y" => true,
-                    "N" | "n" => false,
-                    _ => return Err(self.invalid_tag(tag)),
-                };
-                Ok(Datum::Boolean(b))
-            }
-            Some("d") | Some("D") => {
-                let date = NaiveDate::parse_from_str(v.trim(), "%Y%m%d")
-                    .map_err(|_| self.invalid_tag(tag))?;
-                Ok(Datum::Date(date))
-            }
-            Some("t") | Some("T") => {
-                let time = NaiveTime::parse_from_str(v.trim(), "%H%M%S")
-                    .map_err(|_| self.invalid_tag(tag))?;
-                Ok(Datum::Time(time))
-            }
-            _ => Ok(Datum::String(v.to_string())),
-        }
-    }
-
-    fn as_str<'a>(&self, data: &'a [u8], tag: &[u8]) -> Result<&'a str, Error> {
-        str::from_utf8(data).map_err(|_| self.invalid_tag(tag))
+    fn decode_str<'a>(
+        &self, data: &'a [u8], name: Option<&str>,
+    ) -> Result<Cow<'a, str>, Error> {
+        self.encoding.decode(data).ok_or_else(|| {
+            self.invalid(InvalidFormatKind::BadEncoding {
+                name: name.map(str::to_string),
+                text: lossy(data),
+            })
+        })
     }
 
     fn parse_value<'a>(
         &self, src: &'a BytesMut, offset: usize, tag: &'a [u8],
-    ) -> Result<Option<(&'a str, Datum, usize)>, Error> {
-        let err = || self.invalid_tag(tag);
-
+    ) -> Result<Option<(Cow<'a, str>, Datum, usize)>, Error> {
         let mut parts = tag.split(|&b| b == b':');
         let (name, len, typ) =
             match (parts.next(), parts.next(), parts.next(), parts.next()) {
                 (Some(name), Some(len), typ, None) => (name, len, typ),
-                _ => return Err(err()),
+                _ => {
+                    let tag = lossy(tag);
+                    return Err(self.invalid(InvalidFormatKind::BadTag { tag }));
+                }
             };
 
-        let name = self.as_str(name, tag)?;
-        let len = self.as_str(len, tag)?;
-        let len = len.parse::<usize>().map_err(|_| err())?;
-        let typ = typ.map(|t| self.as_str(t, tag)).transpose()?;
-
-        let (begin, end) = (offset + 1, offset + 1 + len);
+        let name = self.decode_str(name, None)?;
+        let len = parse_length(&name, len).map_err(|k| self.invalid(k))?;
+        self.check(Limit::ValueLength, self.limits.max_value_length, len)?;
+        let typ = typ
+            .map(|t| parse_type(&name, t))
+            .transpose()
+            .map_err(|k| self.invalid(k))?;
+
+        let begin = offset + 1;
+        let len = match self.length {
+            LengthUnit::Bytes => len,
+            LengthUnit::Chars => {
+                let rest = src.get(begin..).unwrap_or_default();
+                let Some(len) = self.encoding.char_bytes(rest, len) else {
+                    return Ok(None);
+                };
+                len
+            }
+        };
+        let end = begin + len;
         if end > src.len() {
             return Ok(None);
         }
 
         let value = &src[begin..end];
-        let value = self.as_str(value, tag)?;
-        let value = self.parse_typed_value(tag, value, typ)?;
+        let value = self.decode_str(value, Some(&name))?;
+        let value = parse_field_value(&name, &value, typ, self.header)
+            .map_err(|k| self.invalid(k))?;
 
         Ok(Some((name, value, end)))
     }
@@ -178,6 +402,7 @@ impl TagDecoder {
             .unwrap_or(src.len());
         self.advance_slice(&src[..whitespace]);
         src.advance(whitespace);
+        self.last = self.position();
 
         // move up to next tag.  although we try to always stop at the next
         // tag, this could be skipping leading text the first time through,
@@ -186,19 +411,29 @@ impl TagDecoder {
         let Some(begin) = src.iter().position(|&b| b == b'<') else {
             return Ok(None);
         };
+
+        // text before the first tag is the header preamble
+        if !std::mem::replace(&mut self.started, true) && begin > 0 {
+            let text = &src[..begin];
+            let text = self.decode_str(text, None)?.trim_end().to_string();
+            self.advance(src, begin);
+            return Ok(Some(ParserTag::Preamble(text)));
+        }
         self.advance(src, begin);
-        let Some(end) = src.iter().position(|&b| b == b'>') else {
+        self.last = self.position();
+        let end = src.iter().position(|&b| b == b'>');
+        let len = end.unwrap_or(src.len()) - 1;
+        self.check(Limit::TagLength, self.limits.max_tag_length, len)?;
+        let Some(end) = end else {
             return Ok(None);
         };
         let tag = &src[1..end];
 
         if tag.eq_ignore_ascii_case(b"eoh") {
-            let n = end + 1;
-            self.advance(src, n);
+            self.end_record(src, end + 1);
             return Ok(Some(ParserTag::Eoh));
         } else if tag.eq_ignore_ascii_case(b"eor") {
-            let n = end + 1;
-            self.advance(src, n);
+            self.end_record(src, end + 1);
             return Ok(Some(ParserTag::Eor));
         } else if tag.eq_ignore_ascii_case(b"app_lotw_eof") {
             // ignore rest regardless of eof handling mode
@@ -210,30 +445,43 @@ impl TagDecoder {
         let Some((name, value, end)) = self.parse_value(src, end, tag)? else {
             return Ok(None);
         };
-        let tag = ParserTag::Field(Field::new(name, value));
+        let field = Field::new(name.into_owned(), value);
+        self.check(Limit::Fields, self.limits.max_fields, self.fields + 1)?;
+        let n = self.consumed + end - self.record_start;
+        self.check(Limit::RecordBytes, self.limits.max_record_bytes, n)?;
+        self.fields += 1;
         self.advance(src, end);
+        let span = Span {
+            start: self.last,
+            end: self.position(),
+        };
 
-        Ok(Some(tag))
+        Ok(Some(ParserTag::Field(field.with_span(span))))
     }
 
     fn decode(
         &mut self, src: &mut BytesMut, eof: bool,
     ) -> Result<Option<Tag>, Error> {
         let res = self.decode_inner(src)?;
+        if res.is_none() && !src.is_empty() {
+            self.check_buffered(src)?;
+        }
         let tag = match (res, eof, src.is_empty()) {
             (Some(tag), _, _) => tag, // return tag we got
             (None, false, _) => return Ok(None), // await more data
             (None, true, true) => return Ok(None), // at eof, nothing left
             (None, true, false) => {
-                // at eof and eof handling was requested
-                return Err(Error::InvalidFormat {
-                    message: Cow::Borrowed("partial data at end of stream"),
-                    position: self.position(),
-                });
+                // at eof and eof handling was requested.  the rest of the
+                // data is consumed so that a lenient stream can end.
+                let err = self.invalid(InvalidFormatKind::PartialData);
+                let n = src.len();
+                self.advance(src, n);
+                return Err(err);
             }
         };
         let tag = match tag {
             ParserTag::Field(field) => Some(Tag::Field(field)),
+            ParserTag::Preamble(text) => Some(Tag::Preamble(text)),
             ParserTag::Eoh => Some(Tag::Eoh),
             ParserTag::Eor => Some(Tag::Eor),
             ParserTag::Eof => None,
@@ -242,6 +490,187 @@ impl TagDecoder {
     }
 }
 
+/// Decoder that returns invalid tags as items rather than failing
+///
+/// After an invalid tag, the decoder skips its opening `<` and resumes at
+/// the next `<`, which is the most plausible start of a valid tag.
+pub(crate) struct LenientDecoder(pub(crate) TagDecoder);
+
+impl LenientDecoder {
+    pub(crate) fn decode(
+        &mut self, src: &mut BytesMut, eof: bool,
+    ) -> Result<Option<Result<Tag, Error>>, Error> {
+        match self.0.decode(src, eof) {
+            Ok(tag) => Ok(tag.map(Ok)),
+            Err(e) => {
+                if !src.is_empty() {
+                    self.0.advance(src, 1);
+                }
+                Ok(Some(Err(e)))
+            }
+        }
+    }
+}
+
+impl Decoder for LenientDecoder {
+    type Item = Result<Tag, Error>;
+    type Error = Error;
+
+    fn decode(
+        &mut self, src: &mut BytesMut,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode(src, false)
+    }
+
+    fn decode_eof(
+        &mut self, src: &mut BytesMut,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        let eof = !self.0.ignore_partial;
+        self.decode(src, eof)
+    }
+}
+
+/// Stream of ADIF tags that continues after invalid tags.
+///
+/// Invalid tags are returned as errors, after which the stream
+/// resynchronizes at the next tag.  I/O errors still end the stream.
+pub struct LenientTagStream<R> {
+    inner: FramedRead<R, LenientDecoder>,
+}
+
+impl<R> Stream for LenientTagStream<R>
+where
+    R: AsyncRead + Unpin,
+{
+    type Item = Result<Tag, Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner)
+            .poll_next(cx)
+            .map(|t| t.map(|t| t.and_then(|t| t)))
+    }
+}
+
+/// Replace invalid UTF-8 in text for an error.
+pub(crate) fn lossy(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
+
+/// Parse the length in a tag for the field named `name`.
+pub(crate) fn parse_length(
+    name: &str, len: &[u8],
+) -> Result<usize, InvalidFormatKind> {
+    str::from_utf8(len)
+        .ok()
+        .and_then(|len| len.parse().ok())
+        .ok_or_else(|| InvalidFormatKind::BadLength {
+            name: name.to_string(),
+            length: lossy(len),
+        })
+}
+
+/// Check the type indicator in a tag for the field named `name`.
+pub(crate) fn parse_type<'a>(
+    name: &str, typ: &'a [u8],
+) -> Result<&'a str, InvalidFormatKind> {
+    str::from_utf8(typ).map_err(|_| InvalidFormatKind::BadTypeEncoding {
+        name: name.to_string(),
+        typ: lossy(typ),
+    })
+}
+
+/// Parse a value as the given type, returning [None] if it is invalid.
+pub(crate) fn parse_as(v: &str, typ: DataType) -> Option<Datum> {
+    let datum = match typ {
+        DataType::Number => Datum::Number(Decimal::from_str(v.trim()).ok()?),
+        DataType::Boolean => Datum::Boolean(match v.trim() {
+            "Y" | "y" => true,
+            "N" | "n" => false,
+            _ => return None,
+        }),
+        DataType::Date => Datum::Date(parse_date(v.trim())?),
+        DataType::Time => Datum::Time(parse_time(v.trim())?),
+        DataType::String => Datum::String(is_string(v).then(|| v.to_string())?),
+        DataType::IntlString => {
+            Datum::IntlString(is_intl_string(v).then(|| v.to_string())?)
+        }
+        DataType::MultilineString => Datum::MultilineString(
+            is_multiline_string(v).then(|| v.to_string())?,
+        ),
+        DataType::IntlMultilineString => {
+            Datum::IntlMultilineString(v.to_string())
+        }
+        DataType::Enumeration => {
+            Datum::Enumeration(is_string(v).then(|| v.to_string())?)
+        }
+        DataType::Location => Datum::Location(Location::parse(v.trim())?),
+    };
+    Some(datum)
+}
+
+/// Parse the value of a field named `name` according to its ADIF type
+/// indicator.
+///
+/// Header fields named `USERDEFn` are declarations, whose type indicator
+/// applies to the declared field rather than to the value itself.  Record
+/// fields so named are ordinary values.
+///
+/// Text with characters its string type does not allow, such as a line
+/// break in a String, is common in real logs and is read as a String.
+pub(crate) fn parse_field_value(
+    name: &str, v: &str, typ: Option<&str>, header: bool,
+) -> Result<Datum, InvalidFormatKind> {
+    let typ = typ.and_then(DataType::from_indicator);
+    if header && UserDef::is_userdef_name(name) {
+        return UserDef::parse(v, typ).map(Datum::from).ok_or_else(|| {
+            InvalidFormatKind::BadUserDef {
+                name: name.to_string(),
+                value: v.to_string(),
+            }
+        });
+    }
+    match typ {
+        Some(typ) => match parse_as(v, typ) {
+            Some(datum) => Ok(datum),
+            None if matches!(
+                typ,
+                DataType::String
+                    | DataType::IntlString
+                    | DataType::MultilineString
+                    | DataType::Enumeration
+            ) =>
+            {
+                Ok(Datum::String(v.to_string()))
+            }
+            None => Err(InvalidFormatKind::BadValue {
+                name: name.to_string(),
+                value: v.to_string(),
+                typ,
+            }),
+        },
+        None => Ok(Datum::String(v.to_string())),
+    }
+}
+
+/// True if the value is a valid ADIF String:  printable ASCII only.
+pub(crate) fn is_string(v: &str) -> bool {
+    v.bytes().all(|b| (b' '..=b'~').contains(&b))
+}
+
+/// True if the value is a valid ADIF IntlString:  no line breaks.
+pub(crate) fn is_intl_string(v: &str) -> bool {
+    !v.contains(['\r', '\n'])
+}
+
+/// True if the value is a valid ADIF MultilineString:  printable ASCII and
+/// line breaks only.
+pub(crate) fn is_multiline_string(v: &str) -> bool {
+    v.bytes()
+        .all(|b| (b' '..=b'~').contains(&b) || b == b'\r' || b == b'\n')
+}
+
 impl Decoder for TagDecoder {
     type Item = Tag;
     type Error = Error;
@@ -268,24 +697,121 @@ pub trait RecordStreamExt: Stream {
     {
         RecordStream {
             stream: self,
-            record: Record::new(),
+            aggregator: Aggregator::default(),
         }
     }
 }
 
 impl<S> RecordStreamExt for S where S: Stream {}
 
+/// Handling of records affected by errors in a lenient stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Discard the affected record
+    Drop,
+    /// Return what remains of the affected record, marked as
+    /// [damaged](Record::is_damaged)
+    Flag,
+}
+
+/// Assembly of tags into records, independent of how tags are read
+#[derive(Debug, Default)]
+pub(crate) struct Aggregator {
+    record: Record,
+    recovery: Option<Recovery>,
+    duplicates: Duplicates,
+    damaged: bool,
+}
+
+impl Aggregator {
+    pub(crate) fn set_recovery(&mut self, recovery: Recovery) {
+        self.recovery = Some(recovery);
+    }
+
+    pub(crate) fn set_duplicates(&mut self, duplicates: Duplicates) {
+        self.duplicates = duplicates;
+    }
+
+    fn make(&mut self, header: bool) -> Option<Result<Record, Error>> {
+        let mut record = std::mem::take(&mut self.record);
+        if std::mem::take(&mut self.damaged) {
+            match self.recovery {
+                Some(Recovery::Drop) => return None,
+                _ => record.damaged = true,
+            }
+        }
+        record.header = header;
+        if !header {
+            // a preamble is only meaningful before a header
+            record.preamble = None;
+        }
+        Some(Ok(record))
+    }
+
+    /// Add a tag, returning a record or error if one is ready.
+    pub(crate) fn push(
+        &mut self, tag: Result<Tag, Error>,
+    ) -> Option<Result<Record, Error>> {
+        let e = match tag {
+            Ok(Tag::Eoh) => return self.make(true),
+            Ok(Tag::Eor) => return self.make(false),
+            Ok(Tag::Preamble(p)) => {
+                self.record.preamble = Some(p.into_boxed_str());
+                return None;
+            }
+            Ok(Tag::Field(f)) => {
+                match self.record.insert_field(f, &self.duplicates) {
+                    Ok(()) => return None,
+                    Err(e) => e,
+                }
+            }
+            Err(e) => e,
+        };
+        // only a stream recovering from errors flags the records they hit
+        if self.recovery.is_some() {
+            self.damaged = true;
+        }
+        Some(Err(e))
+    }
+}
+
 /// Stream that aggregates ADIF tags into complete records.
 pub struct RecordStream<S> {
     stream: S,
-    record: Record,
+    aggregator: Aggregator,
 }
 
 impl<S> RecordStream<S> {
-    fn make(&mut self, header: bool) -> Poll<Option<Result<Record, Error>>> {
-        let mut record = std::mem::take(&mut self.record);
-        record.header = header;
-        Poll::Ready(Some(Ok(record)))
+    /// Continue after errors, handling affected records as specified.
+    ///
+    /// Errors are still returned from the stream, but subsequent records
+    /// continue to be returned as long as the underlying tag stream
+    /// continues, as a [LenientTagStream] does.
+    pub fn with_recovery(mut self, recovery: Recovery) -> Self {
+        self.aggregator.set_recovery(recovery);
+        self
+    }
+
+    /// Handle fields that occur more than once in a record as specified.
+    ///
+    /// By default, a duplicate field is an error.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::{Duplicates, RecordStream};
+    /// use futures::StreamExt;
+    /// let mut s = RecordStream::new(
+    ///     "<notes:3>abc<notes:3>def<eor>".as_bytes(),
+    ///     true,
+    /// )
+    /// .with_duplicates(Duplicates::Concatenate("\n".into()));
+    /// let rec = s.next().await.unwrap().unwrap();
+    /// assert_eq!(rec.get("notes").unwrap().as_str(), "abc\ndef");
+    /// # });
+    /// ```
+    pub fn with_duplicates(mut self, duplicates: Duplicates) -> Self {
+        self.aggregator.set_duplicates(duplicates);
+        self
     }
 }
 
@@ -317,6 +843,70 @@ where
     }
 }
 
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+impl<R> RecordStream<TagStream<crate::compress::Decompress<R>>>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Create a new stream that returns ADIF records from data that may
+    /// be compressed.
+    ///
+    /// The start of the data is read to detect its compression, which
+    /// may return an I/O error.  See [new](RecordStream::new) for the
+    /// meaning of `ignore_partial`.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::RecordStream;
+    /// use futures::StreamExt;
+    /// let data = "<call:4>W1AW<eor>".as_bytes();
+    /// let mut s = RecordStream::new_decompressed(data, true).await.unwrap();
+    /// let rec = s.next().await.unwrap().unwrap();
+    /// assert_eq!(rec.get("call").unwrap().as_str(), "W1AW");
+    /// # });
+    /// ```
+    pub async fn new_decompressed(
+        reader: R, ignore_partial: bool,
+    ) -> Result<Self, Error> {
+        let reader = crate::compress::Decompress::new(reader).await?;
+        Ok(Self::new(reader, ignore_partial))
+    }
+}
+
+impl<R> RecordStream<LenientTagStream<R>>
+where
+    R: AsyncRead,
+{
+    /// Create a new stream that returns ADIF records from damaged input.
+    ///
+    /// Each error is returned from the stream, after which parsing
+    /// resumes at the next tag.  Records containing errors are dropped or
+    /// flagged according to `recovery`.  Incomplete trailing data is
+    /// reported as an error.
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use difa::{Recovery, RecordStream};
+    /// use futures::StreamExt;
+    /// let mut r = RecordStream::new_lenient(
+    ///     "<call:4>W1AW<eor><call:x>AB9BH<eor><call:6>N0CALL<eor>".as_bytes(),
+    ///     Recovery::Drop,
+    /// );
+    /// let rec = r.next().await.unwrap().unwrap();
+    /// assert_eq!(rec.get("call").unwrap().as_str(), "W1AW");
+    /// assert!(r.next().await.unwrap().is_err());
+    /// let rec = r.next().await.unwrap().unwrap();
+    /// assert_eq!(rec.get("call").unwrap().as_str(), "N0CALL");
+    /// # });
+    /// ```
+    pub fn new_lenient(reader: R, recovery: Recovery) -> Self {
+        RecordStream {
+            stream: TagDecoder::new(false).lenient_stream_with(reader),
+            aggregator: Aggregator::default(),
+        }
+        .with_recovery(recovery)
+    }
+}
+
 impl<S> Stream for RecordStream<S>
 where
     S: Stream<Item = Result<Tag, Error>> + Unpin,
@@ -328,14 +918,11 @@ where
     ) -> Poll<Option<Self::Item>> {
         loop {
             match Pin::new(&mut self.stream).poll_next(cx) {
-                Poll::Ready(Some(Ok(Tag::Eoh))) => return self.make(true),
-                Poll::Ready(Some(Ok(Tag::Eor))) => return self.make(false),
-                Poll::Ready(Some(Ok(Tag::Field(f)))) => {
-                    if let Err(e) = self.record.insert(f.name, f.value) {
-                        return Poll::Ready(Some(Err(e)));
+                Poll::Ready(Some(tag)) => {
+                    if let Some(r) = self.aggregator.push(tag) {
+                        return Poll::Ready(Some(r));
                     }
                 }
-                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
                 Poll::Ready(None) => return Poll::Ready(None),
                 Poll::Pending => return Poll::Pending,
             }
@@ -8,7 +8,10 @@ use tokio_util::codec::Decoder;
 
 use super::*;
 use crate::test::helpers::*;
-use crate::{Datum, Error, Field, Record, Tag};
+use crate::{
+    DataType, Datum, Duplicates, Encoding, Error, Field, InvalidFormatKind,
+    LengthUnit, Position, Record, Tag, UserDefValues,
+};
 
 fn tags(s: &str) -> TagStream<&[u8]> {
     TagDecoder::new_stream(s.as_bytes(), true)
@@ -33,6 +36,16 @@ where
     };
 }
 
+async fn next_preamble<R>(f: &mut TagStream<R>) -> String
+where
+    R: AsyncRead + Unpin,
+{
+    let Tag::Preamble(text) = f.next().await.unwrap().unwrap() else {
+        panic!("expected preamble");
+    };
+    text
+}
+
 async fn no_tags<R>(f: &mut TagStream<R>)
 where
     R: AsyncRead + Unpin,
@@ -88,6 +101,7 @@ async fn next_eoh_panics_on_eor() {
 async fn header() {
     let mut f = tags("Foo Bar Baz <adifver:5>3.1.1 <eoh>");
 
+    assert_eq!(next_preamble(&mut f).await, "Foo Bar Baz");
     let field = next_field(&mut f).await;
     assert_eq!(field.name(), "adifver");
     assert_eq!(field.value().as_str(), "3.1.1");
@@ -95,6 +109,63 @@ async fn header() {
     no_tags(&mut f).await;
 }
 
+#[tokio::test]
+#[should_panic(expected = "expected preamble")]
+async fn next_preamble_panics_on_field() {
+    let mut f = tags("<foo:1>a");
+    next_preamble(&mut f).await;
+}
+
+#[tokio::test]
+async fn preamble() {
+    let s = "\n  ADIF Export from Sample Logbook\r\n  by hand \n\n<a:1>b<eoh>x<c:1>d";
+    let mut f = tags(s);
+    assert_eq!(
+        next_preamble(&mut f).await,
+        "ADIF Export from Sample Logbook\r\n  by hand"
+    );
+    assert_eq!(next_field(&mut f).await, Field::new("a", "b"));
+    next_eoh(&mut f).await;
+    // text between later tags is not a preamble
+    assert_eq!(next_field(&mut f).await, Field::new("c", "d"));
+    no_tags(&mut f).await;
+}
+
+#[tokio::test]
+async fn preamble_records() {
+    let mut f = tags("Sample\n<a:1>b<eoh><c:1>d<eor>").records();
+    let rec = next_record(&mut f, true).await;
+    assert_eq!(rec.preamble(), Some("Sample"));
+    let rec = next_record(&mut f, false).await;
+    assert_eq!(rec.preamble(), None);
+    no_records(&mut f).await;
+
+    // without a header, there is nothing to attach the preamble to
+    let mut f = tags("Sample\n<c:1>d<eor>").records();
+    let rec = next_record(&mut f, false).await;
+    assert_eq!(rec.preamble(), None);
+    no_records(&mut f).await;
+
+    let mut f = tags("<a:1>b<eoh>").records();
+    let rec = next_record(&mut f, true).await;
+    assert_eq!(rec.preamble(), None);
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn preamble_encoding() {
+    let bytes = b"Gr\xFC\xDFe<a:1>b<eoh>";
+    let mut f = encoded_tags(bytes, Encoding::Latin1, LengthUnit::Bytes, false);
+    assert_eq!(next_preamble(&mut f).await, "Gr\u{fc}\u{df}e");
+
+    let mut f = encoded_tags(bytes, Encoding::Utf8, LengthUnit::Bytes, false);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(
+        err,
+        invalid_format(bad_encoding(None, "Gr\u{fffd}\u{fffd}e"), 1, 1, 0)
+    );
+}
+
 #[tokio::test]
 async fn typed() {
     let mut f = tags("<foo:3:n>123");
@@ -154,6 +225,14 @@ async fn typed() {
     );
     no_tags(&mut f).await;
 
+    let mut f = tags("<time_on:4:t>2300");
+    let field = next_field(&mut f).await;
+    assert_eq!(
+        field.value(),
+        &Datum::Time(NaiveTime::from_hms_opt(23, 0, 0).unwrap())
+    );
+    no_tags(&mut f).await;
+
     let mut f = tags("<time_on:6:T>230000");
     let field = next_field(&mut f).await;
     assert_eq!(field.name(), "time_on");
@@ -279,6 +358,7 @@ async fn try_trickle_tags(chunk: usize) {
         TrickleReader::new("Foo <bar:3>baz <qux:5:n>12345 <eoh>   ", chunk);
     let mut f = TagDecoder::new_stream(reader, false);
 
+    assert_eq!(next_preamble(&mut f).await, "Foo");
     let field = next_field(&mut f).await;
     assert_eq!(field.name(), "bar");
     assert_eq!(field.value().as_str(), "baz");
@@ -306,7 +386,10 @@ async fn trickle_invalid() {
     let reader = TrickleReader::new("<foo:3:n>abc", 1);
     let mut f = TagDecoder::new_stream(reader, true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("foo:3:n", 1, 1, 0));
+    assert_eq!(
+        err,
+        invalid_format(bad_value("foo", "abc", DataType::Number), 1, 1, 0)
+    );
 }
 
 #[tokio::test]
@@ -318,7 +401,8 @@ async fn trickle_duplicate() {
     let err = f.next().await.unwrap().unwrap_err();
     let mut r = Record::new();
     r.insert("foo", "abc").unwrap();
-    assert_eq!(err, duplicate_key("foo", r));
+    let at = span((1, 11, 10), (1, 21, 20));
+    assert_eq!(err, duplicate_key_at("foo", r, at));
 }
 
 #[tokio::test]
@@ -336,10 +420,7 @@ async fn trickle_records() {
     let rec = next_record(&mut f, false).await;
     assert_eq!(rec.get("foo").unwrap().as_str(), "defg");
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(
-        err,
-        invalid_format("partial data at end of stream", 1, 45, 44)
-    );
+    assert_eq!(err, partial_data(1, 45, 44));
     no_records(&mut f).await;
 }
 
@@ -398,14 +479,14 @@ async fn record_stream() {
 async fn no_length() {
     let mut f = RecordStream::new("<call>W1AW<eor>".as_bytes(), true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("call", 1, 1, 0));
+    assert_eq!(err, invalid_format(bad_tag("call"), 1, 1, 0));
 }
 
 #[tokio::test]
 async fn too_many() {
     let mut f = RecordStream::new("<call:4:s:xxx>W1AW<eor>".as_bytes(), true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("call:4:s:xxx", 1, 1, 0));
+    assert_eq!(err, invalid_format(bad_tag("call:4:s:xxx"), 1, 1, 0));
 }
 
 #[tokio::test]
@@ -503,28 +584,153 @@ async fn boolean_n() {
 async fn invalid_boolean() {
     let mut f = RecordStream::new("<qsl:1:b>X<eor>".as_bytes(), true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("qsl:1:b", 1, 1, 0));
+    assert_eq!(
+        err,
+        invalid_format(bad_value("qsl", "X", DataType::Boolean), 1, 1, 0)
+    );
 }
 
 #[tokio::test]
 async fn invalid_number() {
     let mut f = tags("<foo:3:n>abc");
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("foo:3:n", 1, 1, 0));
+    assert_eq!(
+        err,
+        invalid_format(bad_value("foo", "abc", DataType::Number), 1, 1, 0)
+    );
 }
 
 #[tokio::test]
 async fn invalid_date() {
     let mut f = tags("<qso_date:8:d>notadate");
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("qso_date:8:d", 1, 1, 0));
+    assert_eq!(
+        err,
+        invalid_format(
+            bad_value("qso_date", "notadate", DataType::Date),
+            1,
+            1,
+            0
+        )
+    );
 }
 
 #[tokio::test]
 async fn invalid_time() {
     let mut f = tags("<time_on:6:t>notime");
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("time_on:6:t", 1, 1, 0));
+    assert_eq!(
+        err,
+        invalid_format(bad_value("time_on", "notime", DataType::Time), 1, 1, 0)
+    );
+}
+
+async fn typed_value(s: &str) -> Datum {
+    let mut f = tags(s);
+    let field = next_field(&mut f).await;
+    no_tags(&mut f).await;
+    field.value().clone()
+}
+
+#[tokio::test]
+async fn string_types() {
+    let s = |s: &str| s.to_string();
+    assert_eq!(typed_value("<a:3:s>a b").await, Datum::String(s("a b")));
+    assert_eq!(typed_value("<a:3:S>a~b").await, Datum::String(s("a~b")));
+    let d = typed_value("<a:5:i>J\u{f6}rg").await;
+    assert_eq!(d, Datum::IntlString(s("J\u{f6}rg")));
+    let d = typed_value("<a:4:M>a\r\nb").await;
+    assert_eq!(d, Datum::MultilineString(s("a\r\nb")));
+    let d = typed_value("<a:6:g>\u{f6}\r\n\u{f6}").await;
+    assert_eq!(d, Datum::IntlMultilineString(s("\u{f6}\r\n\u{f6}")));
+    let d = typed_value("<a:3:E>SSB").await;
+    assert_eq!(d, Datum::Enumeration(s("SSB")));
+    let d = typed_value("<a:3:x>abc").await;
+    assert_eq!(d, Datum::String(s("abc")));
+}
+
+#[tokio::test]
+async fn location() {
+    let d = typed_value("<lat:11:l>N042 21.123").await;
+    let loc = Location::parse("N042 21.123").unwrap();
+    assert_eq!(d, Datum::Location(loc));
+    let d = typed_value("<lon:13:L> w087 54.355 ").await;
+    let loc = Location::parse("W087 54.355").unwrap();
+    assert_eq!(d, Datum::Location(loc));
+}
+
+#[tokio::test]
+async fn invalid_string_types() {
+    let cases = [
+        "<a:2:s>\u{f6}",
+        "<a:3:s>a\nb",
+        "<a:3:i>a\rb",
+        "<a:3:m>\u{f6}\n",
+        "<a:3:m>a\tb",
+        "<a:2:e>\u{f6}",
+    ];
+    for s in cases {
+        let value = &s[s.find('>').unwrap() + 1..];
+        assert_eq!(typed_value(s).await, Datum::String(value.into()), "{s}");
+    }
+    let d = typed_value("<notes:5:s>a\nb\nc").await;
+    assert_eq!(d, Datum::String("a\nb\nc".into()));
+    let d = typed_value("<name:7:s>M\u{fc}ller").await;
+    assert_eq!(d, Datum::String("M\u{fc}ller".into()));
+
+    let cases = [
+        ("<a:11:l>N091 00.000", "N091 00.000", DataType::Location),
+        ("<a:3:l>N42", "N42", DataType::Location),
+    ];
+    for (s, value, typ) in cases {
+        let mut f = tags(s);
+        let err = f.next().await.unwrap().unwrap_err();
+        let kind = bad_value("a", value, typ);
+        assert_eq!(err, invalid_format(kind, 1, 1, 0), "{s}");
+    }
+}
+
+#[tokio::test]
+async fn userdef() {
+    let d = typed_value("<USERDEF1:15:N>ShoeSize,{5:20}").await;
+    let range = UserDefValues::Range(5.into(), 20.into());
+    let def = UserDef::new("ShoeSize", Some(DataType::Number));
+    assert_eq!(d, Datum::from(def.with_values(range)));
+
+    let d = typed_value("<userdef2:8>QRP_ARCI").await;
+    assert_eq!(d, Datum::from(UserDef::new("QRP_ARCI", None)));
+
+    // not a declaration, so the value must be a number
+    let mut f = tags("<userdefx:3:n>abc");
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(
+        err,
+        invalid_format(bad_value("userdefx", "abc", DataType::Number), 1, 1, 0)
+    );
+
+    let mut f = tags("<userdef1:6:n>,{1:2}");
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(
+        err,
+        invalid_format(
+            InvalidFormatKind::BadUserDef {
+                name: "userdef1".into(),
+                value: ",{1:2}".into()
+            },
+            1,
+            1,
+            0
+        )
+    );
+
+    // only header fields are declarations
+    let mut f = tags("<eoh><userdef1:3>,{}<userdef2:1:n>7");
+    assert_eq!(f.next().await.unwrap().unwrap(), Tag::Eoh);
+    let field = next_field(&mut f).await;
+    assert_eq!(field.value(), &Datum::String(",{}".into()));
+    let field = next_field(&mut f).await;
+    assert_eq!(field.value(), &Datum::Number(7.into()));
+    no_tags(&mut f).await;
 }
 
 #[tokio::test]
@@ -611,14 +817,14 @@ async fn empty_field() {
 async fn field_missing_length() {
     let mut f = RecordStream::new("<call:><eor>".as_bytes(), true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("call:", 1, 1, 0));
+    assert_eq!(err, invalid_format(bad_length("call", ""), 1, 1, 0));
 }
 
 #[tokio::test]
 async fn field_negative_length() {
     let mut f = RecordStream::new("<call:-1><eor>".as_bytes(), true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("call:-1", 1, 1, 0));
+    assert_eq!(err, invalid_format(bad_length("call", "-1"), 1, 1, 0));
 }
 
 #[tokio::test]
@@ -634,7 +840,10 @@ async fn invalid_utf8_in_field_value() {
     let bytes = b"<foo:2>\xFF\xFE<eor>";
     let mut f = RecordStream::new(bytes as &[u8], true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("foo:2", 1, 1, 0));
+    assert_eq!(
+        err,
+        invalid_format(bad_encoding(Some("foo"), "\u{FFFD}\u{FFFD}"), 1, 1, 0)
+    );
 }
 
 #[tokio::test]
@@ -642,7 +851,10 @@ async fn invalid_utf8_in_tag_name() {
     let bytes = b"<\xFF\xFE:3>val<eor>";
     let mut f = RecordStream::new(bytes as &[u8], true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("\u{FFFD}\u{FFFD}:3", 1, 1, 0));
+    assert_eq!(
+        err,
+        invalid_format(bad_encoding(None, "\u{FFFD}\u{FFFD}"), 1, 1, 0)
+    );
 }
 
 #[tokio::test]
@@ -650,7 +862,7 @@ async fn invalid_utf8_in_tag_len() {
     let bytes = b"<foo:\xFF>val<eor>";
     let mut f = RecordStream::new(bytes as &[u8], true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("foo:\u{FFFD}", 1, 1, 0));
+    assert_eq!(err, invalid_format(bad_length("foo", "\u{FFFD}"), 1, 1, 0));
 }
 
 #[tokio::test]
@@ -658,7 +870,18 @@ async fn invalid_utf8_in_type_spec() {
     let bytes = b"<foo:3:\xFF>val<eor>";
     let mut f = RecordStream::new(bytes as &[u8], true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("foo:3:\u{FFFD}", 1, 1, 0));
+    assert_eq!(
+        err,
+        invalid_format(
+            InvalidFormatKind::BadTypeEncoding {
+                name: "foo".into(),
+                typ: "\u{FFFD}".into()
+            },
+            1,
+            1,
+            0
+        )
+    );
 }
 
 #[tokio::test]
@@ -668,7 +891,24 @@ async fn duplicate_field() {
     let err = f.next().await.unwrap().unwrap_err();
     let mut r = Record::new();
     r.insert("call", "W1AW").unwrap();
-    assert_eq!(err, duplicate_key("call", r));
+    let at = span((1, 13, 12), (1, 26, 25));
+    assert_eq!(err, duplicate_key_at("call", r, at));
+    assert_eq!(
+        err.to_string(),
+        "Duplicate key in record: call (at line 1, column 13 (byte 12))"
+    );
+}
+
+#[tokio::test]
+async fn duplicate_field_not_damaged() {
+    let data = "<a:1:n>1<a:1:n>2<b:1:n>3<eor>";
+    let mut f = RecordStream::new(data.as_bytes(), true);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert!(matches!(err, Error::DuplicateKey { .. }));
+    let rec = next_record(&mut f, false).await;
+    assert!(!rec.is_damaged());
+    assert_eq!(rec.get("b"), Some(&Datum::Number(3.into())));
+    no_records(&mut f).await;
 }
 
 #[tokio::test]
@@ -701,7 +941,15 @@ async fn err_pos_skips_spaces() {
     let bytes = b"<foo:2>aa <bar:2>\xFF\xFE<eor>";
     let mut f = RecordStream::new(bytes as &[u8], true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("bar:2", 1, 11, 10));
+    assert_eq!(
+        err,
+        invalid_format(
+            bad_encoding(Some("bar"), "\u{FFFD}\u{FFFD}"),
+            1,
+            11,
+            10
+        )
+    );
 }
 
 #[tokio::test]
@@ -709,17 +957,15 @@ async fn err_pos_skips_newlines() {
     let bytes = b"<foo:2>aa\n<bar:2>\xFF\xFE<eor>";
     let mut f = RecordStream::new(bytes as &[u8], true);
     let err = f.next().await.unwrap().unwrap_err();
-    assert_eq!(err, invalid_format("bar:2", 2, 1, 10));
+    assert_eq!(
+        err,
+        invalid_format(bad_encoding(Some("bar"), "\u{FFFD}\u{FFFD}"), 2, 1, 10)
+    );
 }
 
 #[tokio::test]
 async fn decode_returns_tag_before_eof() {
-    let mut dec = TagDecoder {
-        ignore_partial: true,
-        consumed: 0,
-        line: 1,
-        column: 1,
-    };
+    let mut dec = TagDecoder::new(true);
     let mut buf = BytesMut::from("<foo:3>bar");
     let tag = Decoder::decode(&mut dec, &mut buf).unwrap();
     assert_eq!(tag, Some(Tag::Field(Field::new("foo", "bar"))));
@@ -732,3 +978,449 @@ async fn err_pos_skips_spaces_at_end() {
     let err = f.next().await.unwrap().unwrap_err();
     assert_eq!(err, partial_data(1, 11, 10));
 }
+
+fn encoded_tags(
+    bytes: &[u8], encoding: Encoding, length: LengthUnit, ignore_partial: bool,
+) -> TagStream<&[u8]> {
+    TagDecoder::new(ignore_partial)
+        .with_encoding(encoding)
+        .with_length_unit(length)
+        .tag_stream_with(bytes)
+}
+
+#[tokio::test]
+async fn latin1() {
+    let bytes = b"<name:6>J\xFCrgen<qth:4>Graz<\xC4:1>\xE9";
+    let mut f = encoded_tags(bytes, Encoding::Latin1, LengthUnit::Bytes, false);
+    assert_eq!(next_field(&mut f).await, Field::new("name", "J\u{fc}rgen"));
+    assert_eq!(next_field(&mut f).await, Field::new("qth", "Graz"));
+    assert_eq!(next_field(&mut f).await, Field::new("\u{c4}", "\u{e9}"));
+    no_tags(&mut f).await;
+}
+
+#[tokio::test]
+async fn windows1252() {
+    let bytes = b"<comment:8>\x93hi\x94 \x80\x81\xE9";
+    let enc = Encoding::Windows1252;
+    let mut f = encoded_tags(bytes, enc, LengthUnit::Chars, false);
+    assert_eq!(
+        next_field(&mut f).await,
+        Field::new("comment", "\u{201c}hi\u{201d} \u{20ac}\u{81}\u{e9}")
+    );
+    no_tags(&mut f).await;
+}
+
+#[tokio::test]
+async fn utf8_chars() {
+    let s = "<name:6>J\u{fc}rgen<qth:3>\u{263a}\u{1f600}x<eor>";
+    let mut f =
+        encoded_tags(s.as_bytes(), Encoding::Utf8, LengthUnit::Chars, false)
+            .records();
+    let rec = next_record(&mut f, false).await;
+    assert_eq!(rec.get("name").unwrap().as_str(), "J\u{fc}rgen");
+    assert_eq!(rec.get("qth").unwrap().as_str(), "\u{263a}\u{1f600}x");
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn utf8_chars_trickle() {
+    let s = "<name:6>J\u{fc}rgen<qth:2>\u{263a}\u{1f600}<eor>";
+    for chunk in 1..=s.len() {
+        let reader = TrickleReader::new(s, chunk);
+        let mut f = TagDecoder::new(false)
+            .with_length_unit(LengthUnit::Chars)
+            .tag_stream_with(reader)
+            .records();
+        let rec = next_record(&mut f, false).await;
+        assert_eq!(rec.get("name").unwrap().as_str(), "J\u{fc}rgen");
+        assert_eq!(rec.get("qth").unwrap().as_str(), "\u{263a}\u{1f600}");
+        no_records(&mut f).await;
+    }
+}
+
+#[tokio::test]
+async fn utf8_chars_invalid() {
+    let bytes = b"<foo:2>\xFF\xFE<eor>";
+    let mut f = encoded_tags(bytes, Encoding::Utf8, LengthUnit::Chars, false);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(
+        err,
+        invalid_format(bad_encoding(Some("foo"), "\u{FFFD}\u{FFFD}"), 1, 1, 0)
+    );
+
+    let bytes = b"<foo:2>\xC3x<eor>";
+    let mut f = encoded_tags(bytes, Encoding::Utf8, LengthUnit::Chars, false);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(
+        err,
+        invalid_format(bad_encoding(Some("foo"), "\u{FFFD}x<"), 1, 1, 0)
+    );
+}
+
+#[tokio::test]
+async fn chars_partial() {
+    for enc in [Encoding::Utf8, Encoding::Latin1] {
+        let bytes = b"<foo:3>ab";
+        let mut f = encoded_tags(bytes, enc, LengthUnit::Chars, false);
+        let err = f.next().await.unwrap().unwrap_err();
+        assert_eq!(err, partial_data(1, 1, 0));
+
+        let mut f = encoded_tags(bytes, enc, LengthUnit::Chars, true);
+        no_tags(&mut f).await;
+    }
+
+    let bytes = "<foo:2>a\u{263a}".as_bytes();
+    let mut f =
+        encoded_tags(&bytes[..9], Encoding::Utf8, LengthUnit::Chars, false);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, partial_data(1, 1, 0));
+}
+
+#[tokio::test]
+async fn typed_latin1() {
+    let bytes = b"<freq:6:n>14.074<qsl_rcvd:1:b>Y";
+    let mut f = encoded_tags(bytes, Encoding::Latin1, LengthUnit::Chars, true);
+    assert_eq!(
+        next_field(&mut f).await,
+        Field::new("freq", Decimal::from_str("14.074").unwrap())
+    );
+    assert_eq!(next_field(&mut f).await, Field::new("qsl_rcvd", true));
+    no_tags(&mut f).await;
+}
+
+fn lenient(s: &str) -> LenientTagStream<&[u8]> {
+    TagDecoder::new(false).lenient_stream_with(s.as_bytes())
+}
+
+#[tokio::test]
+async fn lenient_tags() {
+    let mut f = lenient("<a:1>1 <b:x>2 <c:1:n>x <d:1>\n<e:1>5");
+    assert_eq!(
+        f.next().await.unwrap().unwrap(),
+        Tag::Field(Field::new("a", "1"))
+    );
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, invalid_format(bad_length("b", "x"), 1, 8, 7));
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(
+        err,
+        invalid_format(bad_value("c", "x", DataType::Number), 1, 15, 14)
+    );
+    assert_eq!(
+        f.next().await.unwrap().unwrap(),
+        Tag::Field(Field::new("d", "\n"))
+    );
+    assert_eq!(
+        f.next().await.unwrap().unwrap(),
+        Tag::Field(Field::new("e", "5"))
+    );
+    assert!(f.next().await.is_none());
+}
+
+#[tokio::test]
+async fn lenient_unterminated_tag() {
+    let mut f = lenient("<a:1 <b:1>2");
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, invalid_format(bad_length("a", "1 <b"), 1, 1, 0));
+    assert_eq!(
+        f.next().await.unwrap().unwrap(),
+        Tag::Field(Field::new("b", "2"))
+    );
+    assert!(f.next().await.is_none());
+}
+
+#[tokio::test]
+async fn lenient_partial() {
+    let mut f = lenient("<a:1>1<b:5>2");
+    assert_eq!(
+        f.next().await.unwrap().unwrap(),
+        Tag::Field(Field::new("a", "1"))
+    );
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, partial_data(1, 7, 6));
+    assert!(f.next().await.is_none());
+
+    let mut f = TagDecoder::new(true).lenient_stream_with(&b"<a:1>1<b:5>2"[..]);
+    assert_eq!(
+        f.next().await.unwrap().unwrap(),
+        Tag::Field(Field::new("a", "1"))
+    );
+    assert!(f.next().await.is_none());
+}
+
+#[tokio::test]
+async fn lenient_trickle() {
+    let s = "<a:1>1<b:x>2<c:1>3<eor>";
+    for chunk in 1..=s.len() {
+        let reader = TrickleReader::new(s, chunk);
+        let mut f = TagDecoder::new(false).lenient_stream_with(reader);
+        let tag = f.next().await.unwrap().unwrap();
+        assert_eq!(tag, Tag::Field(Field::new("a", "1")));
+        let err = f.next().await.unwrap().unwrap_err();
+        assert_eq!(err, invalid_format(bad_length("b", "x"), 1, 7, 6));
+        let tag = f.next().await.unwrap().unwrap();
+        assert_eq!(tag, Tag::Field(Field::new("c", "3")));
+        assert_eq!(f.next().await.unwrap().unwrap(), Tag::Eor);
+        assert!(f.next().await.is_none());
+    }
+}
+
+const DAMAGED: &str = "Log<v:1>3<x:y><eoh>\
+    <call:4>W1AW<eor>\
+    <call:5>AB9BH<freq:2:n>xy<mode:3>FT8<eor>\
+    <call:4>N0AA<call:4>N0BB<eor>\
+    <call:4>K1AB<eor>\
+    <call:4>K2AB<freq:9>14";
+
+#[tokio::test]
+async fn recovery_drop() {
+    let mut f = RecordStream::new_lenient(DAMAGED.as_bytes(), Recovery::Drop);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, invalid_format(bad_length("x", "y"), 1, 10, 9));
+    let rec = next_record(&mut f, false).await;
+    assert_eq!(rec.get("call").unwrap().as_str(), "W1AW");
+    assert!(!rec.is_damaged());
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(
+        err,
+        invalid_format(bad_value("freq", "xy", DataType::Number), 1, 50, 49)
+    );
+    let err = f.next().await.unwrap().unwrap_err();
+    let mut dup = Record::new();
+    dup.insert("call", "N0AA").unwrap();
+    let at = span((1, 90, 89), (1, 102, 101));
+    assert_eq!(err, duplicate_key_at("call", dup, at));
+    let rec = next_record(&mut f, false).await;
+    assert_eq!(rec.get("call").unwrap().as_str(), "K1AB");
+    assert!(!rec.is_damaged());
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, partial_data(1, 136, 135));
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn recovery_flag() {
+    let mut f = RecordStream::new_lenient(DAMAGED.as_bytes(), Recovery::Flag);
+    assert!(f.next().await.unwrap().is_err());
+    let rec = next_record(&mut f, true).await;
+    assert!(rec.is_damaged());
+    assert_eq!(rec.preamble(), Some("Log"));
+    assert_eq!(rec.get("v").unwrap().as_str(), "3");
+    let rec = next_record(&mut f, false).await;
+    assert!(!rec.is_damaged());
+    assert!(f.next().await.unwrap().is_err());
+    let rec = next_record(&mut f, false).await;
+    assert!(rec.is_damaged());
+    assert_eq!(rec.get("call").unwrap().as_str(), "AB9BH");
+    assert!(rec.get("freq").is_none());
+    assert_eq!(rec.get("mode").unwrap().as_str(), "FT8");
+    assert!(f.next().await.unwrap().is_err());
+    let rec = next_record(&mut f, false).await;
+    assert!(rec.is_damaged());
+    assert_eq!(rec.get("call").unwrap().as_str(), "N0AA");
+    let rec = next_record(&mut f, false).await;
+    assert!(!rec.is_damaged());
+    assert!(f.next().await.unwrap().is_err());
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn recovery_strict_stream() {
+    // a strict tag stream ends at its first error regardless of policy
+    let mut f = tags("<call:4>W1AW<eor><a:x><call:4>N0AA<eor>")
+        .records()
+        .with_recovery(Recovery::Flag);
+    let rec = next_record(&mut f, false).await;
+    assert!(!rec.is_damaged());
+    assert!(f.next().await.unwrap().is_err());
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn duplicate_policies() {
+    const DUPS: &str = "<call:4>W1AW<note:1>a<note:1>b<eor><call:4>N0AA<eor>";
+    let notes = async |duplicates| {
+        let mut f = RecordStream::new(DUPS.as_bytes(), true)
+            .with_duplicates(duplicates);
+        let rec = next_record(&mut f, false).await;
+        assert_eq!(rec.span(), Some(span((1, 1, 0), (1, 31, 30))));
+        let notes: Vec<_> = rec
+            .get_all("note")
+            .map(|d| d.as_str().into_owned())
+            .collect();
+        assert_eq!(next_record(&mut f, false).await.fields().count(), 1);
+        no_records(&mut f).await;
+        notes
+    };
+    assert_eq!(notes(Duplicates::First).await, ["a"]);
+    assert_eq!(notes(Duplicates::Last).await, ["b"]);
+    assert_eq!(notes(Duplicates::Concatenate("/".into())).await, ["a/b"]);
+    assert_eq!(notes(Duplicates::KeepAll).await, ["a", "b"]);
+
+    let mut f = RecordStream::new(DUPS.as_bytes(), true)
+        .with_duplicates(Duplicates::Error);
+    assert!(matches!(
+        f.next().await.unwrap(),
+        Err(Error::DuplicateKey { .. })
+    ));
+}
+
+fn limited(s: &str, limits: Limits) -> TagStream<&[u8]> {
+    TagDecoder::new(false)
+        .with_limits(limits)
+        .tag_stream_with(s.as_bytes())
+}
+
+#[tokio::test]
+async fn value_length_limit() {
+    let limits = Limits {
+        max_value_length: Some(3),
+        ..Limits::default()
+    };
+    let mut f = limited("<a:3>abc<b:4>defg", limits);
+    assert_eq!(next_field(&mut f).await.value().as_str(), "abc");
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, limit_exceeded(Limit::ValueLength, 3, 1, 9, 8));
+    assert_eq!(
+        err.to_string(),
+        "Input exceeds limit: value length over 3 (at line 1, column 9 \
+         (byte 8))"
+    );
+    no_tags(&mut f).await;
+
+    // the limit is in the unit in which lengths are counted
+    let mut f = TagDecoder::new(false)
+        .with_length_unit(LengthUnit::Chars)
+        .with_limits(limits)
+        .tag_stream_with("<a:3>\u{e9}\u{e9}\u{e9}".as_bytes());
+    assert_eq!(next_field(&mut f).await.value().as_str().len(), 6);
+}
+
+#[tokio::test]
+async fn tag_length_limit() {
+    let limits = Limits {
+        max_tag_length: Some(6),
+        ..Limits::default()
+    };
+    let mut f = limited("<call:4>W1AW<comment:2>hi", limits);
+    assert_eq!(next_field(&mut f).await.value().as_str(), "W1AW");
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, limit_exceeded(Limit::TagLength, 6, 1, 13, 12));
+
+    // an unterminated tag fails once it is too long
+    for chunk in [1, 1024] {
+        let reader = TrickleReader::new("<call:4>W1AW<aaaaaaaaaa", chunk);
+        let mut f = TagDecoder::new(true)
+            .with_limits(limits)
+            .tag_stream_with(reader);
+        assert_eq!(next_field(&mut f).await.value().as_str(), "W1AW");
+        let err = f.next().await.unwrap().unwrap_err();
+        assert_eq!(err, limit_exceeded(Limit::TagLength, 6, 1, 13, 12));
+    }
+}
+
+#[tokio::test]
+async fn fields_limit() {
+    let limits = Limits {
+        max_fields: Some(2),
+        ..Limits::default()
+    };
+    let s = "<a:1>1<b:1>2<eoh><a:1>1<b:1>2<eor><a:1>1<b:1>2<c:1>3<eor>";
+    let mut f = limited(s, limits).records();
+    assert_eq!(next_record(&mut f, true).await.fields().count(), 2);
+    assert_eq!(next_record(&mut f, false).await.fields().count(), 2);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, limit_exceeded(Limit::Fields, 2, 1, 47, 46));
+    assert_eq!(
+        err.to_string(),
+        "Input exceeds limit: number of fields over 2 (at line 1, column 47 \
+         (byte 46))"
+    );
+    no_records(&mut f).await;
+
+    // a lenient stream counts afresh after the end of the record
+    let s = "<a:1>1<b:1>2<c:1>3<d:1>4<eor><e:1>5<eor>";
+    let mut f = TagDecoder::new(false)
+        .with_limits(limits)
+        .lenient_stream_with(s.as_bytes())
+        .records()
+        .with_recovery(Recovery::Flag);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, limit_exceeded(Limit::Fields, 2, 1, 13, 12));
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, limit_exceeded(Limit::Fields, 2, 1, 19, 18));
+    let rec = next_record(&mut f, false).await;
+    assert!(rec.is_damaged());
+    assert_eq!(rec.fields().count(), 2);
+    let rec = next_record(&mut f, false).await;
+    assert!(!rec.is_damaged());
+    assert_eq!(rec.get("e").unwrap().as_str(), "5");
+    no_records(&mut f).await;
+}
+
+#[tokio::test]
+async fn record_bytes_limit() {
+    let limits = Limits {
+        max_record_bytes: Some(15),
+        ..Limits::default()
+    };
+    let s = "<a:1>1<b:1>2<eor>\n<a:1>1<b:1>2<c:1>3<eor>";
+    let mut f = limited(s, limits).records();
+    assert_eq!(next_record(&mut f, false).await.fields().count(), 2);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, limit_exceeded(Limit::RecordBytes, 15, 2, 13, 30));
+    assert_eq!(
+        err.to_string(),
+        "Input exceeds limit: record size over 15 (at line 2, column 13 \
+         (byte 30))"
+    );
+    no_records(&mut f).await;
+
+    // buffered data for an incomplete tag is discarded
+    let s = format!("<a:1>1<eor><b:99999>{}", "x".repeat(100));
+    let mut f = TagDecoder::new(false)
+        .with_limits(limits)
+        .lenient_stream_with(s.as_bytes());
+    assert!(matches!(f.next().await, Some(Ok(Tag::Field(_)))));
+    assert!(matches!(f.next().await, Some(Ok(Tag::Eor))));
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, limit_exceeded(Limit::RecordBytes, 15, 1, 12, 11));
+    assert!(f.next().await.is_none());
+
+    // as is text without any tag
+    let s = "x".repeat(100);
+    let mut f = limited(&s, limits);
+    let err = f.next().await.unwrap().unwrap_err();
+    assert_eq!(err, limit_exceeded(Limit::RecordBytes, 15, 1, 1, 0));
+}
+
+#[tokio::test]
+async fn limits_position() {
+    let limits = Limits {
+        max_record_bytes: Some(15),
+        ..Limits::default()
+    };
+    let position = Position {
+        line: 10,
+        column: 1,
+        byte: 100,
+    };
+    let mut f = TagDecoder::new(false)
+        .with_limits(limits)
+        .with_position(position)
+        .tag_stream_with("<a:1>1<eor>".as_bytes())
+        .records();
+    assert_eq!(next_record(&mut f, false).await.fields().count(), 1);
+}
+
+#[cfg(feature = "follow")]
+#[test]
+fn restart_keeps_limits() {
+    let limits = Limits {
+        max_record_bytes: Some(15),
+        ..Limits::default()
+    };
+    let mut decoder = TagDecoder::new(false).with_limits(limits);
+    decoder.restart();
+    assert_eq!(decoder.limits, limits);
+}
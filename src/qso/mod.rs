@@ -0,0 +1,368 @@
+//! Contacts as structs with typed fields
+//!
+//! A [Record] is a map from names to loosely typed values, which suits
+//! reading and rewriting arbitrary files.  An application that works with
+//! the contacts themselves can instead convert each record to a [Qso],
+//! whose commonly used fields are plain Rust values, and convert it back
+//! to a record to write it out.
+//!
+//! The date and time fields of the start and end of the contact are
+//! combined into single datetimes.  Fields that the struct does not know,
+//! and empty values, are kept in its [extra](Qso::extra) record, so that
+//! converting a record to a [Qso] and back loses nothing.  Values do become
+//! typed, however, so a time written as HHMM is written back as HHMMSS.
+
+use crate::convert;
+use crate::{Datum, Error, FromDatum, Record};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+
+#[cfg(test)]
+mod test;
+
+/// A single contact with typed fields
+///
+/// ```
+/// use difa::{Qso, Record};
+/// let mut record = Record::new();
+/// record.insert("call", "W1AW").unwrap();
+/// record.insert("qso_date", "20240115").unwrap();
+/// record.insert("time_on", "1234").unwrap();
+/// record.insert("freq", "14.074").unwrap();
+/// record.insert("app_n1mm_id", "7").unwrap();
+///
+/// let qso = Qso::try_from(record).unwrap();
+/// assert_eq!(qso.call, "W1AW");
+/// assert_eq!(qso.time_on.unwrap().to_string(), "2024-01-15 12:34:00");
+/// assert_eq!(qso.freq.unwrap().to_string(), "14.074");
+/// assert_eq!(qso.extra.get("app_n1mm_id").unwrap().as_str(), "7");
+///
+/// let record = Record::from(qso);
+/// assert_eq!(record.get("time_on").unwrap().as_str(), "123400");
+/// assert_eq!(record.get("app_n1mm_id").unwrap().as_str(), "7");
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Qso {
+    /// Callsign of the contacted station, `call`
+    pub call: String,
+    /// Start of the contact, from `qso_date` and `time_on`
+    pub time_on: Option<NaiveDateTime>,
+    /// End of the contact, from `qso_date_off` and `time_off`
+    pub time_off: Option<NaiveDateTime>,
+    /// Band on which the logging station transmitted, `band`
+    pub band: Option<String>,
+    /// Band on which the logging station received, `band_rx`
+    pub band_rx: Option<String>,
+    /// Transmitting frequency in MHz, `freq`
+    pub freq: Option<Decimal>,
+    /// Receiving frequency in MHz, `freq_rx`
+    pub freq_rx: Option<Decimal>,
+    /// Mode, `mode`
+    pub mode: Option<String>,
+    /// Submode of the mode, `submode`
+    pub submode: Option<String>,
+    /// Signal report sent, `rst_sent`
+    pub rst_sent: Option<String>,
+    /// Signal report received, `rst_rcvd`
+    pub rst_rcvd: Option<String>,
+    /// Transmitting power in watts, `tx_pwr`
+    pub tx_pwr: Option<Decimal>,
+    /// Propagation mode, `prop_mode`
+    pub prop_mode: Option<String>,
+    /// Name of the satellite used, `sat_name`
+    pub sat_name: Option<String>,
+    /// Contest identifier, `contest_id`
+    pub contest_id: Option<String>,
+    /// Serial number received, `srx`
+    pub srx: Option<u32>,
+    /// Serial number sent, `stx`
+    pub stx: Option<u32>,
+    /// Comment, `comment`
+    pub comment: Option<String>,
+    /// Notes, `notes`
+    pub notes: Option<String>,
+
+    /// Name of the contacted operator, `name`
+    pub name: Option<String>,
+    /// Location of the contacted station, `qth`
+    pub qth: Option<String>,
+    /// Maidenhead locator of the contacted station, `gridsquare`
+    pub gridsquare: Option<String>,
+    /// State or province of the contacted station, `state`
+    pub state: Option<String>,
+    /// County of the contacted station, `cnty`
+    pub cnty: Option<String>,
+    /// DXCC entity name of the contacted station, `country`
+    pub country: Option<String>,
+    /// DXCC entity code of the contacted station, `dxcc`
+    pub dxcc: Option<u32>,
+    /// Continent of the contacted station, `cont`
+    pub cont: Option<String>,
+    /// CQ zone of the contacted station, `cqz`
+    pub cqz: Option<u32>,
+    /// ITU zone of the contacted station, `ituz`
+    pub ituz: Option<u32>,
+
+    /// Callsign used by the logging station, `station_callsign`
+    pub station_callsign: Option<String>,
+    /// Callsign of the logging operator, `operator`
+    pub operator: Option<String>,
+    /// Maidenhead locator of the logging station, `my_gridsquare`
+    pub my_gridsquare: Option<String>,
+    /// City of the logging station, `my_city`
+    pub my_city: Option<String>,
+    /// State or province of the logging station, `my_state`
+    pub my_state: Option<String>,
+    /// County of the logging station, `my_cnty`
+    pub my_cnty: Option<String>,
+    /// DXCC entity name of the logging station, `my_country`
+    pub my_country: Option<String>,
+    /// DXCC entity code of the logging station, `my_dxcc`
+    pub my_dxcc: Option<u32>,
+    /// CQ zone of the logging station, `my_cq_zone`
+    pub my_cq_zone: Option<u32>,
+    /// ITU zone of the logging station, `my_itu_zone`
+    pub my_itu_zone: Option<u32>,
+    /// Radio of the logging station, `my_rig`
+    pub my_rig: Option<String>,
+    /// Antenna of the logging station, `my_antenna`
+    pub my_antenna: Option<String>,
+
+    /// Paper QSL sent status, `qsl_sent`
+    pub qsl_sent: Option<String>,
+    /// Paper QSL received status, `qsl_rcvd`
+    pub qsl_rcvd: Option<String>,
+    /// Date paper QSL sent, `qslsdate`
+    pub qslsdate: Option<NaiveDate>,
+    /// Date paper QSL received, `qslrdate`
+    pub qslrdate: Option<NaiveDate>,
+    /// Means by which a paper QSL is sent or received, `qsl_via`
+    pub qsl_via: Option<String>,
+    /// LoTW QSL sent status, `lotw_qsl_sent`
+    pub lotw_qsl_sent: Option<String>,
+    /// LoTW QSL received status, `lotw_qsl_rcvd`
+    pub lotw_qsl_rcvd: Option<String>,
+    /// Date QSO uploaded to LoTW, `lotw_qslsdate`
+    pub lotw_qslsdate: Option<NaiveDate>,
+    /// Date LoTW QSL received, `lotw_qslrdate`
+    pub lotw_qslrdate: Option<NaiveDate>,
+    /// eQSL sent status, `eqsl_qsl_sent`
+    pub eqsl_qsl_sent: Option<String>,
+    /// eQSL received status, `eqsl_qsl_rcvd`
+    pub eqsl_qsl_rcvd: Option<String>,
+    /// Date eQSL sent, `eqsl_qslsdate`
+    pub eqsl_qslsdate: Option<NaiveDate>,
+    /// Date eQSL received, `eqsl_qslrdate`
+    pub eqsl_qslrdate: Option<NaiveDate>,
+
+    /// All other fields of the record, along with any fields above that
+    /// were empty or whose date or time lacked its counterpart, and any
+    /// later occurrences of fields kept with
+    /// [KeepAll](crate::Duplicates::KeepAll)
+    ///
+    /// The header flag, preamble and damage of the record are kept here
+    /// too.
+    pub extra: Record,
+}
+
+impl TryFrom<Record> for Qso {
+    type Error = Error;
+
+    /// Convert a record to a contact.
+    ///
+    /// Returns [Error::MissingField] if the record has no callsign and
+    /// [Error::InvalidValue] if a known field cannot be converted to its
+    /// type.
+    fn try_from(record: Record) -> Result<Self, Error> {
+        let mut f = Fields {
+            record: &record,
+            taken: Vec::new(),
+        };
+        let Some(call) = f.get("call")? else {
+            return Err(Error::MissingField {
+                field: "call".to_string(),
+                record: Box::new(record.clone()),
+                span: record.span(),
+            });
+        };
+        let mut qso = Self {
+            call,
+            time_on: f.datetime("qso_date", "time_on")?,
+            time_off: f.datetime("qso_date_off", "time_off")?,
+            band: f.get("band")?,
+            band_rx: f.get("band_rx")?,
+            freq: f.get("freq")?,
+            freq_rx: f.get("freq_rx")?,
+            mode: f.get("mode")?,
+            submode: f.get("submode")?,
+            rst_sent: f.get("rst_sent")?,
+            rst_rcvd: f.get("rst_rcvd")?,
+            tx_pwr: f.get("tx_pwr")?,
+            prop_mode: f.get("prop_mode")?,
+            sat_name: f.get("sat_name")?,
+            contest_id: f.get("contest_id")?,
+            srx: f.get("srx")?,
+            stx: f.get("stx")?,
+            comment: f.get("comment")?,
+            notes: f.get("notes")?,
+
+            name: f.get("name")?,
+            qth: f.get("qth")?,
+            gridsquare: f.get("gridsquare")?,
+            state: f.get("state")?,
+            cnty: f.get("cnty")?,
+            country: f.get("country")?,
+            dxcc: f.get("dxcc")?,
+            cont: f.get("cont")?,
+            cqz: f.get("cqz")?,
+            ituz: f.get("ituz")?,
+
+            station_callsign: f.get("station_callsign")?,
+            operator: f.get("operator")?,
+            my_gridsquare: f.get("my_gridsquare")?,
+            my_city: f.get("my_city")?,
+            my_state: f.get("my_state")?,
+            my_cnty: f.get("my_cnty")?,
+            my_country: f.get("my_country")?,
+            my_dxcc: f.get("my_dxcc")?,
+            my_cq_zone: f.get("my_cq_zone")?,
+            my_itu_zone: f.get("my_itu_zone")?,
+            my_rig: f.get("my_rig")?,
+            my_antenna: f.get("my_antenna")?,
+
+            qsl_sent: f.get("qsl_sent")?,
+            qsl_rcvd: f.get("qsl_rcvd")?,
+            qslsdate: f.get("qslsdate")?,
+            qslrdate: f.get("qslrdate")?,
+            qsl_via: f.get("qsl_via")?,
+            lotw_qsl_sent: f.get("lotw_qsl_sent")?,
+            lotw_qsl_rcvd: f.get("lotw_qsl_rcvd")?,
+            lotw_qslsdate: f.get("lotw_qslsdate")?,
+            lotw_qslrdate: f.get("lotw_qslrdate")?,
+            eqsl_qsl_sent: f.get("eqsl_qsl_sent")?,
+            eqsl_qsl_rcvd: f.get("eqsl_qsl_rcvd")?,
+            eqsl_qslsdate: f.get("eqsl_qslsdate")?,
+            eqsl_qslrdate: f.get("eqsl_qslrdate")?,
+
+            extra: Record::new(),
+        };
+        let taken = f.taken;
+        qso.extra = convert::remaining(record, &taken);
+        Ok(qso)
+    }
+}
+
+impl From<Qso> for Record {
+    /// Convert a contact back to a record.
+    ///
+    /// Fields of the struct are added to those of its extra record,
+    /// replacing any of the same name.
+    fn from(qso: Qso) -> Self {
+        let mut record = qso.extra;
+        let mut put = |name: &str, value: Option<Datum>| {
+            if let Some(value) = value {
+                convert::put(&mut record, name, value, None);
+            }
+        };
+        put("call", Some(qso.call.into()));
+        let date = |dt: Option<NaiveDateTime>| dt.map(|dt| dt.date().into());
+        let time = |dt: Option<NaiveDateTime>| dt.map(|dt| dt.time().into());
+        put("qso_date", date(qso.time_on));
+        put("time_on", time(qso.time_on));
+        put("qso_date_off", date(qso.time_off));
+        put("time_off", time(qso.time_off));
+        put("band", qso.band.map(Datum::from));
+        put("band_rx", qso.band_rx.map(Datum::from));
+        put("freq", qso.freq.map(Datum::from));
+        put("freq_rx", qso.freq_rx.map(Datum::from));
+        put("mode", qso.mode.map(Datum::from));
+        put("submode", qso.submode.map(Datum::from));
+        put("rst_sent", qso.rst_sent.map(Datum::from));
+        put("rst_rcvd", qso.rst_rcvd.map(Datum::from));
+        put("tx_pwr", qso.tx_pwr.map(Datum::from));
+        put("prop_mode", qso.prop_mode.map(Datum::from));
+        put("sat_name", qso.sat_name.map(Datum::from));
+        put("contest_id", qso.contest_id.map(Datum::from));
+        put("srx", qso.srx.map(Datum::from));
+        put("stx", qso.stx.map(Datum::from));
+        put("comment", qso.comment.map(Datum::from));
+        put("notes", qso.notes.map(Datum::from));
+
+        put("name", qso.name.map(Datum::from));
+        put("qth", qso.qth.map(Datum::from));
+        put("gridsquare", qso.gridsquare.map(Datum::from));
+        put("state", qso.state.map(Datum::from));
+        put("cnty", qso.cnty.map(Datum::from));
+        put("country", qso.country.map(Datum::from));
+        put("dxcc", qso.dxcc.map(Datum::from));
+        put("cont", qso.cont.map(Datum::from));
+        put("cqz", qso.cqz.map(Datum::from));
+        put("ituz", qso.ituz.map(Datum::from));
+
+        put("station_callsign", qso.station_callsign.map(Datum::from));
+        put("operator", qso.operator.map(Datum::from));
+        put("my_gridsquare", qso.my_gridsquare.map(Datum::from));
+        put("my_city", qso.my_city.map(Datum::from));
+        put("my_state", qso.my_state.map(Datum::from));
+        put("my_cnty", qso.my_cnty.map(Datum::from));
+        put("my_country", qso.my_country.map(Datum::from));
+        put("my_dxcc", qso.my_dxcc.map(Datum::from));
+        put("my_cq_zone", qso.my_cq_zone.map(Datum::from));
+        put("my_itu_zone", qso.my_itu_zone.map(Datum::from));
+        put("my_rig", qso.my_rig.map(Datum::from));
+        put("my_antenna", qso.my_antenna.map(Datum::from));
+
+        put("qsl_sent", qso.qsl_sent.map(Datum::from));
+        put("qsl_rcvd", qso.qsl_rcvd.map(Datum::from));
+        put("qslsdate", qso.qslsdate.map(Datum::from));
+        put("qslrdate", qso.qslrdate.map(Datum::from));
+        put("qsl_via", qso.qsl_via.map(Datum::from));
+        put("lotw_qsl_sent", qso.lotw_qsl_sent.map(Datum::from));
+        put("lotw_qsl_rcvd", qso.lotw_qsl_rcvd.map(Datum::from));
+        put("lotw_qslsdate", qso.lotw_qslsdate.map(Datum::from));
+        put("lotw_qslrdate", qso.lotw_qslrdate.map(Datum::from));
+        put("eqsl_qsl_sent", qso.eqsl_qsl_sent.map(Datum::from));
+        put("eqsl_qsl_rcvd", qso.eqsl_qsl_rcvd.map(Datum::from));
+        put("eqsl_qslsdate", qso.eqsl_qslsdate.map(Datum::from));
+        put("eqsl_qslrdate", qso.eqsl_qslrdate.map(Datum::from));
+        record
+    }
+}
+
+/// Known fields read from a record, whose names are noted so that the
+/// rest can be kept
+struct Fields<'a> {
+    record: &'a Record,
+    taken: Vec<&'static str>,
+}
+
+impl Fields<'_> {
+    /// Convert a field, treating an empty value as absent.
+    fn get<T>(&mut self, name: &'static str) -> Result<Option<T>, Error>
+    where
+        T: FromDatum,
+    {
+        let value = convert::optional(self.record, name, None)?;
+        if value.is_some() {
+            self.taken.push(name);
+        }
+        Ok(value)
+    }
+
+    /// Combine a date and a time, if both are present.
+    fn datetime(
+        &mut self, date: &'static str, time: &'static str,
+    ) -> Result<Option<NaiveDateTime>, Error> {
+        let present = |name| {
+            self.record
+                .get(name)
+                .is_some_and(|d| !d.as_str().trim().is_empty())
+        };
+        if !present(date) || !present(time) {
+            return Ok(None);
+        }
+        let d = self.get(date)?;
+        let t = self.get(time)?;
+        Ok(Option::zip(d, t).map(|(d, t)| NaiveDateTime::new(d, t)))
+    }
+}
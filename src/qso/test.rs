@@ -0,0 +1,214 @@
+use chrono::{NaiveDate, NaiveTime};
+use futures::StreamExt;
+
+use super::*;
+use crate::test::helpers::*;
+use crate::{Duplicates, RecordStream};
+
+async fn parse(data: &str) -> Record {
+    let mut s = RecordStream::new(data.as_bytes(), true);
+    s.next().await.unwrap().unwrap()
+}
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn time(hour: u32, min: u32, sec: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, min, sec).unwrap()
+}
+
+#[tokio::test]
+async fn from_record() {
+    let rec = parse(
+        "<CALL:4>W1AW<qso_date:8>20240115<time_on:4>2359\
+         <qso_date_off:8>20240116<time_off:6>000130<band:3>20m\
+         <freq:6>14.074<mode:4>MFSK<submode:3>FT4<rst_sent:3>-10\
+         <dxcc:3>291<cqz:1>5<gridsquare:4>FN31<my_gridsquare:6>EM10dg\
+         <station_callsign:4>N0AA<qsl_rcvd:1>Y<qslrdate:8>20240201\
+         <lotw_qsl_sent:1>N<tx_pwr:3>100<app_n1mm_id:1>7<eor>",
+    )
+    .await;
+    let qso = Qso::try_from(rec).unwrap();
+    assert_eq!(qso.call, "W1AW");
+    assert_eq!(
+        qso.time_on,
+        Some(date(2024, 1, 15).and_time(time(23, 59, 0)))
+    );
+    assert_eq!(
+        qso.time_off,
+        Some(date(2024, 1, 16).and_time(time(0, 1, 30)))
+    );
+    assert_eq!(qso.band.as_deref(), Some("20m"));
+    assert_eq!(qso.freq, Some(Decimal::new(14074, 3)));
+    assert_eq!(qso.mode.as_deref(), Some("MFSK"));
+    assert_eq!(qso.submode.as_deref(), Some("FT4"));
+    assert_eq!(qso.rst_sent.as_deref(), Some("-10"));
+    assert_eq!(qso.rst_rcvd, None);
+    assert_eq!(qso.dxcc, Some(291));
+    assert_eq!(qso.cqz, Some(5));
+    assert_eq!(qso.gridsquare.as_deref(), Some("FN31"));
+    assert_eq!(qso.my_gridsquare.as_deref(), Some("EM10dg"));
+    assert_eq!(qso.station_callsign.as_deref(), Some("N0AA"));
+    assert_eq!(qso.qsl_rcvd.as_deref(), Some("Y"));
+    assert_eq!(qso.qslrdate, Some(date(2024, 2, 1)));
+    assert_eq!(qso.lotw_qsl_sent.as_deref(), Some("N"));
+    assert_eq!(qso.tx_pwr, Some(Decimal::from(100)));
+    let extra: Vec<_> = qso.extra.fields().map(|(k, _)| k).collect();
+    assert_eq!(extra, ["app_n1mm_id"]);
+}
+
+#[tokio::test]
+async fn kept_as_extra() {
+    let rec = parse(
+        "<call:4>W1AW<qso_date:8>20240115<time_off:4>1200<band:0>\
+         <freq:1> <comment:0><eor>",
+    )
+    .await;
+    let qso = Qso::try_from(rec.clone()).unwrap();
+    assert_eq!(qso.time_on, None);
+    assert_eq!(qso.time_off, None);
+    assert_eq!(qso.band, None);
+    assert_eq!(qso.freq, None);
+    assert_eq!(qso.comment, None);
+    let extra: Vec<_> = qso.extra.fields().map(|(k, _)| k).collect();
+    assert_eq!(extra, ["qso_date", "time_off", "band", "freq", "comment"]);
+    assert_eq!(qso.extra.field_span("band"), rec.field_span("band"));
+    assert_eq!(Record::from(qso), rec);
+}
+
+#[test]
+fn round_trip() {
+    let mut rec = Record::new();
+    rec.insert("call", "W1AW").unwrap();
+    rec.insert("qso_date", date(2024, 1, 15)).unwrap();
+    rec.insert("time_on", time(12, 34, 56)).unwrap();
+    rec.insert("freq", Decimal::new(7074, 3)).unwrap();
+    rec.insert("srx", Decimal::from(42)).unwrap();
+    rec.insert("eqsl_qslsdate", date(2024, 3, 1)).unwrap();
+    rec.insert("my_rig", "IC-7300").unwrap();
+    rec.insert("sig", "POTA").unwrap();
+    rec.insert("userfield", true).unwrap();
+    let qso = Qso::try_from(rec.clone()).unwrap();
+    assert_eq!(qso.srx, Some(42));
+    assert_eq!(qso.eqsl_qslsdate, Some(date(2024, 3, 1)));
+    assert_eq!(Record::from(qso.clone()), rec);
+    assert_eq!(Qso::try_from(Record::from(qso.clone())).unwrap(), qso);
+}
+
+#[test]
+fn keep_all() {
+    let mut rec = Record::new();
+    rec.insert("call", "W1AW").unwrap();
+    rec.insert("sig", "POTA").unwrap();
+    rec.insert_with("sig", "SOTA", Duplicates::KeepAll).unwrap();
+    rec.insert_with("call", "W1AW/P", Duplicates::KeepAll)
+        .unwrap();
+    let qso = Qso::try_from(rec.clone()).unwrap();
+    assert_eq!(qso.call, "W1AW");
+    let calls: Vec<_> = qso.extra.get_all("call").map(|d| d.as_str()).collect();
+    assert_eq!(calls, ["W1AW/P"]);
+    let sigs: Vec<_> = qso.extra.get_all("sig").map(|d| d.as_str()).collect();
+    assert_eq!(sigs, ["POTA", "SOTA"]);
+    assert_eq!(Record::from(qso), rec);
+}
+
+#[test]
+fn to_record() {
+    let qso = Qso {
+        call: "W1AW".into(),
+        time_on: Some(date(2024, 1, 15).and_time(time(12, 0, 0))),
+        band: Some("40m".into()),
+        my_dxcc: Some(291),
+        ..Default::default()
+    };
+    let rec = Record::from(qso);
+    let fields: Vec<_> = rec
+        .fields()
+        .map(|(k, v)| (k, v.as_str().into_owned()))
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            ("call", "W1AW".into()),
+            ("qso_date", "20240115".into()),
+            ("time_on", "120000".into()),
+            ("band", "40m".into()),
+            ("my_dxcc", "291".into()),
+        ]
+    );
+}
+
+#[test]
+fn fields_replace_extra() {
+    let mut extra = Record::new();
+    extra.insert("BAND", "20m").unwrap();
+    extra.insert("state", "CT").unwrap();
+    let qso = Qso {
+        call: "W1AW".into(),
+        band: Some("40m".into()),
+        extra,
+        ..Default::default()
+    };
+    let rec = Record::from(qso);
+    assert_eq!(rec.get("band").unwrap().as_str(), "40m");
+    assert_eq!(rec.get("state").unwrap().as_str(), "CT");
+    assert_eq!(rec.fields().count(), 3);
+}
+
+#[tokio::test]
+async fn missing_call() {
+    let rec = parse("<band:3>20m<eor>").await;
+    let err = Qso::try_from(rec.clone()).unwrap_err();
+    let expected = Error::MissingField {
+        field: "call".into(),
+        record: Box::new(rec),
+        span: Some(span((1, 1, 0), (1, 12, 11))),
+    };
+    assert_eq!(err, expected);
+
+    let rec = parse("<call:0><eor>").await;
+    assert!(matches!(
+        Qso::try_from(rec),
+        Err(Error::MissingField { .. })
+    ));
+}
+
+#[tokio::test]
+async fn invalid_value() {
+    let cases = [
+        ("<call:4>W1AW<freq:3>abc<eor>", "freq", "abc", (1, 13, 12)),
+        ("<call:4>W1AW<dxcc:2>-1<eor>", "dxcc", "-1", (1, 13, 12)),
+        ("<call:4>W1AW<cqz:3>1.5<eor>", "cqz", "1.5", (1, 13, 12)),
+        (
+            "<call:4>W1AW<qso_date:8>20241315<time_on:4>1200<eor>",
+            "qso_date",
+            "20241315",
+            (1, 13, 12),
+        ),
+        (
+            "<call:4>W1AW<qso_date:8>20240115<time_on:4>2500<eor>",
+            "time_on",
+            "2500",
+            (1, 33, 32),
+        ),
+        ("<qslsdate:1>x<call:4>W1AW<eor>", "qslsdate", "x", (1, 1, 0)),
+    ];
+    for (data, field, value, expected) in cases {
+        let rec = parse(data).await;
+        let Err(Error::InvalidValue {
+            field: f,
+            value: v,
+            record,
+            span,
+        }) = Qso::try_from(rec.clone())
+        else {
+            panic!("expected invalid value for {data}");
+        };
+        assert_eq!((f.as_str(), v.as_str()), (field, value), "{data}");
+        assert_eq!(*record, rec);
+        let span = span.unwrap();
+        let start = (span.start.line, span.start.column, span.start.byte);
+        assert_eq!(start, expected, "{data}");
+    }
+}
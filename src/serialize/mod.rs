@@ -0,0 +1,1481 @@
+//! Serialization with serde
+//!
+//! Records and values can be stored in any format supported by serde.  A
+//! [Datum] is an enum of its type holding its value as ADIF text, e.g.
+//! `{"Number":"14.074"}` in JSON, and a [Record] is a map of its fields.
+//! Only the fields of a record are serialized, not whether it was a header
+//! or where it was read.  A field kept more than once with
+//! [KeepAll](crate::Duplicates::KeepAll) is a repeated key, and a repeated
+//! key is read back that way.
+//!
+//! ADIF itself is also a serde format.  [from_reader] reads the records of
+//! a file as a sequence of structs or maps, and [to_writer] writes them
+//! back out, choosing the type of each field from its Rust type.
+
+use crate::blocking::{RecordReader, RecordWriter};
+use crate::parse::parse_as;
+use crate::{
+    DataType, Datum, Duplicates, Error, OutputTypes, Record, UserDef,
+    parse_datetime,
+};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde::de::value::{SeqDeserializer, StrDeserializer, StringDeserializer};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer,
+    MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+};
+use serde::ser::{self, Impossible, SerializeMap, SerializeSeq};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    forward_to_deserialize_any,
+};
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::io::{Read, Write};
+
+#[cfg(test)]
+mod test;
+
+const DATUM: &str = "Datum";
+const VARIANTS: &[&str] = &[
+    "Boolean",
+    "Number",
+    "Date",
+    "Time",
+    "DateTime",
+    "String",
+    "IntlString",
+    "MultilineString",
+    "IntlMultilineString",
+    "Enumeration",
+    "Location",
+    "UserDef",
+];
+
+impl ser::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        Error::Serde(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        Error::Serde(msg.to_string())
+    }
+
+    // the record is filled in by the deserializer of the record
+    fn missing_field(field: &'static str) -> Self {
+        Error::MissingField {
+            field: field.to_string(),
+            record: Box::default(),
+            span: None,
+        }
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::Serde(format!("cannot serialize {what} as ADIF"))
+}
+
+/// Return the name of the variant of a datum.
+fn variant(datum: &Datum) -> &'static str {
+    match datum {
+        Datum::Boolean(_) => "Boolean",
+        Datum::Number(_) => "Number",
+        Datum::Date(_) => "Date",
+        Datum::Time(_) => "Time",
+        Datum::DateTime(_) => "DateTime",
+        Datum::String(_) => "String",
+        Datum::IntlString(_) => "IntlString",
+        Datum::MultilineString(_) => "MultilineString",
+        Datum::IntlMultilineString(_) => "IntlMultilineString",
+        Datum::Enumeration(_) => "Enumeration",
+        Datum::Location(_) => "Location",
+        Datum::UserDef(_) => "UserDef",
+    }
+}
+
+/// Rebuild a datum from the name of its variant and its text.
+fn from_text(variant: &str, text: String) -> Option<Datum> {
+    let typ = match variant {
+        "Boolean" => DataType::Boolean,
+        "Number" => DataType::Number,
+        "Date" => DataType::Date,
+        "Time" => DataType::Time,
+        "Location" => DataType::Location,
+        "DateTime" => return parse_datetime(&text).map(Datum::DateTime),
+        "String" => return Some(Datum::String(text)),
+        "IntlString" => return Some(Datum::IntlString(text)),
+        "MultilineString" => return Some(Datum::MultilineString(text)),
+        "IntlMultilineString" => {
+            return Some(Datum::IntlMultilineString(text));
+        }
+        "Enumeration" => return Some(Datum::Enumeration(text)),
+        _ => return None,
+    };
+    parse_as(&text, typ)
+}
+
+/// Rebuild a declaration from its type indicator, which may be empty, and
+/// its text.
+fn from_declaration(typ: &str, text: &str) -> Option<Datum> {
+    let typ = match typ {
+        "" => None,
+        typ => Some(DataType::from_indicator(typ)?),
+    };
+    UserDef::parse(text, typ).map(Datum::from)
+}
+
+impl Serialize for Datum {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let name = variant(self);
+        let index = VARIANTS.iter().position(|&v| v == name).unwrap_or(0);
+        let index = index as u32;
+        match self {
+            Datum::UserDef(u) => {
+                let typ = u.data_type().map_or("", |t| t.indicator());
+                let value = (typ, u.to_string());
+                serializer.serialize_newtype_variant(DATUM, index, name, &value)
+            }
+            _ => serializer.serialize_newtype_variant(
+                DATUM,
+                index,
+                name,
+                &*self.as_str(),
+            ),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Datum {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_enum(DATUM, VARIANTS, DatumVisitor)
+    }
+}
+
+/// Name of a variant of [Datum], given by name or by index
+struct Variant(&'static str);
+
+impl<'de> Deserialize<'de> for Variant {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(VariantVisitor)
+    }
+}
+
+struct VariantVisitor;
+
+impl Visitor<'_> for VariantVisitor {
+    type Value = Variant;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("an ADIF data type")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Variant, E>
+    where
+        E: de::Error,
+    {
+        let variant = usize::try_from(v).ok().and_then(|i| VARIANTS.get(i));
+        match variant {
+            Some(v) => Ok(Variant(v)),
+            None => Err(E::invalid_value(Unexpected::Unsigned(v), &self)),
+        }
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Variant, E>
+    where
+        E: de::Error,
+    {
+        match VARIANTS.iter().find(|&&name| name == v) {
+            Some(v) => Ok(Variant(v)),
+            None => Err(E::unknown_variant(v, VARIANTS)),
+        }
+    }
+}
+
+struct DatumVisitor;
+
+impl<'de> Visitor<'de> for DatumVisitor {
+    type Value = Datum;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("an ADIF value")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Datum, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        let (Variant(name), access) = data.variant()?;
+        if name == "UserDef" {
+            let (typ, text): (String, String) = access.newtype_variant()?;
+            return from_declaration(&typ, &text).ok_or_else(|| {
+                de::Error::invalid_value(Unexpected::Str(&text), &self)
+            });
+        }
+        let text: String = access.newtype_variant()?;
+        let invalid = de::Error::invalid_value(Unexpected::Str(&text), &self);
+        from_text(name, text).ok_or(invalid)
+    }
+}
+
+impl Serialize for Record {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let len = self.fields.len() + self.extra.len();
+        let mut map = serializer.serialize_map(Some(len))?;
+        for (name, value) in self.output_fields() {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Record {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(RecordVisitor)
+    }
+}
+
+struct RecordVisitor;
+
+impl<'de> Visitor<'de> for RecordVisitor {
+    type Value = Record;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of ADIF fields")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Record, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut record = Record::new();
+        while let Some((name, value)) = map.next_entry::<String, Datum>()? {
+            // cannot fail when keeping duplicates
+            let _ = record.insert_with(name, value, Duplicates::KeepAll);
+        }
+        Ok(record)
+    }
+}
+
+/// Deserialize an instance of a type from ADIF data.
+///
+/// A sequence, such as a [Vec], is read from all records after the header,
+/// if any, and a struct or map from the only such record.  Struct fields
+/// are matched to ADIF fields case-insensitively, and an empty value is
+/// read as [None].  Values are coerced to the types of the fields as with
+/// the accessors of [Datum].
+///
+/// Returns [Error::MissingField] for a required field that is absent and
+/// [Error::InvalidValue] for a value that cannot be read as its type.
+///
+/// ```
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Contact {
+///     call: String,
+///     freq: f64,
+///     rst_sent: Option<String>,
+/// }
+///
+/// let data = "<adifver:5>3.1.4<eoh>\n\
+///             <CALL:4>W1AW<FREQ:6>14.074<eor>\n\
+///             <CALL:4>N0AA<FREQ:5>7.074<RST_SENT:3>-10<eor>\n";
+/// let contacts: Vec<Contact> = difa::from_reader(data.as_bytes()).unwrap();
+/// assert_eq!(contacts[1].call, "N0AA");
+/// assert_eq!(contacts[1].freq, 7.074);
+/// assert_eq!(contacts[0].rst_sent, None);
+/// ```
+pub fn from_reader<R, T>(reader: R) -> Result<T, Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let records = RecordReader::new(reader, false)
+        .filter(|r| !r.as_ref().is_ok_and(Record::is_header));
+    T::deserialize(Records(records))
+}
+
+/// Serialize an instance of a type as ADIF records.
+///
+/// A sequence is written as one record per element and a struct or map as
+/// a single record.  No header is written.  Each field is written with the
+/// type of its value: booleans as Boolean, numbers as Number, unit enum
+/// variants as Enumeration, and all else as String.  Fields that are
+/// [None] are omitted.
+///
+/// ```
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Contact {
+///     call: &'static str,
+///     freq: f64,
+///     rst_sent: Option<&'static str>,
+/// }
+///
+/// let contacts = [Contact { call: "W1AW", freq: 14.074, rst_sent: None }];
+/// let mut buf = Vec::new();
+/// difa::to_writer(&mut buf, &contacts).unwrap();
+/// assert_eq!(buf, b"<call:4>W1AW<freq:6:n>14.074<eor>\n");
+/// ```
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<(), Error>
+where
+    W: Write,
+    T: Serialize + ?Sized,
+{
+    let mut writer =
+        RecordWriter::with_types(writer, OutputTypes::OnlyNonString);
+    value.serialize(RecordsSerializer {
+        writer: &mut writer,
+    })?;
+    writer.flush()
+}
+
+/// Deserializer of a sequence of records
+struct Records<I>(I);
+
+impl<I> Records<I>
+where
+    I: Iterator<Item = Result<Record, Error>>,
+{
+    fn single(mut self) -> Result<Record, Error> {
+        let record = self
+            .0
+            .next()
+            .ok_or_else(|| Error::Serde("no record in input".into()))??;
+        match self.0.next() {
+            None => Ok(record),
+            Some(Err(e)) => Err(e),
+            Some(Ok(_)) => Err(Error::Serde("more than one record".into())),
+        }
+    }
+}
+
+impl<'de, I> Deserializer<'de> for Records<I>
+where
+    I: Iterator<Item = Result<Record, Error>>,
+{
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let record = self.single()?;
+        RecordDeserializer(&record).deserialize_map(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self, name: &'static str, fields: &'static [&'static str], visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let record = self.single()?;
+        RecordDeserializer(&record).deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self, _name: &'static str, visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct enum identifier
+        ignored_any
+    }
+}
+
+impl<'de, I> SeqAccess<'de> for Records<I>
+where
+    I: Iterator<Item = Result<Record, Error>>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(
+        &mut self, seed: T,
+    ) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.0.next() {
+            None => Ok(None),
+            Some(record) => {
+                seed.deserialize(RecordDeserializer(&record?)).map(Some)
+            }
+        }
+    }
+}
+
+/// Deserializer of a record as a struct or map
+struct RecordDeserializer<'a>(&'a Record);
+
+impl RecordDeserializer<'_> {
+    fn fields(&self, names: &'static [&'static str]) -> Fields<'_> {
+        Fields {
+            record: self.0,
+            fields: self.0.fields().collect::<Vec<_>>().into_iter(),
+            names,
+            value: None,
+        }
+    }
+
+    /// Fill in the record missing a field.
+    fn missing(&self, e: Error) -> Error {
+        match e {
+            Error::MissingField { field, .. } => Error::MissingField {
+                field,
+                record: Box::new(self.0.clone()),
+                span: self.0.span(),
+            },
+            e => e,
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for RecordDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor
+            .visit_map(self.fields(&[]))
+            .map_err(|e| self.missing(e))
+    }
+
+    fn deserialize_struct<V>(
+        self, _name: &'static str, fields: &'static [&'static str], visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor
+            .visit_map(self.fields(fields))
+            .map_err(|e| self.missing(e))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self, _name: &'static str, visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map enum
+        identifier ignored_any
+    }
+}
+
+/// Access to the fields of a record as a map
+struct Fields<'a> {
+    record: &'a Record,
+    fields: std::vec::IntoIter<(&'a str, &'a Datum)>,
+    // names of struct fields, to which keys are matched case-insensitively
+    names: &'static [&'static str],
+    value: Option<(&'a str, &'a Datum)>,
+}
+
+impl<'de> MapAccess<'de> for Fields<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let Some((name, value)) = self.fields.next() else {
+            return Ok(None);
+        };
+        self.value = Some((name, value));
+        let key = self
+            .names
+            .iter()
+            .copied()
+            .find(|n| n.eq_ignore_ascii_case(name))
+            .unwrap_or(name);
+        let key: StrDeserializer<Error> = key.into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let Some((name, datum)) = self.value.take() else {
+            return Err(Error::Serde("value requested before key".into()));
+        };
+        let value = Value {
+            name,
+            datum,
+            record: self.record,
+        };
+        seed.deserialize(value).map_err(|e| match e {
+            Error::Serde(_) => value.invalid(),
+            e => e,
+        })
+    }
+}
+
+/// Deserializer of the value of a single field
+#[derive(Clone, Copy)]
+struct Value<'a> {
+    name: &'a str,
+    datum: &'a Datum,
+    record: &'a Record,
+}
+
+impl Value<'_> {
+    fn invalid(&self) -> Error {
+        Error::InvalidValue {
+            field: self.name.to_string(),
+            value: self.datum.as_str().into_owned(),
+            record: Box::new(self.record.clone()),
+            span: self.record.field_span(self.name),
+        }
+    }
+
+    fn integer<T>(&self) -> Result<T, Error>
+    where
+        T: TryFrom<i64>,
+    {
+        let n = self.datum.as_integer();
+        n.and_then(|n| T::try_from(n).ok())
+            .ok_or_else(|| self.invalid())
+    }
+
+    fn number(&self) -> Result<f64, Error> {
+        let n = self.datum.as_number().and_then(|n| n.to_f64());
+        n.ok_or_else(|| self.invalid())
+    }
+}
+
+impl<'de> Deserializer<'de> for Value<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.datum {
+            Datum::Boolean(b) => visitor.visit_bool(*b),
+            Datum::Number(n) if n.fract().is_zero() => match n.to_i64() {
+                Some(i) => visitor.visit_i64(i),
+                None => visitor.visit_f64(self.number()?),
+            },
+            Datum::Number(_) => visitor.visit_f64(self.number()?),
+            datum => visitor.visit_str(&datum.as_str()),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let b = self.datum.as_bool().ok_or_else(|| self.invalid())?;
+        visitor.visit_bool(b)
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_i8(self.integer()?)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_i16(self.integer()?)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_i32(self.integer()?)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_i64(self.integer()?)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u8(self.integer()?)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u16(self.integer()?)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u32(self.integer()?)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u64(self.integer()?)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_f64(self.number()?)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_f64(self.number()?)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_str(&self.datum.as_str())
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.datum.as_str().into_owned())
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.datum.as_str().trim().is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self, _name: &'static str, visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self, name: &'static str, variants: &'static [&'static str], visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if name == DATUM {
+            return visitor.visit_enum(DatumAccess(self.datum));
+        }
+        // enumerations are case-insensitive like field names
+        let text = self.datum.as_str();
+        let text = match variants.iter().find(|v| v.eq_ignore_ascii_case(&text))
+        {
+            Some(v) => Cow::Borrowed(*v),
+            None => text,
+        };
+        let text: StringDeserializer<Error> =
+            text.into_owned().into_deserializer();
+        visitor.visit_enum(text)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char bytes byte_buf unit unit_struct seq tuple tuple_struct
+        map struct identifier
+    }
+}
+
+/// Access to a [Datum] as the enum that it serializes to
+struct DatumAccess<'a>(&'a Datum);
+
+impl<'de> EnumAccess<'de> for DatumAccess<'_> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let name: StrDeserializer<Error> = variant(self.0).into_deserializer();
+        Ok((seed.deserialize(name)?, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for DatumAccess<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Err(Error::Serde("ADIF value is not a unit variant".into()))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.0 {
+            Datum::UserDef(u) => {
+                let typ = u.data_type().map_or("", |t| t.indicator());
+                let parts = [typ.to_string(), u.to_string()];
+                seed.deserialize(SeqDeserializer::new(parts.into_iter()))
+            }
+            datum => {
+                let text: StringDeserializer<Error> =
+                    datum.as_str().into_owned().into_deserializer();
+                seed.deserialize(text)
+            }
+        }
+    }
+
+    fn tuple_variant<V>(
+        self, _len: usize, _visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Serde("ADIF value is not a tuple variant".into()))
+    }
+
+    fn struct_variant<V>(
+        self, _fields: &'static [&'static str], _visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Serde("ADIF value is not a struct variant".into()))
+    }
+}
+
+// Methods of a serializer that reject their input, with what is rejected.
+macro_rules! reject {
+    (@ok) => { Self::Ok };
+    (@ok $ret:ty) => { $ret };
+    (
+        $(
+            $method:ident $(<$t:ident>)? ($($arg:ty),*) $(-> $ret:ty)?
+                = $what:literal;
+        )*
+    ) => {
+        $(
+            fn $method $(<$t>)? (
+                self, $(_: $arg),*
+            ) -> Result<reject!(@ok $($ret)?), Error>
+            $(where $t: Serialize + ?Sized)?
+            {
+                Err(unsupported($what))
+            }
+        )*
+    };
+}
+
+// Methods of a serializer that widen narrow numbers and unwrap wrappers.
+macro_rules! forward {
+    () => {
+        fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+            self.serialize_i64(v.into())
+        }
+
+        fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+            self.serialize_i64(v.into())
+        }
+
+        fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+            self.serialize_i64(v.into())
+        }
+
+        fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+            self.serialize_u64(v.into())
+        }
+
+        fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+            self.serialize_u64(v.into())
+        }
+
+        fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+            self.serialize_u64(v.into())
+        }
+
+        fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Error>
+        where
+            T: Serialize + ?Sized,
+        {
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_struct<T>(
+            self, _name: &'static str, value: &T,
+        ) -> Result<Self::Ok, Error>
+        where
+            T: Serialize + ?Sized,
+        {
+            value.serialize(self)
+        }
+    };
+}
+
+// Methods of a serializer of records that reject what cannot be a record.
+macro_rules! reject_records {
+    () => {
+        reject! {
+            serialize_bool(bool) = "a boolean as a record";
+            serialize_i64(i64) = "a number as a record";
+            serialize_u64(u64) = "a number as a record";
+            serialize_f32(f32) = "a number as a record";
+            serialize_f64(f64) = "a number as a record";
+            serialize_char(char) = "a string as a record";
+            serialize_str(&str) = "a string as a record";
+            serialize_bytes(&[u8]) = "bytes";
+            serialize_unit_variant(&'static str, u32, &'static str)
+                = "an enum as a record";
+            serialize_newtype_variant<T>(&'static str, u32, &'static str, &T)
+                = "an enum as a record";
+            serialize_tuple_variant(&'static str, u32, &'static str, usize)
+                -> Self::SerializeTupleVariant = "an enum as a record";
+            serialize_struct_variant(&'static str, u32, &'static str, usize)
+                -> Self::SerializeStructVariant = "an enum as a record";
+        }
+    };
+}
+
+/// Serializer of a sequence of records, or a single record
+struct RecordsSerializer<'a, W> {
+    writer: &'a mut RecordWriter<W>,
+}
+
+impl<'a, W> Serializer for RecordsSerializer<'a, W>
+where
+    W: Write,
+{
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = WriteRecord<'a, W>;
+    type SerializeStruct = WriteRecord<'a, W>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    forward!();
+    reject_records!();
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self, _name: &'static str, _len: usize,
+    ) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_map(
+        self, len: Option<usize>,
+    ) -> Result<WriteRecord<'a, W>, Error> {
+        let fields = RecordSerializer.serialize_map(len)?;
+        Ok(WriteRecord {
+            fields,
+            writer: self.writer,
+        })
+    }
+
+    fn serialize_struct(
+        self, name: &'static str, len: usize,
+    ) -> Result<WriteRecord<'a, W>, Error> {
+        let fields = RecordSerializer.serialize_struct(name, len)?;
+        Ok(WriteRecord {
+            fields,
+            writer: self.writer,
+        })
+    }
+}
+
+impl<W> SerializeSeq for RecordsSerializer<'_, W>
+where
+    W: Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let record = value.serialize(RecordSerializer)?;
+        self.writer.write(&record)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<W> ser::SerializeTuple for RecordsSerializer<'_, W>
+where
+    W: Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<W> ser::SerializeTupleStruct for RecordsSerializer<'_, W>
+where
+    W: Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Fields of a record being serialized
+#[derive(Default)]
+struct RecordFields {
+    record: Record,
+    key: Option<String>,
+}
+
+impl RecordFields {
+    fn key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let Some(name) = self.key.take() else {
+            return Err(Error::Serde("value serialized before key".into()));
+        };
+        self.field(&name, value)
+    }
+
+    fn field<T>(&mut self, name: &str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        match value.serialize(DatumSerializer)? {
+            Some(datum) => self.record.insert(name, datum),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A single record to be written once serialized
+struct WriteRecord<'a, W> {
+    fields: RecordFields,
+    writer: &'a mut RecordWriter<W>,
+}
+
+impl<W> SerializeMap for WriteRecord<'_, W>
+where
+    W: Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.fields.serialize_key(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.fields.serialize_value(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        let record = SerializeMap::end(self.fields)?;
+        self.writer.write(&record)
+    }
+}
+
+impl<W> ser::SerializeStruct for WriteRecord<'_, W>
+where
+    W: Write,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(
+        &mut self, key: &'static str, value: &T,
+    ) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeStruct::serialize_field(&mut self.fields, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        let record = ser::SerializeStruct::end(self.fields)?;
+        self.writer.write(&record)
+    }
+}
+
+impl SerializeMap for RecordFields {
+    type Ok = Record;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.value(value)
+    }
+
+    fn end(self) -> Result<Record, Error> {
+        Ok(self.record)
+    }
+}
+
+impl ser::SerializeStruct for RecordFields {
+    type Ok = Record;
+    type Error = Error;
+
+    fn serialize_field<T>(
+        &mut self, key: &'static str, value: &T,
+    ) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Record, Error> {
+        Ok(self.record)
+    }
+}
+
+/// Serializer of a struct or map as a record
+struct RecordSerializer;
+
+impl Serializer for RecordSerializer {
+    type Ok = Record;
+    type Error = Error;
+    type SerializeSeq = Impossible<Record, Error>;
+    type SerializeTuple = Impossible<Record, Error>;
+    type SerializeTupleStruct = Impossible<Record, Error>;
+    type SerializeTupleVariant = Impossible<Record, Error>;
+    type SerializeMap = RecordFields;
+    type SerializeStruct = RecordFields;
+    type SerializeStructVariant = Impossible<Record, Error>;
+
+    forward!();
+    reject_records!();
+    reject! {
+        serialize_none() = "a missing record";
+        serialize_seq(Option<usize>) -> Self::SerializeSeq
+            = "a nested sequence";
+        serialize_tuple(usize) -> Self::SerializeTuple = "a nested sequence";
+        serialize_tuple_struct(&'static str, usize)
+            -> Self::SerializeTupleStruct = "a nested sequence";
+    }
+
+    fn serialize_unit(self) -> Result<Record, Error> {
+        Ok(Record::new())
+    }
+
+    fn serialize_unit_struct(
+        self, _name: &'static str,
+    ) -> Result<Record, Error> {
+        Ok(Record::new())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<RecordFields, Error> {
+        Ok(RecordFields::default())
+    }
+
+    fn serialize_struct(
+        self, _name: &'static str, _len: usize,
+    ) -> Result<RecordFields, Error> {
+        Ok(RecordFields::default())
+    }
+}
+
+/// Serializer of the name of a field
+struct KeySerializer;
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    forward!();
+    reject! {
+        serialize_bool(bool) = "a boolean as a field name";
+        serialize_i64(i64) = "a number as a field name";
+        serialize_u64(u64) = "a number as a field name";
+        serialize_f32(f32) = "a number as a field name";
+        serialize_f64(f64) = "a number as a field name";
+        serialize_bytes(&[u8]) = "bytes as a field name";
+        serialize_none() = "a missing field name";
+        serialize_unit() = "a missing field name";
+        serialize_unit_struct(&'static str) = "a missing field name";
+        serialize_newtype_variant<T>(&'static str, u32, &'static str, &T)
+            = "an enum as a field name";
+        serialize_seq(Option<usize>) -> Self::SerializeSeq
+            = "a sequence as a field name";
+        serialize_tuple(usize) -> Self::SerializeTuple
+            = "a sequence as a field name";
+        serialize_tuple_struct(&'static str, usize)
+            -> Self::SerializeTupleStruct = "a sequence as a field name";
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant = "an enum as a field name";
+        serialize_map(Option<usize>) -> Self::SerializeMap
+            = "a map as a field name";
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct
+            = "a struct as a field name";
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant = "an enum as a field name";
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_unit_variant(
+        self, _name: &'static str, _index: u32, variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_string())
+    }
+}
+
+/// Serializer of the value of a field, which is [None] if the field is to
+/// be omitted
+struct DatumSerializer;
+
+impl DatumSerializer {
+    fn number(n: Option<Decimal>) -> Result<Option<Datum>, Error> {
+        match n {
+            Some(n) => Ok(Some(Datum::Number(n))),
+            None => Err(unsupported("a number out of range")),
+        }
+    }
+}
+
+impl Serializer for DatumSerializer {
+    type Ok = Option<Datum>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Option<Datum>, Error>;
+    type SerializeTuple = Impossible<Option<Datum>, Error>;
+    type SerializeTupleStruct = Impossible<Option<Datum>, Error>;
+    type SerializeTupleVariant = Impossible<Option<Datum>, Error>;
+    type SerializeMap = Impossible<Option<Datum>, Error>;
+    type SerializeStruct = Impossible<Option<Datum>, Error>;
+    type SerializeStructVariant = Impossible<Option<Datum>, Error>;
+
+    forward!();
+    reject! {
+        serialize_bytes(&[u8]) = "bytes";
+        serialize_seq(Option<usize>) -> Self::SerializeSeq
+            = "a sequence as a field";
+        serialize_tuple(usize) -> Self::SerializeTuple
+            = "a sequence as a field";
+        serialize_tuple_struct(&'static str, usize)
+            -> Self::SerializeTupleStruct = "a sequence as a field";
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant = "an enum with data";
+        serialize_map(Option<usize>) -> Self::SerializeMap
+            = "a map as a field";
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct
+            = "a struct as a field";
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant = "an enum with data";
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<Option<Datum>, Error> {
+        Ok(Some(Datum::Boolean(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Option<Datum>, Error> {
+        Ok(Some(Datum::Number(v.into())))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Option<Datum>, Error> {
+        Ok(Some(Datum::Number(v.into())))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Option<Datum>, Error> {
+        Self::number(Decimal::try_from(v).ok())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Option<Datum>, Error> {
+        // via its shortest representation, so that 7.1f32 is not
+        // 7.099999904632568
+        Self::number(v.to_string().parse().ok())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Option<Datum>, Error> {
+        Ok(Some(Datum::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Option<Datum>, Error> {
+        Ok(Some(Datum::String(v.to_string())))
+    }
+
+    fn serialize_none(self) -> Result<Option<Datum>, Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit(self) -> Result<Option<Datum>, Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(
+        self, _name: &'static str,
+    ) -> Result<Option<Datum>, Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self, _name: &'static str, _index: u32, variant: &'static str,
+    ) -> Result<Option<Datum>, Error> {
+        Ok(Some(Datum::Enumeration(variant.to_string())))
+    }
+
+    fn serialize_newtype_variant<T>(
+        self, name: &'static str, _index: u32, variant: &'static str, value: &T,
+    ) -> Result<Option<Datum>, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        // a datum serializes to its text, from which it is rebuilt, and a
+        // declaration to its type indicator and text
+        if name != DATUM {
+            return Err(unsupported("an enum with data"));
+        }
+        let datum = if variant == "UserDef" {
+            value.serialize(DeclarationSerializer)?
+        } else {
+            let Some(Datum::String(text)) = value.serialize(self)? else {
+                return Err(unsupported("an enum with data"));
+            };
+            from_text(variant, text)
+        };
+        datum
+            .map(Some)
+            .ok_or_else(|| Error::Serde(format!("invalid {variant}")))
+    }
+}
+
+/// Serializer of the type indicator and text of a declaration, which is
+/// [None] if they do not make one
+struct DeclarationSerializer;
+
+impl Serializer for DeclarationSerializer {
+    type Ok = Option<Datum>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Option<Datum>, Error>;
+    type SerializeTuple = Declaration;
+    type SerializeTupleStruct = Impossible<Option<Datum>, Error>;
+    type SerializeTupleVariant = Impossible<Option<Datum>, Error>;
+    type SerializeMap = Impossible<Option<Datum>, Error>;
+    type SerializeStruct = Impossible<Option<Datum>, Error>;
+    type SerializeStructVariant = Impossible<Option<Datum>, Error>;
+
+    forward!();
+    reject! {
+        serialize_bool(bool) = "a boolean as a declaration";
+        serialize_i64(i64) = "a number as a declaration";
+        serialize_u64(u64) = "a number as a declaration";
+        serialize_f32(f32) = "a number as a declaration";
+        serialize_f64(f64) = "a number as a declaration";
+        serialize_char(char) = "a string as a declaration";
+        serialize_str(&str) = "a string as a declaration";
+        serialize_bytes(&[u8]) = "bytes";
+        serialize_none() = "a missing declaration";
+        serialize_unit() = "a missing declaration";
+        serialize_unit_struct(&'static str) = "a missing declaration";
+        serialize_unit_variant(&'static str, u32, &'static str)
+            = "an enum as a declaration";
+        serialize_newtype_variant<T>(&'static str, u32, &'static str, &T)
+            = "an enum as a declaration";
+        serialize_seq(Option<usize>) -> Self::SerializeSeq
+            = "a sequence as a declaration";
+        serialize_tuple_struct(&'static str, usize)
+            -> Self::SerializeTupleStruct = "a sequence as a declaration";
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant = "an enum as a declaration";
+        serialize_map(Option<usize>) -> Self::SerializeMap
+            = "a map as a declaration";
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct
+            = "a struct as a declaration";
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant = "an enum as a declaration";
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Declaration, Error> {
+        Ok(Declaration::default())
+    }
+}
+
+/// Type indicator and text of a declaration being serialized
+#[derive(Default)]
+struct Declaration(Vec<String>);
+
+impl ser::SerializeTuple for Declaration {
+    type Ok = Option<Datum>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let Some(Datum::String(text)) = value.serialize(DatumSerializer)?
+        else {
+            return Err(unsupported("a declaration"));
+        };
+        self.0.push(text);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Option<Datum>, Error> {
+        match self.0.as_slice() {
+            [typ, text] => Ok(from_declaration(typ, text)),
+            _ => Err(unsupported("a declaration")),
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use super::*;
+use crate::test::helpers::*;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum QslRcvd {
+    Y,
+    N,
+    R,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Contact {
+    call: String,
+    qso_date: String,
+    freq: f64,
+    dxcc: Option<u16>,
+    lotw: Option<bool>,
+    qsl_rcvd: Option<QslRcvd>,
+}
+
+fn contact(call: &str, freq: f64) -> Contact {
+    Contact {
+        call: call.into(),
+        qso_date: "20240115".into(),
+        freq,
+        dxcc: None,
+        lotw: None,
+        qsl_rcvd: None,
+    }
+}
+
+fn write<T>(value: &T) -> String
+where
+    T: Serialize + ?Sized,
+{
+    let mut buf = Vec::new();
+    to_writer(&mut buf, value).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn datum_json() {
+    let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+    let cases = [
+        (Datum::Boolean(true), r#"{"Boolean":"Y"}"#),
+        (
+            Datum::Number(Decimal::new(14074, 3)),
+            r#"{"Number":"14.074"}"#,
+        ),
+        (Datum::Date(date), r#"{"Date":"20240115"}"#),
+        (
+            Datum::DateTime(date.and_hms_opt(12, 34, 56).unwrap()),
+            r#"{"DateTime":"20240115 123456"}"#,
+        ),
+        (Datum::String("W1AW".into()), r#"{"String":"W1AW"}"#),
+        (
+            Datum::MultilineString("a\r\nb".into()),
+            r#"{"MultilineString":"a\r\nb"}"#,
+        ),
+        (Datum::Enumeration("FT8".into()), r#"{"Enumeration":"FT8"}"#),
+        (
+            parse_as("N041 42.860", DataType::Location).unwrap(),
+            r#"{"Location":"N041 42.860"}"#,
+        ),
+        (
+            Datum::from(
+                UserDef::parse("ShoeSize,{5:20}", Some(DataType::Number))
+                    .unwrap(),
+            ),
+            r#"{"UserDef":["n","ShoeSize,{5:20}"]}"#,
+        ),
+        (
+            Datum::from(UserDef::parse("Epc", None).unwrap()),
+            r#"{"UserDef":["","Epc"]}"#,
+        ),
+    ];
+    for (datum, json) in cases {
+        assert_eq!(serde_json::to_string(&datum).unwrap(), json);
+        let back: Datum = serde_json::from_str(json).unwrap();
+        assert_eq!(back, datum, "{json}");
+    }
+}
+
+#[test]
+fn datum_json_invalid() {
+    let cases = [
+        r#"{"Number":"abc"}"#,
+        r#"{"Date":"20241315"}"#,
+        r#"{"DateTime":"20240115"}"#,
+        r#"{"Boolean":"X"}"#,
+        r#"{"Bogus":"X"}"#,
+        r#"{"UserDef":["Q","Epc"]}"#,
+        r#"{"UserDef":["","x,{}"]}"#,
+    ];
+    for json in cases {
+        assert!(serde_json::from_str::<Datum>(json).is_err(), "{json}");
+    }
+}
+
+#[test]
+fn datum_index() {
+    let de: de::value::U64Deserializer<Error> = 5u64.into_deserializer();
+    assert_eq!(Variant::deserialize(de).unwrap().0, "String");
+    let de: de::value::U64Deserializer<Error> = 12u64.into_deserializer();
+    assert!(Variant::deserialize(de).is_err());
+}
+
+#[test]
+fn record_json() {
+    let mut rec = Record::new();
+    rec.insert("call", "W1AW").unwrap();
+    rec.insert("freq", Datum::Number(Decimal::new(14074, 3)))
+        .unwrap();
+    let json = serde_json::to_string(&rec).unwrap();
+    assert_eq!(
+        json,
+        r#"{"call":{"String":"W1AW"},"freq":{"Number":"14.074"}}"#
+    );
+    let back: Record = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, rec);
+
+    let json = r#"{"call":{"String":"W1AW"},"CALL":{"String":"N0AA"}}"#;
+    let back: Record = serde_json::from_str(json).unwrap();
+    let calls: Vec<_> = back.get_all("call").map(|d| d.as_str()).collect();
+    assert_eq!(calls, ["W1AW", "N0AA"]);
+    let json = r#"{"call":{"String":"W1AW"},"call":{"String":"N0AA"}}"#;
+    assert_eq!(serde_json::to_string(&back).unwrap(), json);
+}
+
+#[test]
+fn write_records() {
+    let mut contacts = vec![contact("W1AW", 14.074), contact("N0AA", 7.0)];
+    contacts[1].dxcc = Some(291);
+    contacts[1].lotw = Some(true);
+    contacts[1].qsl_rcvd = Some(QslRcvd::R);
+    assert_eq!(
+        write(&contacts),
+        "<call:4>W1AW<qso_date:8>20240115<freq:6:n>14.074<eor>\n\
+         <call:4>N0AA<qso_date:8>20240115<freq:1:n>7<dxcc:3:n>291\
+         <lotw:1:b>Y<qsl_rcvd:1:e>R<eor>\n"
+    );
+}
+
+#[test]
+fn write_single() {
+    assert_eq!(
+        write(&contact("W1AW", 14.074)),
+        "<call:4>W1AW<qso_date:8>20240115<freq:6:n>14.074<eor>\n"
+    );
+    let map = BTreeMap::from([("call", "W1AW"), ("band", "20m")]);
+    assert_eq!(write(&map), "<band:3>20m<call:4>W1AW<eor>\n");
+    assert_eq!(write(&Some(vec![map])), "<band:3>20m<call:4>W1AW<eor>\n");
+    assert_eq!(write(&None::<Contact>), "");
+    assert_eq!(write(&()), "");
+}
+
+#[test]
+fn write_types() {
+    #[derive(Serialize)]
+    struct Wrapper(u8);
+
+    #[derive(Serialize)]
+    struct Types {
+        a: i8,
+        b: i16,
+        c: i32,
+        d: i64,
+        e: u32,
+        f: f32,
+        g: char,
+        h: Wrapper,
+        i: (),
+        j: Datum,
+    }
+
+    let t = Types {
+        a: -1,
+        b: 2,
+        c: -3,
+        d: 4,
+        e: 5,
+        f: 7.1,
+        g: 'x',
+        h: Wrapper(6),
+        i: (),
+        j: Datum::Date(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()),
+    };
+    assert_eq!(
+        write(&[t]),
+        "<a:2:n>-1<b:1:n>2<c:2:n>-3<d:1:n>4<e:1:n>5<f:3:n>7.1<g:1>x\
+         <h:1:n>6<j:8:d>20240115<eor>\n"
+    );
+}
+
+#[test]
+fn write_unsupported() {
+    #[derive(Serialize)]
+    enum Shape {
+        Circle(u8),
+    }
+
+    #[derive(Serialize)]
+    struct Nested {
+        inner: Vec<u8>,
+    }
+
+    let mut buf = Vec::new();
+    assert!(matches!(to_writer(&mut buf, "W1AW"), Err(Error::Serde(_))));
+    assert!(matches!(to_writer(&mut buf, &[1, 2]), Err(Error::Serde(_))));
+    assert!(matches!(
+        to_writer(&mut buf, &[Nested { inner: vec![] }]),
+        Err(Error::Serde(_))
+    ));
+    let map = HashMap::from([("shape", Shape::Circle(1))]);
+    assert!(matches!(to_writer(&mut buf, &map), Err(Error::Serde(_))));
+    let map = HashMap::from([(1, "W1AW")]);
+    assert!(matches!(to_writer(&mut buf, &map), Err(Error::Serde(_))));
+    let map = HashMap::from([("freq", f64::NAN)]);
+    assert!(matches!(to_writer(&mut buf, &map), Err(Error::Serde(_))));
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn write_declarations() {
+    let data = "<userdef1:3>Epc<userdef2:19:e>SweaterSize,{S,M,L}<eoh>";
+    let header = RecordReader::new(data.as_bytes(), true).next().unwrap();
+    assert_eq!(
+        write(&[header.unwrap()]),
+        "<userdef1:3>Epc<userdef2:19:e>SweaterSize,{S,M,L}<eor>\n"
+    );
+    let map = BTreeMap::from([(
+        "userdef1",
+        Datum::from(UserDef::parse("Score,{-1.5:2}", None).unwrap()),
+    )]);
+    assert_eq!(write(&map), "<userdef1:14>Score,{-1.5:2}<eor>\n");
+}
+
+#[test]
+fn read_records() {
+    let data = "<adifver:5>3.1.4<eoh>\n\
+                <CALL:4>W1AW<QSO_DATE:8>20240115<FREQ:6>14.074<eor>\n\
+                <call:4>N0AA<qso_date:8>20240115<freq:1>7<dxcc:3>291\
+                <lotw:1>Y<qsl_rcvd:1>r<dxcc_extra:0><eor>\n";
+    let contacts: Vec<Contact> = from_reader(data.as_bytes()).unwrap();
+    let mut expected = vec![contact("W1AW", 14.074), contact("N0AA", 7.0)];
+    expected[1].dxcc = Some(291);
+    expected[1].lotw = Some(true);
+    expected[1].qsl_rcvd = Some(QslRcvd::R);
+    assert_eq!(contacts, expected);
+}
+
+#[test]
+fn read_single() {
+    let data = "<call:4>W1AW<qso_date:8>20240115<freq:6>14.074\
+                <dxcc:0><eor>";
+    let c: Contact = from_reader(data.as_bytes()).unwrap();
+    assert_eq!(c, contact("W1AW", 14.074));
+    let c: Option<Contact> = from_reader(data.as_bytes()).unwrap();
+    assert_eq!(c, Some(contact("W1AW", 14.074)));
+
+    let map: HashMap<String, String> = from_reader(data.as_bytes()).unwrap();
+    assert_eq!(map["freq"], "14.074");
+    assert_eq!(map["dxcc"], "");
+
+    let two = format!("{data}{data}");
+    let err = from_reader::<_, Contact>(two.as_bytes()).unwrap_err();
+    assert_eq!(err, Error::Serde("more than one record".into()));
+    let err = from_reader::<_, Contact>(&b""[..]).unwrap_err();
+    assert_eq!(err, Error::Serde("no record in input".into()));
+}
+
+#[test]
+fn read_types() {
+    #[derive(Debug, Deserialize)]
+    struct Wrapper(i16);
+
+    #[derive(Debug, Deserialize)]
+    struct Types {
+        a: i8,
+        b: Wrapper,
+        c: i32,
+        d: i64,
+        e: u8,
+        f: u16,
+        g: u32,
+        h: u64,
+        i: f32,
+        j: bool,
+        k: Datum,
+        l: serde_json::Value,
+    }
+
+    let data = "<a:2>-1<b:1>2<c:2>-3<d:1>4<e:1>5<f:1>6<g:1>7<h:1>8\
+                <i:3>7.5<j:1>n<k:8:d>20240115<l:4:n>14.5<eor>";
+    let t: Types = from_reader(data.as_bytes()).unwrap();
+    assert_eq!((t.a, t.b.0, t.c, t.d), (-1, 2, -3, 4));
+    assert_eq!((t.e, t.f, t.g, t.h), (5, 6, 7, 8));
+    assert_eq!(t.i, 7.5);
+    assert!(!t.j);
+    assert_eq!(
+        t.k,
+        Datum::Date(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap())
+    );
+    assert_eq!(t.l, serde_json::json!(14.5));
+}
+
+#[test]
+fn read_any() {
+    let data = "<a:1:b>Y<b:2:n>-7<c:20:n>18446744073709551616\
+                <d:3:n>1.5<e:4>W1AW<eor>";
+    let map: HashMap<String, serde_json::Value> =
+        from_reader(data.as_bytes()).unwrap();
+    assert_eq!(map["a"], serde_json::json!(true));
+    assert_eq!(map["b"], serde_json::json!(-7));
+    assert_eq!(map["c"], serde_json::json!(18446744073709551616.0));
+    assert_eq!(map["d"], serde_json::json!(1.5));
+    assert_eq!(map["e"], serde_json::json!("W1AW"));
+}
+
+#[test]
+fn round_trip() {
+    let data = "<call:4>W1AW<qso_date:8:d>20240115<freq:6:n>14.074\
+                <my_gridsquare:6>EM10dg<eor>\n\
+                <call:4>N0AA<qso_date:8:d>20240116<lotw:1:b>N<eor>\n";
+    let records: Vec<Record> = from_reader(data.as_bytes()).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(write(&records), data);
+}
+
+#[test]
+fn missing_field() {
+    let data = "<call:4>W1AW<freq:6>14.074<eor>";
+    let err = from_reader::<_, Vec<Contact>>(data.as_bytes()).unwrap_err();
+    let rec = from_reader::<_, Record>(data.as_bytes()).unwrap();
+    assert_eq!(
+        err,
+        Error::MissingField {
+            field: "qso_date".into(),
+            record: Box::new(rec),
+            span: Some(span((1, 1, 0), (1, 27, 26))),
+        }
+    );
+}
+
+#[test]
+fn invalid_value() {
+    let data = "<call:4>W1AW<qso_date:8>20240115<freq:3>abc<eor>";
+    let err = from_reader::<_, Contact>(data.as_bytes()).unwrap_err();
+    let rec = from_reader::<_, Record>(data.as_bytes()).unwrap();
+    assert_eq!(
+        err,
+        Error::InvalidValue {
+            field: "freq".into(),
+            value: "abc".into(),
+            record: Box::new(rec),
+            span: Some(span((1, 33, 32), (1, 44, 43))),
+        }
+    );
+
+    let cases = [
+        ("<dxcc:5>70000", "dxcc"),
+        ("<lotw:1>X", "lotw"),
+        ("<qsl_rcvd:1>Q", "qsl_rcvd"),
+    ];
+    for (field, name) in cases {
+        let data =
+            format!("<call:4>W1AW<qso_date:8>20240115<freq:1>7{field}<eor>");
+        let err = from_reader::<_, Contact>(data.as_bytes()).unwrap_err();
+        assert!(
+            matches!(&err, Error::InvalidValue { field, .. } if field == name),
+            "{err}"
+        );
+    }
+}
+
+#[test]
+fn reader_error() {
+    let data = "<call:4>W1AW<eor><call:x>W1AW<eor>";
+    let err = from_reader::<_, Vec<Record>>(data.as_bytes()).unwrap_err();
+    assert!(matches!(err, Error::InvalidFormat { .. }), "{err}");
+    let err = from_reader::<_, Record>(data.as_bytes()).unwrap_err();
+    assert!(matches!(err, Error::InvalidFormat { .. }), "{err}");
+}
+
+#[test]
+fn error_display() {
+    let err = <Error as ser::Error>::custom("oops");
+    assert_eq!(err.to_string(), "serde error: oops");
+    let err = <Error as de::Error>::custom("oops");
+    assert_eq!(err, Error::Serde("oops".into()));
+}
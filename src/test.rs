@@ -66,6 +66,24 @@ fn tag_is_eor_false_for_field() {
     assert!(!tag.is_eor());
 }
 
+#[test]
+fn tag_as_preamble() {
+    let tag = Tag::Preamble("Sample Logbook".to_string());
+    assert_eq!(tag.as_preamble(), Some("Sample Logbook"));
+    assert!(tag.as_field().is_none());
+    assert!(Tag::Eoh.as_preamble().is_none());
+}
+
+#[test]
+fn record_preamble() {
+    let mut header = Record::new_header();
+    assert_eq!(header.preamble(), None);
+    header.set_preamble("Sample Logbook");
+    assert_eq!(header.preamble(), Some("Sample Logbook"));
+    let header = header.map_fields(|_, v| v);
+    assert_eq!(header.preamble(), Some("Sample Logbook"));
+}
+
 #[test]
 fn as_bool_unsupported_types() {
     let d = Datum::Date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
@@ -143,13 +161,21 @@ fn error_eq() {
 
     assert_eq!(e1, e2);
     assert_ne!(e1, e3);
-    assert_ne!(e1, invalid_format("msg", 1, 1, 0));
+    assert_ne!(e1, invalid_format(other("msg"), 1, 1, 0));
 
-    let e1 = invalid_format("abc", 1, 1, 0);
-    let e2 = invalid_format("def", 1, 1, 0);
-    let e3 = invalid_format("abc", 2, 1, 0);
+    let e1 = invalid_format(other("abc"), 1, 1, 0);
+    let e2 = invalid_format(other("def"), 1, 1, 0);
+    let e3 = invalid_format(other("abc"), 2, 1, 0);
     assert_errs_ne(e1, e2, e3);
 
+    let e1 = limit_exceeded(Limit::Fields, 5, 1, 1, 0);
+    let e2 = limit_exceeded(Limit::ValueLength, 5, 1, 1, 0);
+    let e3 = limit_exceeded(Limit::Fields, 6, 1, 1, 0);
+    assert_errs_ne(e1, e2, e3);
+    let e1 = limit_exceeded(Limit::Fields, 5, 1, 1, 0);
+    let e2 = limit_exceeded(Limit::Fields, 5, 1, 2, 1);
+    assert_ne!(e1, e2);
+
     let mut rec = Record::new();
     let e1 = duplicate_key("abc", rec.clone());
     let e2 = duplicate_key("def", rec.clone());
@@ -251,3 +277,548 @@ fn to_cabrillo() {
     );
     assert_eq!(dt.to_cabrillo(), "2024-01-15 1234");
 }
+
+#[test]
+fn string_types_coerce() {
+    let s = |s: &str| s.to_string();
+    let cases = [
+        Datum::IntlString(s("Y")),
+        Datum::MultilineString(s("Y")),
+        Datum::IntlMultilineString(s("Y")),
+        Datum::Enumeration(s("Y")),
+    ];
+    for d in cases {
+        assert_eq!(d.as_bool(), Some(true));
+        assert_eq!(d.as_str(), "Y");
+        assert_eq!(d.to_cabrillo(), "Y");
+        assert!(d.as_number().is_none());
+    }
+    let d = Datum::IntlString(s("20240102"));
+    assert_eq!(d.as_date(), NaiveDate::from_ymd_opt(2024, 1, 2));
+    let d = Datum::Enumeration(s("123456"));
+    assert_eq!(d.as_time(), NaiveTime::from_hms_opt(12, 34, 56));
+    let d = Datum::MultilineString(s("20240102 123456"));
+    assert!(d.as_datetime().is_some());
+}
+
+#[test]
+fn as_integer() {
+    assert_eq!(Datum::Number(Decimal::from(42)).as_integer(), Some(42));
+    assert_eq!(Datum::Number(Decimal::new(420, 1)).as_integer(), Some(42));
+    assert_eq!(Datum::Number(Decimal::new(425, 1)).as_integer(), None);
+    assert_eq!(Datum::Number(Decimal::MAX).as_integer(), None);
+    assert_eq!(Datum::String("-7".to_string()).as_integer(), Some(-7));
+    assert_eq!(Datum::String("x".to_string()).as_integer(), None);
+    assert_eq!(Datum::Boolean(true).as_integer(), None);
+}
+
+#[test]
+fn as_location() {
+    let loc = Location::parse("N042 21.123").unwrap();
+    let d = Datum::Location(loc);
+    assert_eq!(d.as_location(), Some(loc));
+    assert_eq!(d.as_str(), "N042 21.123");
+    assert_eq!(d.to_cabrillo(), "N042 21.123");
+    assert!(d.as_bool().is_none());
+    assert!(d.as_number().is_none());
+    assert_eq!(Datum::from("N042 21.123").as_location(), Some(loc));
+    assert!(Datum::from("N042").as_location().is_none());
+    assert!(Datum::Boolean(true).as_location().is_none());
+}
+
+#[test]
+fn location_parse() {
+    let loc = Location::parse("s090 00.000").unwrap();
+    assert_eq!(loc.direction(), 'S');
+    assert!(loc.is_latitude());
+    assert_eq!(loc.to_degrees(), Decimal::from(-90));
+    let loc = Location::parse("E180 00.000").unwrap();
+    assert!(!loc.is_latitude());
+    assert_eq!(loc.to_degrees(), Decimal::from(180));
+    let loc = Location::parse("N001 30.000").unwrap();
+    assert_eq!(loc.to_degrees(), Decimal::new(15, 1));
+    assert_eq!(loc.to_string(), "N001 30.000");
+
+    let bad = [
+        "",
+        "N042 21.1234",
+        "X042 21.123",
+        "N042-21.123",
+        "N042 21,123",
+        "N0a2 21.123",
+        "N042 2b.123",
+        "N042 21.1c3",
+        "N091 00.000",
+        "N090 00.001",
+        "E181 00.000",
+        "E999 00.000",
+        "N042 60.000",
+        "N\u{f6}42 21.12",
+    ];
+    for s in bad {
+        assert!(Location::parse(s).is_none(), "{s}");
+    }
+}
+
+#[test]
+fn location_new() {
+    let loc = Location::new('w', 10, Decimal::new(123450, 4)).unwrap();
+    assert_eq!(loc.to_string(), "W010 12.345");
+    assert_eq!(loc.minutes(), Decimal::new(12345, 3));
+    assert!(Location::new('N', 10, Decimal::new(12345, 4)).is_none());
+    assert!(Location::new('N', 10, Decimal::from(-1)).is_none());
+    assert!(Location::new('N', 10, Decimal::from(60)).is_none());
+    assert!(Location::new('N', 10, Decimal::from(100000)).is_none());
+}
+
+#[test]
+fn data_type_indicators() {
+    let types = [
+        DataType::Boolean,
+        DataType::Number,
+        DataType::Date,
+        DataType::Time,
+        DataType::String,
+        DataType::IntlString,
+        DataType::MultilineString,
+        DataType::IntlMultilineString,
+        DataType::Enumeration,
+        DataType::Location,
+    ];
+    for t in types {
+        assert_eq!(DataType::from_indicator(t.indicator()), Some(t));
+        let upper = t.indicator().to_ascii_uppercase();
+        assert_eq!(DataType::from_indicator(&upper), Some(t));
+    }
+    assert_eq!(DataType::from_indicator(""), None);
+    assert_eq!(DataType::from_indicator("nn"), None);
+    assert_eq!(DataType::from_indicator("x"), None);
+    assert_eq!(DataType::from_indicator("é"), None);
+}
+
+#[test]
+fn userdef_parse() {
+    let n = Some(DataType::Number);
+    let def = UserDef::parse(" Size , { S , M } ", None).unwrap();
+    assert_eq!(def.name(), "Size");
+    assert_eq!(def.data_type(), None);
+    let values = UserDefValues::Enum(vec!["S".into(), "M".into()]);
+    assert_eq!(def.values(), Some(&values));
+    assert_eq!(def.to_string(), "Size,{S,M}");
+
+    let def = UserDef::parse("Score,{-1.5:2}", n).unwrap();
+    let values = UserDefValues::Range(Decimal::new(-15, 1), 2.into());
+    assert_eq!(def.values(), Some(&values));
+    assert_eq!(def.to_string(), "Score,{-1.5:2}");
+
+    let def = UserDef::parse("Epc", n).unwrap();
+    assert_eq!(def.values(), None);
+    assert_eq!(def.to_string(), "Epc");
+
+    let bad = [
+        "", ",{1:2}", "x,", "x,1:2", "x,{1:2", "x,1:2}", "x,{a:2}", "x,{1:b}",
+        "x,{2:1}", "x,{}", "x,{a,,b}",
+    ];
+    for s in bad {
+        assert!(UserDef::parse(s, n).is_none(), "{s}");
+    }
+}
+
+#[test]
+fn userdef_name() {
+    assert!(UserDef::is_userdef_name("USERDEF1"));
+    assert!(UserDef::is_userdef_name("userdef42"));
+    assert!(!UserDef::is_userdef_name("userdef"));
+    assert!(!UserDef::is_userdef_name("userdefx"));
+    assert!(!UserDef::is_userdef_name("user"));
+    assert!(!UserDef::is_userdef_name("userde\u{f6}1"));
+}
+
+#[test]
+fn userdef_apply() {
+    let def = UserDef::new("x", None);
+    assert_eq!(def.apply("a".into()), Some(Datum::from("a")));
+
+    let values = UserDefValues::Enum(vec!["A".into()]);
+    let def = UserDef::new("x", None).with_values(values);
+    assert_eq!(def.apply("a".into()), Some(Datum::from("a")));
+    assert_eq!(def.apply("b".into()), None);
+
+    let def = UserDef::new("x", Some(DataType::Date));
+    assert_eq!(def.apply("x".into()), None);
+    let d = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    assert_eq!(def.apply(d.into()), Some(Datum::Date(d)));
+}
+
+#[test]
+fn userdef_datum() {
+    let def = UserDef::parse("Size,{S,M}", Some(DataType::Enumeration));
+    let d = Datum::from(def.unwrap());
+    assert_eq!(d.as_str(), "Size,{S,M}");
+    assert_eq!(d.to_cabrillo(), "Size,{S,M}");
+    assert!(d.as_bool().is_none());
+}
+
+#[test]
+fn invalid_value_display() {
+    let err = Error::InvalidValue {
+        field: "size".into(),
+        value: "XL".into(),
+        record: Box::new(Record::new()),
+        span: None,
+    };
+    assert_eq!(err.to_string(), "invalid value 'XL' for field 'size'");
+}
+
+#[test]
+fn split_app_names() {
+    assert_eq!(split_app_name("APP_LOTW_MODE"), Some(("LOTW", "MODE")));
+    assert_eq!(
+        split_app_name("app_n1mm_radio_nr"),
+        Some(("n1mm", "radio_nr"))
+    );
+    for name in ["APP", "APP_", "APP_LOTW", "APP_LOTW_", "APP__MODE", "CALL"] {
+        assert_eq!(split_app_name(name), None, "{name}");
+    }
+    assert_eq!(split_app_name("\u{f6}\u{f6}_X_Y"), None);
+}
+
+#[test]
+fn app_fields() {
+    let mut r = Record::new();
+    r.insert("call", "W1AW").unwrap();
+    r.insert_app("LOTW", "MODEGROUP", "DATA").unwrap();
+    r.insert("app_", "x").unwrap();
+    r.insert_app("n1mm", "radio_nr", Decimal::ONE).unwrap();
+    let apps: Vec<_> = r.app_fields().map(|(p, f, _)| (p, f)).collect();
+    assert_eq!(apps, [("LOTW", "MODEGROUP"), ("n1mm", "radio_nr")]);
+
+    assert_eq!(r.get("app_lotw_modegroup").unwrap().as_str(), "DATA");
+    assert_eq!(r.get_app("lotw", "modegroup").unwrap().as_str(), "DATA");
+    assert!(r.get_app("lotw", "mode").is_none());
+    assert!(r.get_app("lo_tw", "mode").is_none());
+
+    let err = r.insert_app("LOTW", "modegroup", "x").unwrap_err();
+    assert!(matches!(err, Error::DuplicateKey { .. }));
+    for (program, field) in [("", "x"), ("a_b", "x"), ("a", "")] {
+        let err = r.insert_app(program, field, "x").unwrap_err();
+        let name = format!("APP_{program}_{field}");
+        assert_eq!(err, Error::InvalidName { name });
+    }
+}
+
+#[test]
+fn retain() {
+    let mut r = Record::new_header();
+    r.insert("a", "1").unwrap();
+    r.insert("b", "2").unwrap();
+    r.insert("c", "3").unwrap();
+    r.retain(|k, v| k != "b" && v.as_str() != "3");
+    let fields: Vec<_> = r.fields().map(|(k, _)| k).collect();
+    assert_eq!(fields, ["a"]);
+    assert!(r.is_header());
+}
+
+#[test]
+fn invalid_name_display() {
+    let err = Error::InvalidName {
+        name: "APP_X".into(),
+    };
+    assert_eq!(err.to_string(), "invalid field name 'APP_X'");
+}
+
+#[test]
+fn coerce_times() {
+    let t = |s: &str| Datum::from(s).as_time();
+    assert_eq!(t("1430"), NaiveTime::from_hms_opt(14, 30, 0));
+    assert_eq!(t("143015"), NaiveTime::from_hms_opt(14, 30, 15));
+    for bad in [
+        "", "143", "14300", "1430150", "14:30", " 1430", "2400", "+430",
+    ] {
+        assert_eq!(t(bad), None, "{bad}");
+    }
+
+    let d = |s: &str| Datum::from(s).as_date();
+    assert_eq!(d("20240115"), NaiveDate::from_ymd_opt(2024, 1, 15));
+    for bad in [
+        "",
+        "2024115",
+        "2024-1-15",
+        "+2024115",
+        "202401150",
+        "20240230",
+    ] {
+        assert_eq!(d(bad), None, "{bad}");
+    }
+
+    let dt = |s: &str| Datum::from(s).as_datetime();
+    let expected = NaiveDate::from_ymd_opt(2024, 1, 15)
+        .unwrap()
+        .and_hms_opt(14, 30, 0);
+    assert_eq!(dt("20240115 1430"), expected);
+    assert_eq!(dt("20240115 143000"), expected);
+    for bad in ["20240115", "20240115 14", "2024011 1430", "20240115  1430"] {
+        assert_eq!(dt(bad), None, "{bad}");
+    }
+}
+
+#[test]
+fn spans() {
+    let at = |n| span((1, n, n - 1), (1, n + 10, n + 9));
+    let mut record = Record::new();
+    assert!(record.span().is_none());
+    record.insert("a", "x").unwrap();
+    record
+        .insert_field(
+            Field::new("b", "y").with_span(at(11)),
+            &Duplicates::Error,
+        )
+        .unwrap();
+    record.insert("c", "z").unwrap();
+    record
+        .insert_field(
+            Field::new("d", "w").with_span(at(31)),
+            &Duplicates::Error,
+        )
+        .unwrap();
+    assert_eq!(record.span(), Some(span((1, 11, 10), (1, 41, 40))));
+    assert_eq!(record.field_span("a"), None);
+    assert_eq!(record.field_span("B"), Some(at(11)));
+    assert_eq!(record.field_span("c"), None);
+    assert_eq!(record.field_span("d"), Some(at(31)));
+    assert_eq!(record.field_span("e"), None);
+
+    let err = record.insert_field(
+        Field::new("b", "v").with_span(at(41)),
+        &Duplicates::Error,
+    );
+    assert_eq!(err, Err(duplicate_key_at("b", record.clone(), at(41))));
+    // without a span of its own, the error points at the existing field
+    let err = record.insert("b", "v");
+    assert_eq!(err, Err(duplicate_key_at("b", record.clone(), at(11))));
+    let err = record.insert("a", "v");
+    assert_eq!(err, Err(duplicate_key("a", record.clone())));
+
+    let mut plain = Record::new();
+    plain.insert("a", "x").unwrap();
+    plain.insert("b", "y").unwrap();
+    plain.insert("c", "z").unwrap();
+    plain.insert("d", "w").unwrap();
+    assert_eq!(record, plain);
+    assert_eq!(Field::new("b", "y").with_span(at(1)), Field::new("b", "y"));
+    assert_ne!(Field::new("b", "y"), Field::new("b", "z"));
+
+    let record = record.map_fields(|_, v| v);
+    assert_eq!(record.field_span("d"), Some(at(31)));
+    let mut retained = record.clone();
+    retained.retain(|k, _| k != "b");
+    assert_eq!(retained.field_span("a"), None);
+    assert_eq!(retained.field_span("c"), None);
+    assert_eq!(retained.field_span("d"), Some(at(31)));
+    let mut retained = record;
+    retained.retain(|k, _| k == "a");
+    assert_eq!(retained.field_span("a"), None);
+    retained.insert("b", "y").unwrap();
+    assert_eq!(retained.field_span("b"), None);
+}
+
+#[test]
+fn span_display() {
+    let s = span((1, 2, 1), (3, 4, 20));
+    assert_eq!(
+        s.to_string(),
+        "line 1, column 2 (byte 1) to line 3, column 4 (byte 20)"
+    );
+    let err = Error::InvalidValue {
+        field: "size".into(),
+        value: "XL".into(),
+        record: Box::new(Record::new()),
+        span: Some(s),
+    };
+    assert_eq!(
+        err.to_string(),
+        "invalid value 'XL' for field 'size' (at line 1, column 2 (byte 1))"
+    );
+    let err = Error::MissingField {
+        field: "freq".into(),
+        record: Box::new(Record::new()),
+        span: Some(s),
+    };
+    assert_eq!(
+        err.to_string(),
+        "missing required field 'freq' in record \
+         (at line 1, column 2 (byte 1))"
+    );
+}
+
+#[test]
+fn insert_duplicates() {
+    let mut r = Record::new();
+    r.insert("call", "W1AW").unwrap();
+    r.insert_with("CALL", "N0AA", Duplicates::First).unwrap();
+    assert_eq!(r.get("call").unwrap().as_str(), "W1AW");
+    r.insert_with("CALL", "N0AA", Duplicates::Last).unwrap();
+    assert_eq!(r.get("call").unwrap().as_str(), "N0AA");
+    let err = r.insert_with("call", "K1AB", Duplicates::Error);
+    assert_eq!(err, Err(duplicate_key("call", r.clone())));
+    r.insert_with("call", "K1AB", Duplicates::Concatenate(",".into()))
+        .unwrap();
+    assert_eq!(r.get("call").unwrap(), &Datum::from("N0AA,K1AB"));
+    r.insert_with("new", "x", Duplicates::Last).unwrap();
+    assert_eq!(r.fields().count(), 2);
+    assert_eq!(r.get_all("call").count(), 1);
+    assert_eq!(r.get_all("missing").count(), 0);
+
+    let mut r = Record::new();
+    let comment = Datum::IntlString("caf\u{e9}".into());
+    r.insert("comment", comment).unwrap();
+    r.insert_with("comment", "5", Duplicates::Concatenate(" ".into()))
+        .unwrap();
+    let comment = Datum::IntlString("caf\u{e9} 5".into());
+    assert_eq!(r.get("comment").unwrap(), &comment);
+    let notes = Datum::MultilineString("a".into());
+    r.insert("notes", notes).unwrap();
+    r.insert_with("notes", "b", Duplicates::Concatenate("\r\n".into()))
+        .unwrap();
+    let notes = Datum::MultilineString("a\r\nb".into());
+    assert_eq!(r.get("notes").unwrap(), &notes);
+    let intl_notes = Datum::IntlMultilineString("c".into());
+    r.insert("intl_notes", intl_notes).unwrap();
+    r.insert_with("intl_notes", "d", Duplicates::Concatenate("".into()))
+        .unwrap();
+    let intl_notes = Datum::IntlMultilineString("cd".into());
+    assert_eq!(r.get("intl_notes").unwrap(), &intl_notes);
+}
+
+#[test]
+fn keep_all_duplicates() {
+    let mut r = Record::new();
+    r.insert("comment", "a").unwrap();
+    r.insert("call", "W1AW").unwrap();
+    r.insert_with("COMMENT", "b", Duplicates::KeepAll).unwrap();
+    r.insert_with("comment", "c", Duplicates::KeepAll).unwrap();
+    let all = |r: &Record| -> Vec<String> {
+        r.get_all("Comment")
+            .map(|d| d.as_str().into_owned())
+            .collect()
+    };
+    assert_eq!(all(&r), ["a", "b", "c"]);
+    assert_eq!(r.fields().count(), 2);
+
+    let mut first = Record::new();
+    first.insert("comment", "a").unwrap();
+    first.insert("call", "W1AW").unwrap();
+    assert_ne!(r, first);
+
+    let r = r.map_fields(|_, v| Datum::from(v.as_str().to_uppercase()));
+    assert_eq!(all(&r), ["A", "B", "C"]);
+    let mut r = r;
+    r.retain(|_, v| v.as_str() != "B");
+    assert_eq!(all(&r), ["A", "C"]);
+}
+
+#[test]
+fn duplicate_spans() {
+    let at = |n| span((1, n, n - 1), (1, n + 10, n + 9));
+    let field = |v, n| Field::new("a", v).with_span(at(n));
+    let mut r = Record::new();
+    r.insert_field(field("x", 1), &Duplicates::Error).unwrap();
+    r.insert_field(field("y", 11), &Duplicates::First).unwrap();
+    assert_eq!(r.field_span("a"), Some(at(1)));
+    assert_eq!(r.span(), Some(span((1, 1, 0), (1, 21, 20))));
+    r.insert_field(field("y", 21), &Duplicates::Last).unwrap();
+    assert_eq!(r.field_span("a"), Some(at(21)));
+    r.insert_field(field("z", 31), &Duplicates::Concatenate("".into()))
+        .unwrap();
+    r.insert_field(field("w", 41), &Duplicates::KeepAll)
+        .unwrap();
+    assert_eq!(r.field_span("a"), Some(at(21)));
+    assert_eq!(r.span(), Some(span((1, 1, 0), (1, 51, 50))));
+    assert_eq!(r.get("a").unwrap().as_str(), "yz");
+
+    let mut r = Record::new();
+    r.insert("a", "x").unwrap();
+    r.insert("b", "x").unwrap();
+    r.insert_field(field("y", 1), &Duplicates::Last).unwrap();
+    assert_eq!(r.field_span("a"), Some(at(1)));
+    assert_eq!(r.field_span("b"), None);
+    r.insert_with("a", "z", Duplicates::Last).unwrap();
+    assert_eq!(r.field_span("a"), None);
+}
+
+#[test]
+fn invalid_format_display() {
+    let cases = [
+        (bad_tag("a:1:2:3"), "malformed tag <a:1:2:3>"),
+        (
+            bad_length("call", "x"),
+            "invalid length 'x' for field 'call'",
+        ),
+        (
+            InvalidFormatKind::BadTypeEncoding {
+                name: "call".into(),
+                typ: "\u{fffd}".into(),
+            },
+            "invalid type indicator '\u{fffd}' for field 'call'",
+        ),
+        (
+            bad_value("qso_date", "2024", DataType::Date),
+            "invalid Date '2024' for field 'qso_date'",
+        ),
+        (
+            InvalidFormatKind::BadUserDef {
+                name: "userdef1".into(),
+                value: ",{}".into(),
+            },
+            "invalid declaration ',{}' for field 'userdef1'",
+        ),
+        (
+            bad_encoding(None, "Gr\u{fffd}e"),
+            "invalid text 'Gr\u{fffd}e'",
+        ),
+        (
+            bad_encoding(Some("name"), "J\u{fffd}n"),
+            "invalid text 'J\u{fffd}n' for field 'name'",
+        ),
+        (
+            InvalidFormatKind::PartialData,
+            "partial data at end of stream",
+        ),
+        (other("unterminated entity"), "unterminated entity"),
+    ];
+    for (kind, expected) in cases {
+        assert_eq!(kind.to_string(), expected);
+    }
+
+    let err = invalid_format(bad_length("call", "x"), 2, 3, 10);
+    assert_eq!(
+        err.to_string(),
+        "Invalid ADIF format: invalid length 'x' for field 'call' \
+         (at line 2, column 3 (byte 10))"
+    );
+}
+
+#[test]
+fn error_position() {
+    let err = limit_exceeded(Limit::Fields, 5, 2, 3, 10);
+    let position = Position {
+        line: 2,
+        column: 3,
+        byte: 10,
+    };
+    assert_eq!(err.position(), Some(position));
+    assert_eq!(err.span(), None);
+
+    let at = span((1, 1, 0), (1, 9, 8));
+    let err = Error::InvalidValue {
+        field: "a".into(),
+        value: "b".into(),
+        record: Box::new(Record::new()),
+        span: Some(at),
+    };
+    assert_eq!(err.position(), Some(at.start));
+    assert_eq!(err.span(), Some(at));
+    let err = duplicate_key("a", Record::new());
+    assert_eq!(err.position(), None);
+    assert_eq!(Error::MissingHeader.position(), None);
+}
@@ -6,25 +6,94 @@ use std::task::{Context, Poll};
 use futures::Stream;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
-use crate::{Error, Position, Record};
+use crate::{
+    DataType, Error, InvalidFormatKind, Limit, Position, Record, Span,
+};
 
 pub(crate) fn invalid_format(
-    message: &'static str, line: usize, column: usize, byte: usize,
+    kind: InvalidFormatKind, line: usize, column: usize, byte: usize,
 ) -> Error {
     Error::InvalidFormat {
-        message: Cow::Borrowed(message),
+        kind,
         position: Position { line, column, byte },
     }
 }
 
 pub(crate) fn partial_data(line: usize, column: usize, byte: usize) -> Error {
-    invalid_format("partial data at end of stream", line, column, byte)
+    invalid_format(InvalidFormatKind::PartialData, line, column, byte)
+}
+
+pub(crate) fn bad_tag(tag: &str) -> InvalidFormatKind {
+    InvalidFormatKind::BadTag { tag: tag.into() }
+}
+
+pub(crate) fn bad_length(name: &str, length: &str) -> InvalidFormatKind {
+    InvalidFormatKind::BadLength {
+        name: name.into(),
+        length: length.into(),
+    }
+}
+
+pub(crate) fn bad_value(
+    name: &str, value: &str, typ: DataType,
+) -> InvalidFormatKind {
+    InvalidFormatKind::BadValue {
+        name: name.into(),
+        value: value.into(),
+        typ,
+    }
+}
+
+pub(crate) fn bad_encoding(
+    name: Option<&str>, text: &str,
+) -> InvalidFormatKind {
+    InvalidFormatKind::BadEncoding {
+        name: name.map(str::to_string),
+        text: text.into(),
+    }
+}
+
+pub(crate) fn other(message: &'static str) -> InvalidFormatKind {
+    InvalidFormatKind::Other(Cow::Borrowed(message))
+}
+
+pub(crate) fn limit_exceeded(
+    limit: Limit, max: usize, line: usize, column: usize, byte: usize,
+) -> Error {
+    Error::LimitExceeded {
+        limit,
+        max,
+        position: Position { line, column, byte },
+    }
 }
 
 pub(crate) fn duplicate_key(key: &str, record: Record) -> Error {
     Error::DuplicateKey {
         key: key.to_string(),
-        record,
+        record: Box::new(record),
+        span: None,
+    }
+}
+
+pub(crate) fn span(
+    (line, column, byte): (usize, usize, usize),
+    (end_line, end_column, end_byte): (usize, usize, usize),
+) -> Span {
+    Span {
+        start: Position { line, column, byte },
+        end: Position {
+            line: end_line,
+            column: end_column,
+            byte: end_byte,
+        },
+    }
+}
+
+pub(crate) fn duplicate_key_at(key: &str, record: Record, span: Span) -> Error {
+    Error::DuplicateKey {
+        key: key.to_string(),
+        record: Box::new(record),
+        span: Some(span),
     }
 }
 
@@ -35,7 +104,8 @@ pub(crate) fn cannot_output(typ: &'static str, reason: &'static str) -> Error {
 pub(crate) fn missing_field(field: &str, record: Record) -> Error {
     Error::MissingField {
         field: field.into(),
-        record,
+        record: Box::new(record),
+        span: None,
     }
 }
 
@@ -47,9 +117,12 @@ pub(crate) struct TrickleReader {
 }
 
 impl TrickleReader {
-    pub(crate) fn new(data: &str, chunk: usize) -> Self {
+    pub(crate) fn new<D>(data: D, chunk: usize) -> Self
+    where
+        D: AsRef<[u8]>,
+    {
         Self {
-            data: data.as_bytes().to_vec(),
+            data: data.as_ref().to_vec(),
             pos: 0,
             chunk,
             delayed: false,
@@ -80,6 +153,27 @@ impl AsyncRead for TrickleReader {
     }
 }
 
+impl io::Read for TrickleReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.data.len() - self.pos;
+        if remaining == 0 {
+            return Ok(0);
+        }
+
+        if !self.delayed {
+            self.delayed = true;
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+
+        let to_read = remaining.min(self.chunk).min(buf.len());
+        buf[..to_read]
+            .copy_from_slice(&self.data[self.pos..self.pos + to_read]);
+        self.pos += to_read;
+        self.delayed = false;
+        Ok(to_read)
+    }
+}
+
 pub(crate) struct TrickleWriter {
     buf: Vec<u8>,
     chunk: usize,
@@ -94,6 +188,10 @@ impl TrickleWriter {
             delayed: false,
         }
     }
+
+    pub(crate) fn written(&self) -> &[u8] {
+        &self.buf
+    }
 }
 
 impl AsyncWrite for TrickleWriter {
@@ -0,0 +1,156 @@
+use crate::parse::parse_as;
+use crate::{DataType, Datum};
+use rust_decimal::Decimal;
+use std::fmt::{Display, Formatter, Result};
+use std::str::FromStr;
+
+/// Values permitted for a user-defined field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UserDefValues {
+    /// One of a list of values, compared case-insensitively
+    Enum(Vec<String>),
+    /// A number within an inclusive range
+    Range(Decimal, Decimal),
+}
+
+/// Declaration of a user-defined field.
+///
+/// ADIF headers declare user-defined fields with `USERDEFn` fields, which
+/// give the name of the field, its type, and optionally the values it may
+/// take.  Header fields so named are read as [Datum::UserDef], and can be
+/// written the same way.
+///
+/// ```
+/// use difa::{DataType, Datum, UserDef, UserDefValues};
+/// let def = UserDef::parse("ShoeSize,{5:20}", Some(DataType::Number))
+///     .unwrap();
+/// assert_eq!(def.name(), "ShoeSize");
+/// assert_eq!(
+///     def.values(),
+///     Some(&UserDefValues::Range(5.into(), 20.into()))
+/// );
+/// let size = def.apply(Datum::from("11")).unwrap();
+/// assert_eq!(size, Datum::Number(11.into()));
+/// assert!(def.apply(Datum::from("21")).is_none());
+/// assert_eq!(def.to_string(), "ShoeSize,{5:20}");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserDef {
+    name: String,
+    typ: Option<DataType>,
+    values: Option<UserDefValues>,
+}
+
+impl UserDef {
+    /// Declare a field with the given name and type.
+    pub fn new<S>(name: S, typ: Option<DataType>) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            typ,
+            values: None,
+        }
+    }
+
+    /// Restrict the field to the given values.
+    pub fn with_values(mut self, values: UserDefValues) -> Self {
+        self.values = Some(values);
+        self
+    }
+
+    /// Parse the value of a `USERDEFn` field, e.g. `SweaterSize,{S,M,L}`.
+    ///
+    /// Returns [None] if the declaration is malformed.
+    pub fn parse(value: &str, typ: Option<DataType>) -> Option<Self> {
+        let (name, values) = match value.split_once(',') {
+            Some((name, values)) => (name.trim(), Some(values.trim())),
+            None => (value.trim(), None),
+        };
+        if name.is_empty() {
+            return None;
+        }
+        let mut def = Self::new(name, typ);
+        let Some(values) = values else {
+            return Some(def);
+        };
+        let values = values.strip_prefix('{')?.strip_suffix('}')?;
+        let values = if let Some((min, max)) = values.split_once(':') {
+            let min = Decimal::from_str(min.trim()).ok()?;
+            let max = Decimal::from_str(max.trim()).ok()?;
+            if min > max {
+                return None;
+            }
+            UserDefValues::Range(min, max)
+        } else {
+            let values: Vec<_> =
+                values.split(',').map(|v| v.trim().to_string()).collect();
+            if values.iter().any(|v| v.is_empty()) {
+                return None;
+            }
+            UserDefValues::Enum(values)
+        };
+        def.values = Some(values);
+        Some(def)
+    }
+
+    pub(crate) fn is_userdef_name(name: &str) -> bool {
+        name.get(..7)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("userdef"))
+            && name.len() > 7
+            && name[7..].bytes().all(|b| b.is_ascii_digit())
+    }
+
+    /// Return the name of the declared field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the type of the declared field.
+    pub fn data_type(&self) -> Option<DataType> {
+        self.typ
+    }
+
+    /// Return the values permitted for the declared field, if restricted.
+    pub fn values(&self) -> Option<&UserDefValues> {
+        self.values.as_ref()
+    }
+
+    /// Apply the declaration to a value of the declared field.
+    ///
+    /// Untyped strings are parsed as the declared type.  Returns [None] if
+    /// the value is not valid for the type or is not a permitted value.
+    pub fn apply(&self, value: Datum) -> Option<Datum> {
+        let value = match (self.typ, value) {
+            (Some(typ), Datum::String(s)) => parse_as(&s, typ)?,
+            (_, value) => value,
+        };
+        let permitted = match &self.values {
+            None => true,
+            Some(UserDefValues::Enum(values)) => {
+                let s = value.as_str();
+                values.iter().any(|v| v.eq_ignore_ascii_case(&s))
+            }
+            Some(UserDefValues::Range(min, max)) => {
+                value.as_number().is_some_and(|n| *min <= n && n <= *max)
+            }
+        };
+        permitted.then_some(value)
+    }
+}
+
+impl Display for UserDef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.name)?;
+        match &self.values {
+            None => Ok(()),
+            Some(UserDefValues::Enum(values)) => {
+                write!(f, ",{{{}}}", values.join(","))
+            }
+            Some(UserDefValues::Range(min, max)) => {
+                write!(f, ",{{{min}:{max}}}")
+            }
+        }
+    }
+}
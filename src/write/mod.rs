@@ -1,6 +1,7 @@
 //! Writing ADIF data to async writers
 
-use crate::{Datum, Error, Record, Tag};
+use crate::parse::{is_intl_string, is_multiline_string, is_string};
+use crate::{Datum, Encoding, Error, LengthUnit, Record, Tag, split_app_name};
 use bytes::{BufMut, BytesMut};
 use futures::sink::Sink;
 use std::pin::Pin;
@@ -15,6 +16,10 @@ mod test;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum OutputTypes {
     /// Always include type specifiers for all fields
+    ///
+    /// Untyped strings that are not valid ADIF Strings, e.g. because they
+    /// contain non-ASCII characters, are written without a type specifier
+    /// so that they read back unchanged.
     Always,
     /// Only include type specifiers for non-string types
     OnlyNonString,
@@ -27,6 +32,8 @@ pub enum OutputTypes {
 #[derive(Debug, Default)]
 pub struct TagEncoder {
     types: OutputTypes,
+    encoding: Encoding,
+    length: LengthUnit,
 }
 
 impl TagEncoder {
@@ -61,7 +68,39 @@ impl TagEncoder {
     /// assert_eq!(&buf[..], b"<call:4:s>W1AW");
     /// ```
     pub fn with_types(types: OutputTypes) -> Self {
-        Self { types }
+        Self {
+            types,
+            ..Self::default()
+        }
+    }
+
+    /// Set the character encoding of the output.
+    ///
+    /// Values containing characters that the encoding cannot represent
+    /// will fail to encode.
+    ///
+    /// ```
+    /// use bytes::BytesMut;
+    /// use difa::{Encoding, Field, LengthUnit, Tag, TagEncoder};
+    /// use tokio_util::codec::Encoder;
+    ///
+    /// let mut encoder = TagEncoder::new()
+    ///     .with_encoding(Encoding::Latin1)
+    ///     .with_length_unit(LengthUnit::Chars);
+    /// let mut buf = BytesMut::new();
+    /// let field = Field::new("name", "J\u{fc}rgen");
+    /// encoder.encode(Tag::Field(field), &mut buf).unwrap();
+    /// assert_eq!(&buf[..], b"<name:6>J\xfcrgen");
+    /// ```
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set the unit in which value lengths are counted.
+    pub fn with_length_unit(mut self, length: LengthUnit) -> Self {
+        self.length = length;
+        self
     }
 
     /// Create a sink from this encoder and a writer.
@@ -86,24 +125,79 @@ impl TagEncoder {
         FramedWrite::new(writer, self)
     }
 
-    fn type_indicator(
+    pub(crate) fn type_indicator(
         &self, datum: &Datum,
     ) -> Result<Option<&'static str>, Error> {
+        let invalid = |typ| Error::CannotOutput {
+            typ,
+            reason: "value contains characters not allowed by type",
+        };
         match (self.types, datum) {
             (_, Datum::DateTime(_)) => Err(Error::CannotOutput {
                 typ: "DateTime",
                 reason: "split into date and time fields",
             }),
+            // the declared type is part of the declaration
+            (_, Datum::UserDef(u)) => Ok(u.data_type().map(|t| t.indicator())),
             (OutputTypes::Never, _) => Ok(None),
             (_, Datum::Boolean(_)) => Ok(Some("b")),
             (_, Datum::Number(_)) => Ok(Some("n")),
             (_, Datum::Date(_)) => Ok(Some("d")),
             (_, Datum::Time(_)) => Ok(Some("t")),
-            (OutputTypes::Always, Datum::String(_)) => Ok(Some("s")),
-            (_, Datum::String(_)) => Ok(None),
+            (_, Datum::Location(_)) => Ok(Some("l")),
+            (_, Datum::Enumeration(s)) if !is_string(s) => {
+                Err(invalid("Enumeration"))
+            }
+            (_, Datum::Enumeration(_)) => Ok(Some("e")),
+            (OutputTypes::OnlyNonString, _) => Ok(None),
+            (_, Datum::String(s)) => Ok(is_string(s).then_some("s")),
+            (_, Datum::IntlString(s)) if !is_intl_string(s) => {
+                Err(invalid("IntlString"))
+            }
+            (_, Datum::IntlString(_)) => Ok(Some("i")),
+            (_, Datum::MultilineString(s)) if !is_multiline_string(s) => {
+                Err(invalid("MultilineString"))
+            }
+            (_, Datum::MultilineString(_)) => Ok(Some("m")),
+            (_, Datum::IntlMultilineString(_)) => Ok(Some("g")),
+        }
+    }
+
+    /// Validate the name of a field, returning its program ID and field name
+    /// if it is application-defined.
+    pub(crate) fn check_app_name(
+        name: &str,
+    ) -> Result<Option<(&str, &str)>, Error> {
+        let app = name
+            .get(..4)
+            .is_some_and(|p| p.eq_ignore_ascii_case("app_"));
+        match split_app_name(name) {
+            None if app => Err(Error::CannotOutput {
+                typ: "field",
+                reason: "APP field names must be APP_{PROGRAMID}_{FIELD}",
+            }),
+            parts => Ok(parts),
         }
     }
 
+    fn encode_preamble(
+        &self, text: &str, dst: &mut BytesMut,
+    ) -> Result<(), Error> {
+        if text.contains('<') {
+            return Err(Error::CannotOutput {
+                typ: "preamble",
+                reason: "preamble cannot contain '<'",
+            });
+        }
+        let bytes = self.encoding.encode(text).ok_or(Error::CannotOutput {
+            typ: "preamble",
+            reason: "not representable in output encoding",
+        })?;
+        dst.put_slice(&bytes);
+        dst.put_u8(b'\n');
+        Ok(())
+    }
+
     fn encode_eoh(dst: &mut BytesMut) {
         dst.put_slice(b"<eoh>\n");
     }
@@ -116,18 +210,32 @@ impl TagEncoder {
         &self, name: &str, value: &Datum, dst: &mut BytesMut,
     ) -> Result<(), Error> {
         let s = value.as_str();
+        let typ = self.type_indicator(value)?;
+        Self::check_app_name(name)?;
+        let encode = |s| {
+            self.encoding.encode(s).ok_or(Error::CannotOutput {
+                typ: "field",
+                reason: "not representable in output encoding",
+            })
+        };
+        let name = encode(name)?;
+        let bytes = encode(&s)?;
+        let len = match self.length {
+            LengthUnit::Bytes => bytes.len(),
+            LengthUnit::Chars => s.chars().count(),
+        };
 
         dst.put_u8(b'<');
-        dst.put_slice(name.as_bytes());
+        dst.put_slice(&name);
         dst.put_u8(b':');
         let mut buf = itoa::Buffer::new();
-        dst.put_slice(buf.format(s.len()).as_bytes());
-        if let Some(typ) = self.type_indicator(value)? {
+        dst.put_slice(buf.format(len).as_bytes());
+        if let Some(typ) = typ {
             dst.put_u8(b':');
             dst.put_slice(typ.as_bytes());
         }
         dst.put_u8(b'>');
-        dst.put_slice(s.as_bytes());
+        dst.put_slice(&bytes);
         Ok(())
     }
 }
@@ -141,6 +249,7 @@ impl Encoder<Tag> for TagEncoder {
         match item {
             Tag::Eoh => Self::encode_eoh(dst),
             Tag::Eor => Self::encode_eor(dst),
+            Tag::Preamble(text) => self.encode_preamble(&text, dst)?,
             Tag::Field(field) => {
                 self.encode_field(field.name(), field.value(), dst)?;
             }
@@ -152,12 +261,49 @@ impl Encoder<Tag> for TagEncoder {
 /// Internal tag type for writing with borrowed field data
 enum WriterTag<'a> {
     Field { name: &'a str, value: &'a Datum },
+    Preamble(&'a str),
     Eoh,
     Eor,
 }
 
+impl<'a> WriterTag<'a> {
+    /// Return the tags that make up a record, in output order.
+    fn for_record(r: &'a Record) -> impl Iterator<Item = WriterTag<'a>> {
+        let preamble = r.preamble().filter(|_| r.is_header());
+        let end = if r.is_header() {
+            WriterTag::Eoh
+        } else {
+            WriterTag::Eor
+        };
+        preamble
+            .map(WriterTag::Preamble)
+            .into_iter()
+            .chain(
+                r.output_fields()
+                    .map(|(name, value)| WriterTag::Field { name, value }),
+            )
+            .chain(std::iter::once(end))
+    }
+}
+
 /// Wrapper around TagEncoder for encoding WriterTag
-struct WriterTagEncoder(TagEncoder);
+pub(crate) struct WriterTagEncoder(TagEncoder);
+
+impl WriterTagEncoder {
+    pub(crate) fn new(encoder: TagEncoder) -> Self {
+        Self(encoder)
+    }
+
+    /// Encode a whole record.
+    pub(crate) fn encode_record(
+        &mut self, r: &Record, dst: &mut BytesMut,
+    ) -> Result<(), Error> {
+        for tag in WriterTag::for_record(r) {
+            self.encode(tag, dst)?;
+        }
+        Ok(())
+    }
+}
 
 impl Encoder<WriterTag<'_>> for WriterTagEncoder {
     type Error = Error;
@@ -168,6 +314,7 @@ impl Encoder<WriterTag<'_>> for WriterTagEncoder {
         match item {
             WriterTag::Eoh => TagEncoder::encode_eoh(dst),
             WriterTag::Eor => TagEncoder::encode_eor(dst),
+            WriterTag::Preamble(text) => self.0.encode_preamble(text, dst)?,
             WriterTag::Field { name, value } => {
                 self.0.encode_field(name, value, dst)?;
             }
@@ -222,25 +369,55 @@ where
     /// # })
     /// ```
     pub fn new(writer: W) -> Self {
-        Self {
-            inner: FramedWrite::new(
-                writer,
-                WriterTagEncoder(TagEncoder::new()),
-            ),
-        }
+        Self::with_encoder(writer, TagEncoder::new())
     }
 
     /// Create a new RecordSink with given type specifier behavior.
     pub fn with_types(writer: W, types: OutputTypes) -> Self {
+        Self::with_encoder(writer, TagEncoder::with_types(types))
+    }
+
+    /// Create a new RecordSink that writes tags with the given encoder.
+    ///
+    /// ```
+    /// use difa::{Encoding, Record, RecordSink, TagEncoder};
+    /// use futures::SinkExt;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let mut buf = Vec::new();
+    /// let encoder = TagEncoder::new().with_encoding(Encoding::Windows1252);
+    /// let mut sink = RecordSink::with_encoder(&mut buf, encoder);
+    ///
+    /// let mut record = Record::new();
+    /// record.insert("comment", "\u{20ac}5").unwrap();
+    /// sink.send(record).await.unwrap();
+    /// sink.close().await.unwrap();
+    ///
+    /// assert_eq!(buf, b"<comment:2>\x805<eor>\n");
+    /// # })
+    /// ```
+    pub fn with_encoder(writer: W, encoder: TagEncoder) -> Self {
         Self {
-            inner: FramedWrite::new(
-                writer,
-                WriterTagEncoder(TagEncoder::with_types(types)),
-            ),
+            inner: FramedWrite::new(writer, WriterTagEncoder(encoder)),
         }
     }
 }
 
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+impl<W> RecordSink<crate::compress::Compress<W>>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Create a new RecordSink that compresses its output.
+    ///
+    /// The sink must be closed to complete the compressed data.
+    pub fn new_compressed(
+        writer: W, compression: crate::compress::Compression,
+    ) -> Self {
+        Self::new(crate::compress::Compress::new(writer, compression))
+    }
+}
+
 impl<W> Sink<Record> for RecordSink<W>
 where
     W: AsyncWrite + Unpin,
@@ -256,17 +433,10 @@ where
     fn start_send(
         mut self: Pin<&mut Self>, item: Record,
     ) -> Result<(), Self::Error> {
-        let tag = if item.is_header() {
-            WriterTag::Eoh
-        } else {
-            WriterTag::Eor
-        };
-        for (name, value) in item.fields() {
-            Pin::new(&mut self.inner)
-                .start_send(WriterTag::Field { name, value })?;
+        for tag in WriterTag::for_record(&item) {
+            Pin::new(&mut self.inner).start_send(tag)?;
         }
-
-        Pin::new(&mut self.inner).start_send(tag)
+        Ok(())
     }
 
     fn poll_flush(
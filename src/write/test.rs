@@ -10,7 +10,11 @@ use rust_decimal::Decimal;
 
 use super::{RecordSink, TagEncoder, TagSinkExt};
 use crate::test::helpers::*;
-use crate::{Datum, Field, OutputTypes, Record, RecordStream, Tag};
+use crate::{
+    DataType, Datum, Duplicates, Encoding, Error, Field, LengthUnit, Location,
+    OutputTypes, Record, RecordStream, RecordStreamExt, Tag, TagDecoder,
+    UserDef, UserDefValues,
+};
 
 #[tokio::test]
 async fn tag_sink() {
@@ -117,6 +121,85 @@ async fn encode_string() {
     encode_field("foo".into(), "<f:3:s>foo", "<f:3>foo", "<f:3>foo").await;
 }
 
+#[tokio::test]
+async fn encode_non_ascii_string() {
+    let out = "<f:2>\u{f6}";
+    encode_field("\u{f6}".into(), out, out, out).await;
+    let out = "<f:3>a\nb";
+    encode_field("a\nb".into(), out, out, out).await;
+}
+
+#[tokio::test]
+async fn encode_string_types() {
+    let s = |s: &str| s.to_string();
+    let d = Datum::IntlString(s("\u{f6}"));
+    encode_field(d, "<f:2:i>\u{f6}", "<f:2>\u{f6}", "<f:2>\u{f6}").await;
+    let d = Datum::MultilineString(s("a\nb"));
+    encode_field(d, "<f:3:m>a\nb", "<f:3>a\nb", "<f:3>a\nb").await;
+    let d = Datum::IntlMultilineString(s("\u{f6}\n"));
+    encode_field(d, "<f:3:g>\u{f6}\n", "<f:3>\u{f6}\n", "<f:3>\u{f6}\n").await;
+    let d = Datum::Enumeration(s("SSB"));
+    encode_field(d, "<f:3:e>SSB", "<f:3:e>SSB", "<f:3>SSB").await;
+}
+
+#[tokio::test]
+async fn encode_location() {
+    let d = Datum::Location(Location::parse("S012 03.450").unwrap());
+    let (typed, untyped) = ("<f:11:l>S012 03.450", "<f:11>S012 03.450");
+    encode_field(d, typed, typed, untyped).await;
+}
+
+#[tokio::test]
+async fn invalid_string_types() {
+    let s = |s: &str| s.to_string();
+    let cases = [
+        (Datum::IntlString(s("a\nb")), "IntlString"),
+        (Datum::MultilineString(s("\u{f6}")), "MultilineString"),
+        (Datum::Enumeration(s("a\nb")), "Enumeration"),
+    ];
+    for (datum, typ) in cases {
+        let field = Field::new("f", datum);
+        let mut buf = Vec::new();
+        let mut sink =
+            TagEncoder::with_types(OutputTypes::Always).tag_sink_with(&mut buf);
+        let err = sink.send(Tag::Field(field)).await.unwrap_err();
+        let reason = "value contains characters not allowed by type";
+        assert_eq!(err, cannot_output(typ, reason));
+        assert!(buf.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn encode_userdef() {
+    let values = UserDefValues::Enum(vec!["S".into(), "M".into()]);
+    let def =
+        UserDef::new("size", Some(DataType::Enumeration)).with_values(values);
+    let out = "<f:10:e>size,{S,M}";
+    encode_field(def.into(), out, out, out).await;
+    let out = "<f:4>size";
+    encode_field(UserDef::new("size", None).into(), out, out, out).await;
+}
+
+#[tokio::test]
+async fn app_names() {
+    let out = "<APP_LOTW_MODE:3>FT8";
+    let field = Field::new("APP_LOTW_MODE", "FT8");
+    assert_eq!(
+        encode_tag(Tag::Field(field), OutputTypes::Never).await,
+        out.as_bytes()
+    );
+
+    for name in ["APP_", "app_lotw", "App_LOTW_", "APP__MODE"] {
+        let field = Field::new(name, "FT8");
+        let mut buf = Vec::new();
+        let mut sink = TagEncoder::new().tag_sink_with(&mut buf);
+        let err = sink.send(Tag::Field(field)).await.unwrap_err();
+        let reason = "APP field names must be APP_{PROGRAMID}_{FIELD}";
+        assert_eq!(err, cannot_output("field", reason), "{name}");
+        assert!(buf.is_empty());
+    }
+}
+
 #[tokio::test]
 async fn datetime_errors() {
     let field = Field::new(
@@ -167,6 +250,27 @@ async fn record_sink_basic() {
     assert!(output.contains("<eor>\n"));
 }
 
+#[tokio::test]
+async fn record_sink_keep_all() {
+    let mut record = Record::new();
+    record.insert("comment", "a").unwrap();
+    record.insert("call", "W1AW").unwrap();
+    record
+        .insert_with("COMMENT", "b", Duplicates::KeepAll)
+        .unwrap();
+    record.insert("note", "x").unwrap();
+    record
+        .insert_with("note", "y", Duplicates::KeepAll)
+        .unwrap();
+    record.retain(|_, v| v.as_str() != "x");
+
+    let buf = encode_record(record, OutputTypes::Never).await;
+    assert_eq!(
+        String::from_utf8(buf).unwrap(),
+        "<comment:1>a<comment:1>b<call:4>W1AW<note:1>y<eor>\n"
+    );
+}
+
 #[tokio::test]
 async fn record_sink_header() {
     let mut header = Record::new_header();
@@ -179,6 +283,65 @@ async fn record_sink_header() {
     assert!(output.contains("<eoh>\n"));
 }
 
+#[tokio::test]
+async fn record_sink_preamble() {
+    let mut header = Record::new_header();
+    header.set_preamble("Exported by difa\nfor testing");
+    header.insert("adifver", "3.1.4").unwrap();
+    let buf = encode_record(header.clone(), OutputTypes::Never).await;
+    assert_eq!(
+        buf,
+        b"Exported by difa\nfor testing\n<adifver:5>3.1.4<eoh>\n"
+    );
+
+    let mut f = RecordStream::new(&buf[..], false);
+    assert_eq!(f.next().await.unwrap().unwrap(), header);
+    assert!(f.next().await.is_none());
+
+    // only headers have a preamble
+    let mut record = Record::new();
+    record.set_preamble("ignored");
+    record.insert("call", "W1AW").unwrap();
+    let buf = encode_record(record, OutputTypes::Never).await;
+    assert_eq!(buf, b"<call:4>W1AW<eor>\n");
+}
+
+#[tokio::test]
+async fn preamble_errors() {
+    let mut buf = Vec::new();
+    let mut sink = (&mut buf).tag_sink();
+    let err = sink
+        .send(Tag::Preamble("a <b>".to_string()))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err,
+        cannot_output("preamble", "preamble cannot contain '<'")
+    );
+
+    let mut buf = Vec::new();
+    let encoder = TagEncoder::new().with_encoding(Encoding::Latin1);
+    let mut sink = encoder.tag_sink_with(&mut buf);
+    let err = sink
+        .send(Tag::Preamble("\u{263a}".to_string()))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err,
+        cannot_output("preamble", "not representable in output encoding")
+    );
+
+    let mut header = Record::new_header();
+    header.set_preamble("<");
+    let mut buf = Vec::new();
+    let mut sink = RecordSink::new(&mut buf);
+    let err = sink.send(header).await.unwrap_err();
+    assert_eq!(
+        err,
+        cannot_output("preamble", "preamble cannot contain '<'")
+    );
+}
+
 fn create_test_record(
     call: &str, date: &str, time: &str, freq: &str, mode: &str,
 ) -> Record {
@@ -355,3 +518,107 @@ async fn poll_close_backpressure() {
     // Drive close to completion, which reaches poll_shutdown.
     sink.close().await.unwrap();
 }
+
+async fn encode_with(
+    field: Field, encoding: Encoding, length: LengthUnit,
+) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    let encoder = TagEncoder::new()
+        .with_encoding(encoding)
+        .with_length_unit(length);
+    let mut sink = encoder.tag_sink_with(&mut buf);
+    sink.send(Tag::Field(field)).await?;
+    sink.close().await?;
+    Ok(buf)
+}
+
+#[tokio::test]
+async fn encode_encodings() {
+    let field = Field::new("name", "J\u{fc}rgen \u{20ac}");
+    let cases: [(Encoding, LengthUnit, &[u8]); 4] = [
+        (
+            Encoding::Utf8,
+            LengthUnit::Bytes,
+            b"<name:11>J\xC3\xBCrgen \xE2\x82\xAC",
+        ),
+        (
+            Encoding::Utf8,
+            LengthUnit::Chars,
+            b"<name:8>J\xC3\xBCrgen \xE2\x82\xAC",
+        ),
+        (
+            Encoding::Windows1252,
+            LengthUnit::Bytes,
+            b"<name:8>J\xFCrgen \x80",
+        ),
+        (
+            Encoding::Windows1252,
+            LengthUnit::Chars,
+            b"<name:8>J\xFCrgen \x80",
+        ),
+    ];
+    for (encoding, length, expected) in cases {
+        let out = encode_with(field.clone(), encoding, length).await.unwrap();
+        assert_eq!(out, expected);
+    }
+
+    let field = Field::new("\u{c4}", "\u{e9}");
+    let out = encode_with(field, Encoding::Latin1, LengthUnit::Bytes)
+        .await
+        .unwrap();
+    assert_eq!(out, b"<\xC4:1>\xE9");
+}
+
+#[tokio::test]
+async fn encode_unrepresentable() {
+    let cases = [
+        (Encoding::Latin1, Field::new("c", "\u{20ac}")),
+        (Encoding::Latin1, Field::new("\u{20ac}", "c")),
+        (Encoding::Windows1252, Field::new("c", "\u{263a}")),
+        (Encoding::Windows1252, Field::new("c", "\u{80}")),
+    ];
+    for (encoding, field) in cases {
+        let err = encode_with(field, encoding, LengthUnit::Bytes)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            cannot_output("field", "not representable in output encoding")
+        );
+    }
+}
+
+#[tokio::test]
+async fn encoding_roundtrip() {
+    let cases = [
+        (Encoding::Utf8, "\u{201c}73\u{201d} \u{263a}"),
+        (Encoding::Latin1, "\u{0}\u{85}\u{ff}"),
+        (Encoding::Windows1252, "\u{201c}73\u{201d} \u{ff}"),
+    ];
+    for (encoding, comment) in cases {
+        let mut record = Record::new();
+        record.insert("name", "J\u{fc}rgen").unwrap();
+        record.insert("comment", comment).unwrap();
+        record
+            .insert("freq", Decimal::from_str("14.074").unwrap())
+            .unwrap();
+
+        for length in [LengthUnit::Bytes, LengthUnit::Chars] {
+            let mut buf = Vec::new();
+            let encoder = TagEncoder::with_types(OutputTypes::OnlyNonString)
+                .with_encoding(encoding)
+                .with_length_unit(length);
+            let mut sink = RecordSink::with_encoder(&mut buf, encoder);
+            sink.send(record.clone()).await.unwrap();
+            sink.close().await.unwrap();
+
+            let mut stream = TagDecoder::new(false)
+                .with_encoding(encoding)
+                .with_length_unit(length)
+                .tag_stream_with(&buf[..])
+                .records();
+            assert_eq!(stream.next().await.unwrap().unwrap(), record);
+            assert!(stream.next().await.is_none());
+        }
+    }
+}
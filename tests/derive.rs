@@ -0,0 +1,236 @@
+use chrono::{NaiveDate, NaiveTime};
+use difa::{
+    Datum, Duplicates, Error, Location, OutputTypes, Record, RecordSink,
+    RecordStream,
+};
+use difa_derive::AdifRecord;
+use futures::{SinkExt, StreamExt};
+use rust_decimal::Decimal;
+
+#[derive(Debug, PartialEq, AdifRecord)]
+struct Contact {
+    call: String,
+    #[adif(name = "qso_date", type = "d")]
+    date: NaiveDate,
+    time_on: Option<NaiveTime>,
+    freq: Option<Decimal>,
+    #[adif(type = "e")]
+    mode: Option<String>,
+    dxcc: Option<u16>,
+    #[adif(name = "qsl_rcvd")]
+    confirmed: Option<bool>,
+    #[adif(name = "lat")]
+    location: Option<Location>,
+    #[adif(extra)]
+    rest: Record,
+}
+
+#[derive(Debug, PartialEq, AdifRecord)]
+struct Minimal {
+    r#call: String,
+    #[adif(type = "n")]
+    srx: Option<String>,
+}
+
+#[derive(Debug, PartialEq, AdifRecord)]
+struct Generic<T>
+where
+    T: difa::FromDatum + Into<Datum>,
+{
+    call: T,
+    #[adif(extra)]
+    rest: Record,
+}
+
+async fn parse(data: &str) -> Record {
+    let mut s = RecordStream::new(data.as_bytes(), true);
+    s.next().await.unwrap().unwrap()
+}
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[tokio::test]
+async fn read() {
+    let rec = parse(
+        "<CALL:4>W1AW<qso_date:8>20240115<time_on:4>1234<freq:6>14.074\
+         <mode:3>FT8<dxcc:3>291<qsl_rcvd:1>Y<lat:11>N041 42.860\
+         <sig:4>POTA<eor>",
+    )
+    .await;
+    let contact = Contact::try_from(rec).unwrap();
+    assert_eq!(contact.call, "W1AW");
+    assert_eq!(contact.date, date(2024, 1, 15));
+    assert_eq!(contact.time_on, NaiveTime::from_hms_opt(12, 34, 0));
+    assert_eq!(contact.freq, Some(Decimal::new(14074, 3)));
+    assert_eq!(contact.mode.as_deref(), Some("FT8"));
+    assert_eq!(contact.dxcc, Some(291));
+    assert_eq!(contact.confirmed, Some(true));
+    assert!(contact.location.is_some());
+    let rest: Vec<_> = contact.rest.fields().map(|(k, _)| k).collect();
+    assert_eq!(rest, ["sig"]);
+}
+
+#[tokio::test]
+async fn absent_and_empty() {
+    let rec = parse("<call:4>W1AW<qso_date:8>20240115<freq:0><eor>").await;
+    let contact = Contact::try_from(rec).unwrap();
+    assert_eq!(contact.freq, None);
+    assert_eq!(contact.dxcc, None);
+    let rest: Vec<_> = contact.rest.fields().map(|(k, _)| k).collect();
+    assert!(rest.is_empty());
+}
+
+#[tokio::test]
+async fn missing_field() {
+    let rec = parse("<qso_date:8>20240115<eor>").await;
+    let err = Contact::try_from(rec.clone()).unwrap_err();
+    let Error::MissingField {
+        field,
+        record,
+        span,
+    } = err
+    else {
+        panic!("expected missing field");
+    };
+    assert_eq!(field, "call");
+    assert_eq!(*record, rec);
+    assert_eq!(span, rec.span());
+
+    let rec = parse("<call:4>W1AW<qso_date:1> <eor>").await;
+    let err = Contact::try_from(rec).unwrap_err();
+    assert!(
+        matches!(err, Error::MissingField { field, .. } if field == "qso_date")
+    );
+}
+
+#[tokio::test]
+async fn invalid_value() {
+    let cases = [
+        ("<qso_date:8>20241315", "qso_date", "20241315"),
+        ("<qso_date:8>20240115<freq:3>abc", "freq", "abc"),
+        ("<qso_date:8>20240115<dxcc:5>70000", "dxcc", "70000"),
+        ("<qso_date:8>20240115<qsl_rcvd:1>R", "qsl_rcvd", "R"),
+        ("<qso_date:8>20240115<time_on:4>2500", "time_on", "2500"),
+    ];
+    for (fields, name, value) in cases {
+        let data = format!("<call:4>W1AW{fields}<eor>");
+        let rec = parse(&data).await;
+        let err = Contact::try_from(rec.clone()).unwrap_err();
+        let Error::InvalidValue {
+            field,
+            value: v,
+            record,
+            span,
+        } = err
+        else {
+            panic!("expected invalid value for {data}");
+        };
+        assert_eq!((field.as_str(), v.as_str()), (name, value), "{data}");
+        assert_eq!(*record, rec);
+        assert_eq!(span, rec.field_span(name));
+        assert!(span.is_some());
+    }
+}
+
+#[tokio::test]
+async fn type_indicator() {
+    let rec = parse("<call:4>W1AW<srx:3>042<eor>").await;
+    let minimal = Minimal::try_from(rec).unwrap();
+    assert_eq!(minimal.srx.as_deref(), Some("42"));
+    let rec = Record::from(minimal);
+    assert_eq!(rec.get("srx"), Some(&Datum::Number(Decimal::from(42))));
+
+    let rec = parse("<call:4>W1AW<srx:3>abc<eor>").await;
+    assert!(matches!(
+        Minimal::try_from(rec),
+        Err(Error::InvalidValue { field, .. }) if field == "srx"
+    ));
+
+    let rec = Record::from(Minimal {
+        call: "W1AW".into(),
+        srx: Some("abc".into()),
+    });
+    assert_eq!(rec.get("srx"), Some(&Datum::String("abc".into())));
+}
+
+#[test]
+fn write() {
+    let mut rest = Record::new();
+    rest.insert("sig", "POTA").unwrap();
+    rest.insert("freq", "7.074").unwrap();
+    let contact = Contact {
+        call: "W1AW".into(),
+        date: date(2024, 1, 15),
+        time_on: None,
+        freq: Some(Decimal::new(14074, 3)),
+        mode: Some("FT8".into()),
+        dxcc: Some(291),
+        confirmed: Some(false),
+        location: None,
+        rest,
+    };
+    let rec = Record::from(contact);
+    let fields: Vec<_> = rec.fields().collect();
+    assert_eq!(
+        fields,
+        [
+            ("sig", &Datum::String("POTA".into())),
+            ("freq", &Datum::Number(Decimal::new(14074, 3))),
+            ("call", &Datum::String("W1AW".into())),
+            ("qso_date", &Datum::Date(date(2024, 1, 15))),
+            ("mode", &Datum::Enumeration("FT8".into())),
+            ("dxcc", &Datum::Number(Decimal::from(291))),
+            ("qsl_rcvd", &Datum::Boolean(false)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn round_trip() {
+    let data = "<call:4>W1AW<qso_date:8>20240115<time_on:6>123456\
+                <freq:6>14.074<mode:3>FT8<app_n1mm_id:1>7<eor>\n";
+    let contact = Contact::try_from(parse(data).await).unwrap();
+
+    let mut buf = Vec::new();
+    let mut sink = RecordSink::with_types(&mut buf, OutputTypes::Never);
+    sink.send(contact.into()).await.unwrap();
+    sink.close().await.unwrap();
+    let written = String::from_utf8(buf).unwrap();
+
+    let again = Contact::try_from(parse(&written).await).unwrap();
+    assert_eq!(again, Contact::try_from(parse(data).await).unwrap());
+}
+
+#[tokio::test]
+async fn keep_all() {
+    let data = "<call:4>W1AW<qso_date:8>20240115<sig:4>POTA<sig:4>SOTA\
+                <call:6>W1AW/P<eor>\n";
+    let mut s = RecordStream::new(data.as_bytes(), true)
+        .with_duplicates(Duplicates::KeepAll);
+    let contact = Contact::try_from(s.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(contact.call, "W1AW");
+
+    let mut buf = Vec::new();
+    let mut sink = RecordSink::with_types(&mut buf, OutputTypes::Never);
+    sink.send(contact.into()).await.unwrap();
+    sink.close().await.unwrap();
+    let written = String::from_utf8(buf).unwrap();
+    assert_eq!(
+        written,
+        "<sig:4>POTA<sig:4>SOTA<call:4>W1AW<call:6>W1AW/P\
+         <qso_date:8>20240115<eor>\n"
+    );
+}
+
+#[test]
+fn generic() {
+    let mut rec = Record::new();
+    rec.insert("call", "W1AW").unwrap();
+    rec.insert("band", "20m").unwrap();
+    let g = Generic::<String>::try_from(rec.clone()).unwrap();
+    assert_eq!(g.call, "W1AW");
+    assert_eq!(g.rest.fields().count(), 1);
+    assert_eq!(Record::from(g), rec);
+}
@@ -1,12 +1,15 @@
 use chrono::{Days, NaiveDate, NaiveTime};
-use difa::{Datum, Error, OutputTypes, Record, RecordSink, RecordStream};
+use difa::{
+    AdxSink, Datum, Error, Location, OutputTypes, Record, RecordSink,
+    RecordStream,
+};
 use futures::{SinkExt, StreamExt};
 use proptest::prelude::*;
 use rust_decimal::Decimal;
 
 fn field_name_strategy() -> impl Strategy<Value = String> {
-    prop::char::ranges(vec![('a'..='z'), ('A'..='Z')].into()).prop_flat_map(
-        |first| {
+    prop::char::ranges(vec![('a'..='z'), ('A'..='Z')].into())
+        .prop_flat_map(|first| {
             prop::collection::vec(
                 prop::char::ranges(
                     vec![('a'..='z'), ('A'..='Z'), ('0'..='9'), ('_'..='_')]
@@ -20,8 +23,31 @@ fn field_name_strategy() -> impl Strategy<Value = String> {
                 s.extend(rest);
                 s
             })
-        },
-    )
+        })
+        // writers reject APP_ names that are not APP_{PROGRAMID}_{FIELD}
+        .prop_filter("malformed APP_ name", |s| {
+            let s = s.to_ascii_lowercase();
+            let Some(rest) = s.strip_prefix("app_") else {
+                return true;
+            };
+            rest.split_once('_')
+                .is_some_and(|(p, f)| !p.is_empty() && !f.is_empty())
+        })
+        // header fields so named are read back as declarations
+        .prop_filter("USERDEFn name", |s| {
+            let s = s.to_ascii_lowercase();
+            !s.strip_prefix("userdef")
+                .is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit()))
+        })
+}
+
+fn adx_field_name_strategy() -> impl Strategy<Value = String> {
+    // ADX writes APP_ fields as APP elements, which read back as other
+    // names, and reads any APP element that way
+    field_name_strategy().prop_filter("APP name", |s| {
+        let s = s.to_ascii_lowercase();
+        !s.starts_with("app_") && s != "app"
+    })
 }
 
 fn string_datum_strategy() -> impl Strategy<Value = Datum> {
@@ -40,6 +66,53 @@ fn string_datum_strategy_for_whitespace() -> impl Strategy<Value = Datum> {
     .prop_map(|v| Datum::String(v.into_iter().collect()))
 }
 
+fn printable_string_strategy() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::char::range(' ', '~'), 0..20)
+        .prop_map(|v| v.into_iter().collect())
+}
+
+fn string_types_datum_strategy() -> impl Strategy<Value = Datum> {
+    prop_oneof![
+        any::<String>()
+            .prop_filter("line break", |s| !s.contains(['\r', '\n']))
+            .prop_map(Datum::IntlString),
+        prop::collection::vec(
+            prop_oneof![
+                prop::char::range(' ', '~'),
+                prop::sample::select(vec!['\r', '\n']),
+            ],
+            0..20,
+        )
+        .prop_map(|v| Datum::MultilineString(v.into_iter().collect())),
+        any::<String>().prop_map(Datum::IntlMultilineString),
+    ]
+}
+
+fn enumeration_datum_strategy(
+    whitespace: bool,
+) -> impl Strategy<Value = Datum> {
+    if whitespace {
+        string_datum_strategy_for_whitespace()
+            .prop_map(|d| Datum::Enumeration(d.as_str().into_owned()))
+            .boxed()
+    } else {
+        printable_string_strategy()
+            .prop_map(Datum::Enumeration)
+            .boxed()
+    }
+}
+
+fn location_datum_strategy() -> impl Strategy<Value = Datum> {
+    (
+        prop::sample::select(vec!['N', 'S', 'E', 'W']),
+        0u8..=180,
+        0i64..60000,
+    )
+        .prop_filter_map("valid location", |(dir, deg, min)| {
+            Location::new(dir, deg, Decimal::new(min, 3)).map(Datum::Location)
+        })
+}
+
 fn boolean_datum_strategy() -> impl Strategy<Value = Datum> {
     any::<bool>().prop_map(Datum::Boolean)
 }
@@ -71,6 +144,8 @@ fn datum_strategy(whitespace: bool) -> impl Strategy<Value = Datum> {
             number_datum_strategy(),
             date_datum_strategy(),
             time_datum_strategy(),
+            enumeration_datum_strategy(true),
+            location_datum_strategy(),
         ]
         .boxed()
     } else {
@@ -80,39 +155,45 @@ fn datum_strategy(whitespace: bool) -> impl Strategy<Value = Datum> {
             number_datum_strategy(),
             date_datum_strategy(),
             time_datum_strategy(),
+            enumeration_datum_strategy(false),
+            location_datum_strategy(),
         ]
         .boxed()
     }
 }
 
 fn record_strategy(whitespace: bool) -> impl Strategy<Value = Record> {
-    prop::collection::hash_map(
-        field_name_strategy(),
-        datum_strategy(whitespace),
-        0..=10,
-    )
-    .prop_map(|fields| {
-        let mut record = Record::new();
-        for (name, datum) in fields {
-            let _ = record.insert(name, datum);
-        }
-        record
-    })
+    record_strategy_with(field_name_strategy(), whitespace)
+}
+
+fn record_strategy_with(
+    names: impl Strategy<Value = String>, whitespace: bool,
+) -> impl Strategy<Value = Record> {
+    prop::collection::hash_map(names, datum_strategy(whitespace), 0..=10)
+        .prop_map(|fields| {
+            let mut record = Record::new();
+            for (name, datum) in fields {
+                let _ = record.insert(name, datum);
+            }
+            record
+        })
 }
 
 fn header_strategy(whitespace: bool) -> impl Strategy<Value = Record> {
-    prop::collection::hash_map(
-        field_name_strategy(),
-        datum_strategy(whitespace),
-        0..=3,
-    )
-    .prop_map(|fields| {
-        let mut record = Record::new_header();
-        for (name, datum) in fields {
-            let _ = record.insert(name, datum);
-        }
-        record
-    })
+    header_strategy_with(field_name_strategy(), whitespace)
+}
+
+fn header_strategy_with(
+    names: impl Strategy<Value = String>, whitespace: bool,
+) -> impl Strategy<Value = Record> {
+    prop::collection::hash_map(names, datum_strategy(whitespace), 0..=3)
+        .prop_map(|fields| {
+            let mut record = Record::new_header();
+            for (name, datum) in fields {
+                let _ = record.insert(name, datum);
+            }
+            record
+        })
 }
 
 fn output_types_strategy() -> impl Strategy<Value = OutputTypes> {
@@ -206,6 +287,18 @@ fn assert_field_equals_coerced(parsed: &Datum, original: &Datum) {
         Datum::DateTime(_) => {
             unreachable!("DateTime should not be in test data");
         }
+        Datum::IntlString(s)
+        | Datum::MultilineString(s)
+        | Datum::IntlMultilineString(s)
+        | Datum::Enumeration(s) => {
+            assert_eq!(parsed.as_str().as_ref(), s);
+        }
+        Datum::Location(l) => {
+            assert_eq!(parsed.as_location().unwrap(), *l);
+        }
+        Datum::UserDef(_) => {
+            unreachable!("UserDef should not be in test data");
+        }
     }
 }
 
@@ -232,6 +325,27 @@ async fn test_roundtrip_never(header: Record, records: Vec<Record>) {
     assert!(stream.next().await.is_none());
 }
 
+async fn test_roundtrip_adx(header: Record, records: Vec<Record>) {
+    let mut buf = Vec::new();
+    let mut sink = AdxSink::new(&mut buf);
+    sink.send(header.clone()).await.unwrap();
+    for record in &records {
+        sink.send(record.clone()).await.unwrap();
+    }
+    sink.close().await.unwrap();
+
+    let mut stream = RecordStream::new_adx(&buf[..], false);
+    let parsed_header = stream.next().await.unwrap().unwrap();
+    assert!(parsed_header.is_header());
+    assert_records_equal_coerced(&parsed_header, &header);
+    for record in records {
+        let parsed = stream.next().await.unwrap().unwrap();
+        assert!(!parsed.is_header());
+        assert_records_equal_coerced(&parsed, &record);
+    }
+    assert!(stream.next().await.is_none());
+}
+
 async fn test_whitespace(
     header: Record, records: Vec<Record>, output_types: OutputTypes,
     whitespace_injections: Vec<(usize, Vec<u8>)>,
@@ -296,6 +410,19 @@ proptest! {
         tokio_test::block_on(test_roundtrip(header, records, output_types));
     }
 
+    #[test]
+    fn roundtrip_string_types(
+        fields in prop::collection::vec(string_types_datum_strategy(), 0..=10)
+    ) {
+        let mut record = Record::new();
+        for (i, datum) in fields.into_iter().enumerate() {
+            record.insert(format!("f{i}"), datum).unwrap();
+        }
+        let header = Record::new_header();
+        let test = test_roundtrip(header, vec![record], OutputTypes::Always);
+        tokio_test::block_on(test);
+    }
+
     #[test]
     fn roundtrip_untyped(
         header in header_strategy(false),
@@ -304,6 +431,17 @@ proptest! {
         tokio_test::block_on(test_roundtrip_never(header, records));
     }
 
+    #[test]
+    fn roundtrip_adx(
+        header in header_strategy_with(adx_field_name_strategy(), false),
+        records in prop::collection::vec(
+            record_strategy_with(adx_field_name_strategy(), false),
+            0..=20,
+        )
+    ) {
+        tokio_test::block_on(test_roundtrip_adx(header, records));
+    }
+
     #[test]
     fn roundtrip_with_whitespace(
         header in header_strategy(true),